@@ -1,7 +1,28 @@
-use grammar_lib::{extract_vocabulary, KagomeToken, PatternCategory, VocabWord};
+use grammar_lib::explain::{explain, Explanation};
+use grammar_lib::{
+    extract_vocabulary, list_patterns, pattern_text, AnalysisResult, KagomeToken, PatternCategory,
+    VocabWord,
+};
+use kagome_client::KagomeServer;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::io::{self, Read};
 
+/// Port for the CLI's own Kagome server (the app and tests use 6061)
+const KAGOME_PORT: u16 = 6062;
+
+const USAGE: &str = "Usage:
+  grammar-cli [--with-vocabulary]              analyze a JSON token array from stdin
+  grammar-cli analyze [TEXT]                   analyze raw text (argument or stdin)
+  grammar-cli batch [FILE]                     analyze JSONL ({\"text\": ...} or plain lines) or .srt, one result per line
+  grammar-cli patterns list [--level N3] [--category construction|conjugation]
+  grammar-cli vocab [FILE] [--limit N]         frequency-ranked vocabulary
+  grammar-cli explain <pattern_name>           explain matching against a JSON token array from stdin
+
+Options:
+  --format json|table   output format (default: json, table for explain; --json is shorthand)
+  --with-vocabulary     include extracted vocabulary in analysis output";
+
 #[derive(Debug, Serialize, Deserialize)]
 struct PatternMatch {
     pattern_name: String,
@@ -9,6 +30,13 @@ struct PatternMatch {
     start_char: u32,
     end_char: u32,
     category: String,
+    alternatives: Vec<PatternAlternative>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PatternAlternative {
+    pattern_name: String,
+    confidence: f32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,28 +51,383 @@ struct AnalysisOutput {
     tokens: Vec<KagomeToken>,
     grammar_matches: Vec<PatternMatch>,
     compound_spans: Vec<CompoundSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vocabulary: Option<Vec<VocabWord>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct AnalysisWithVocabulary {
-    tokens: Vec<KagomeToken>,
-    grammar_matches: Vec<PatternMatch>,
-    compound_spans: Vec<CompoundSpan>,
-    vocabulary: Vec<VocabWord>,
+/// One line of `batch` output
+#[derive(Debug, Serialize)]
+struct BatchOutput {
+    line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<serde_json::Value>,
+    text: String,
+    #[serde(flatten)]
+    analysis: AnalysisOutput,
+}
+
+#[derive(Debug, Serialize)]
+struct VocabEntry {
+    #[serde(flatten)]
+    word: VocabWord,
+    /// Number of input lines containing the word
+    count: usize,
+}
+
+/// A line of text to analyze, with an optional caller-supplied id (JSONL) or cue number (SRT)
+#[derive(Debug, Deserialize)]
+struct InputLine {
+    #[serde(default)]
+    id: Option<serde_json::Value>,
+    text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Table,
+}
+
+#[derive(Debug)]
+struct CliArgs {
+    positional: Vec<String>,
+    /// None means the command's default (JSON, except `explain`)
+    format: Option<Format>,
+    level: Option<String>,
+    category: Option<String>,
+    limit: Option<usize>,
+    with_vocabulary: bool,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let with_vocabulary = std::env::args().any(|arg| arg == "--with-vocabulary");
+    let args = parse_args(std::env::args().skip(1).collect())?;
 
-    let mut input = String::new();
-    io::stdin().read_to_string(&mut input)?;
+    let Some(command) = args.positional.first() else {
+        return run_tokens(&args);
+    };
+
+    match command.as_str() {
+        "analyze" => run_analyze(&args),
+        "batch" => run_batch(&args),
+        "patterns" => run_patterns(&args),
+        "vocab" => run_vocab(&args),
+        "explain" => run_explain(&args),
+        "help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(format!("Unknown command: {}\n\n{}", other, USAGE).into()),
+    }
+}
+
+fn parse_args(raw: Vec<String>) -> Result<CliArgs, Box<dyn std::error::Error>> {
+    let mut args = CliArgs {
+        positional: Vec::new(),
+        format: None,
+        level: None,
+        category: None,
+        limit: None,
+        with_vocabulary: false,
+    };
+
+    let mut iter = raw.into_iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .ok_or_else(|| format!("Missing value for {}", name))
+        };
+
+        match arg.as_str() {
+            "--json" => args.format = Some(Format::Json),
+            "--with-vocabulary" => args.with_vocabulary = true,
+            "--format" => {
+                args.format = Some(match value("--format")?.as_str() {
+                    "json" => Format::Json,
+                    "table" => Format::Table,
+                    other => return Err(format!("Unknown format: {}", other).into()),
+                })
+            }
+            "--level" => args.level = Some(value("--level")?.to_lowercase()),
+            "--category" => args.category = Some(value("--category")?.to_lowercase()),
+            "--limit" => args.limit = Some(value("--limit")?.parse()?),
+            "-h" | "--help" => args.positional.insert(0, "help".to_string()),
+            flag if flag.starts_with("--") => {
+                return Err(format!("Unknown option: {}\n\n{}", flag, USAGE).into())
+            }
+            _ => args.positional.push(arg),
+        }
+    }
 
-    let tokens: Vec<KagomeToken> = serde_json::from_str(&input)?;
+    Ok(args)
+}
+
+// ========== Commands ==========
+
+/// Original mode: pre-tokenized JSON array on stdin, text reconstructed from surfaces
+fn run_tokens(args: &CliArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let tokens = read_stdin_tokens()?;
     let text: String = tokens.iter().map(|t| t.surface.as_str()).collect();
 
     let result = grammar_lib::analyze(&text, &tokens);
+    print_analysis(&text, result, args)
+}
+
+/// Raw text from the arguments or stdin, tokenized through Kagome so whitespace is kept
+fn run_analyze(args: &CliArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let text = if args.positional.len() > 1 {
+        args.positional[1..].join(" ")
+    } else {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        input.trim_end_matches('\n').to_string()
+    };
+
+    let server = KagomeServer::start(KAGOME_PORT)?;
+    let tokens = tokenize(&server, &text)?;
+
+    let result = grammar_lib::analyze(&text, &tokens);
+    print_analysis(&text, result, args)
+}
+
+fn run_batch(args: &CliArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let lines = read_input_lines(args.positional.get(1))?;
+    let server = KagomeServer::start(KAGOME_PORT)?;
+
+    for (index, line) in lines.into_iter().enumerate() {
+        let tokens = tokenize(&server, &line.text)?;
+        let result = grammar_lib::analyze(&line.text, &tokens);
+
+        match args.format.unwrap_or(Format::Json) {
+            Format::Json => {
+                let output = BatchOutput {
+                    line: index + 1,
+                    id: line.id,
+                    analysis: to_output(result, args.with_vocabulary),
+                    text: line.text,
+                };
+                println!("{}", serde_json::to_string(&output)?);
+            }
+            Format::Table => {
+                println!("#{}", index + 1);
+                print_analysis_table(&line.text, &result, args.with_vocabulary);
+                println!();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_patterns(args: &CliArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if args.positional.get(1).is_none_or(|sub| sub != "list") {
+        return Err(format!("Usage: grammar-cli patterns list\n\n{}", USAGE).into());
+    }
+
+    let patterns: Vec<_> = list_patterns()
+        .into_iter()
+        .filter(|p| {
+            args.level
+                .as_ref()
+                .is_none_or(|level| p.jlpt_level == level)
+        })
+        .filter(|p| {
+            args.category
+                .as_ref()
+                .is_none_or(|category| category_name(p.category).eq_ignore_ascii_case(category))
+        })
+        .collect();
+
+    match args.format.unwrap_or(Format::Json) {
+        Format::Json => println!("{}", serde_json::to_string(&patterns)?),
+        Format::Table => {
+            println!(
+                "{:<36} {:<5} {:<13} {:>8}",
+                "pattern", "level", "category", "priority"
+            );
+            for p in &patterns {
+                println!(
+                    "{:<36} {:<5} {:<13} {:>8}",
+                    p.name,
+                    p.jlpt_level,
+                    category_name(p.category),
+                    p.priority
+                );
+            }
+            println!("\n{} patterns", patterns.len());
+        }
+    }
+
+    Ok(())
+}
+
+fn run_vocab(args: &CliArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let lines = read_input_lines(args.positional.get(1))?;
+    let server = KagomeServer::start(KAGOME_PORT)?;
+
+    // Keep first-seen order so ties are stable
+    let mut counts: HashMap<VocabWord, usize> = HashMap::new();
+    let mut order = Vec::new();
+
+    for line in &lines {
+        let tokens = tokenize(&server, &line.text)?;
+        let result = grammar_lib::analyze(&line.text, &tokens);
+
+        for word in extract_vocabulary(&result.tokens) {
+            let count = counts.entry(word.clone()).or_insert(0);
+            if *count == 0 {
+                order.push(word);
+            }
+            *count += 1;
+        }
+    }
 
-    let grammar_output: Vec<PatternMatch> = result
+    let mut entries: Vec<VocabEntry> = order
+        .into_iter()
+        .map(|word| VocabEntry {
+            count: counts[&word],
+            word,
+        })
+        .collect();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.count));
+    if let Some(limit) = args.limit {
+        entries.truncate(limit);
+    }
+
+    match args.format.unwrap_or(Format::Json) {
+        Format::Json => println!("{}", serde_json::to_string(&entries)?),
+        Format::Table => {
+            println!("{:>6}  {:<16} {:<16} pos", "lines", "word", "reading");
+            for entry in &entries {
+                println!(
+                    "{:>6}  {:<16} {:<16} {}",
+                    entry.count,
+                    entry.word.base_form,
+                    entry.word.reading,
+                    entry.word.pos.first().map(String::as_str).unwrap_or("")
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_explain(args: &CliArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let pattern_name = args
+        .positional
+        .get(1)
+        .ok_or("Usage: grammar-cli explain <pattern_name>")?;
+    let tokens = read_stdin_tokens()?;
+
+    let explanation = explain(pattern_name, &tokens)
+        .ok_or_else(|| format!("Unknown pattern: {}", pattern_name))?;
+
+    match args.format.unwrap_or(Format::Table) {
+        Format::Json => println!("{}", serde_json::to_string(&explanation)?),
+        Format::Table => print_explanation(&explanation, &tokens),
+    }
+
+    Ok(())
+}
+
+// ========== Input ==========
+
+fn read_stdin_tokens() -> Result<Vec<KagomeToken>, Box<dyn std::error::Error>> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    Ok(serde_json::from_str(&input)?)
+}
+
+/// Read lines to analyze from a file (or stdin). `.srt` files yield one line per cue;
+/// otherwise each non-empty line is either a JSON object with a `text` field or plain text.
+fn read_input_lines(path: Option<&String>) -> Result<Vec<InputLine>, Box<dyn std::error::Error>> {
+    let content = match path {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            input
+        }
+    };
+
+    if path.is_some_and(|p| p.to_lowercase().ends_with(".srt")) {
+        return Ok(parse_srt(&content));
+    }
+
+    let mut lines = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('{') {
+            let input: InputLine = serde_json::from_str(line)
+                .map_err(|e| format!("Invalid JSON on line {}: {}", line_number + 1, e))?;
+            lines.push(input);
+        } else {
+            lines.push(InputLine {
+                id: None,
+                text: line.to_string(),
+            });
+        }
+    }
+
+    Ok(lines)
+}
+
+/// Minimal SRT reader: cue number becomes the id, timing lines are dropped
+fn parse_srt(content: &str) -> Vec<InputLine> {
+    let content = content.trim_start_matches('\u{feff}').replace('\r', "");
+
+    content
+        .split("\n\n")
+        .filter_map(|block| {
+            let lines: Vec<&str> = block.lines().map(str::trim).collect();
+            let timing = lines.iter().position(|l| l.contains("-->"))?;
+            let text = lines[timing + 1..]
+                .iter()
+                .filter(|l| !l.is_empty())
+                .copied()
+                .collect::<Vec<_>>()
+                .join("\n");
+            if text.is_empty() {
+                return None;
+            }
+
+            let id = timing
+                .checked_sub(1)
+                .and_then(|i| lines[i].parse::<u64>().ok())
+                .map(serde_json::Value::from);
+            Some(InputLine { id, text })
+        })
+        .collect()
+}
+
+fn tokenize(
+    server: &KagomeServer,
+    text: &str,
+) -> Result<Vec<KagomeToken>, Box<dyn std::error::Error>> {
+    let tokens: Vec<_> = server
+        .tokenize(text, "search")?
+        .into_iter()
+        .filter(|t| !t.surface.trim().is_empty())
+        .collect();
+
+    // Convert kagome_client::KagomeToken -> grammar_lib::KagomeToken via serde
+    Ok(serde_json::from_value(serde_json::to_value(tokens)?)?)
+}
+
+// ========== Output ==========
+
+fn category_name(category: PatternCategory) -> &'static str {
+    match category {
+        PatternCategory::Construction => "Construction",
+        PatternCategory::Conjugation => "Conjugation",
+    }
+}
+
+fn to_output(result: AnalysisResult, with_vocabulary: bool) -> AnalysisOutput {
+    let grammar_matches = result
         .grammar_matches
         .iter()
         .map(|m| PatternMatch {
@@ -52,14 +435,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             confidence: m.confidence,
             start_char: m.start_char,
             end_char: m.end_char,
-            category: match m.category {
-                PatternCategory::Construction => "Construction".to_string(),
-                PatternCategory::Conjugation => "Conjugation".to_string(),
-            },
+            category: category_name(m.category).to_string(),
+            alternatives: m
+                .alternatives
+                .iter()
+                .map(|a| PatternAlternative {
+                    pattern_name: a.pattern_name.to_string(),
+                    confidence: a.confidence,
+                })
+                .collect(),
         })
         .collect();
 
-    let compound_output: Vec<CompoundSpan> = result
+    let compound_spans = result
         .compound_spans
         .iter()
         .map(|c| CompoundSpan {
@@ -69,23 +457,115 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         })
         .collect();
 
-    if with_vocabulary {
-        let vocabulary = extract_vocabulary(&result.tokens);
-        let output = AnalysisWithVocabulary {
-            tokens: result.tokens,
-            grammar_matches: grammar_output,
-            compound_spans: compound_output,
-            vocabulary,
-        };
-        println!("{}", serde_json::to_string(&output)?);
-    } else {
-        let output = AnalysisOutput {
-            tokens: result.tokens,
-            grammar_matches: grammar_output,
-            compound_spans: compound_output,
-        };
-        println!("{}", serde_json::to_string(&output)?);
+    let vocabulary = with_vocabulary.then(|| extract_vocabulary(&result.tokens));
+
+    AnalysisOutput {
+        tokens: result.tokens,
+        grammar_matches,
+        compound_spans,
+        vocabulary,
     }
+}
 
+fn print_analysis(
+    text: &str,
+    result: AnalysisResult,
+    args: &CliArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match args.format.unwrap_or(Format::Json) {
+        Format::Json => {
+            let output = to_output(result, args.with_vocabulary);
+            println!("{}", serde_json::to_string(&output)?);
+        }
+        Format::Table => print_analysis_table(text, &result, args.with_vocabulary),
+    }
     Ok(())
 }
+
+fn print_analysis_table(text: &str, result: &AnalysisResult, with_vocabulary: bool) {
+    println!("{}", text);
+    println!(
+        "  {:<32} {:<5} {:<13} {:>7} {:>6}  text",
+        "pattern", "level", "category", "span", "conf"
+    );
+
+    for m in &result.grammar_matches {
+        let alternatives: Vec<_> = m.alternatives.iter().map(|a| a.pattern_name).collect();
+        println!(
+            "  {:<32} {:<5} {:<13} {:>7} {:>6.2}  {}{}",
+            m.pattern_name,
+            grammar_lib::get_jlpt_level(m.pattern_name),
+            category_name(m.category),
+            format!("{}-{}", m.start_char, m.end_char),
+            m.confidence,
+            pattern_text(text, m),
+            if alternatives.is_empty() {
+                String::new()
+            } else {
+                format!("  (possibly {})", alternatives.join(", "))
+            }
+        );
+    }
+
+    if !result.compound_spans.is_empty() {
+        let compounds: Vec<_> = result
+            .compound_spans
+            .iter()
+            .map(|c| c.text.as_str())
+            .collect();
+        println!("  compounds: {}", compounds.join(", "));
+    }
+
+    if with_vocabulary {
+        let vocabulary: Vec<_> = extract_vocabulary(&result.tokens)
+            .into_iter()
+            .map(|w| format!("{}({})", w.base_form, w.reading))
+            .collect();
+        println!("  vocabulary: {}", vocabulary.join(", "));
+    }
+}
+
+fn print_explanation(explanation: &Explanation, tokens: &[KagomeToken]) {
+    println!("Pattern: {}", explanation.pattern_name);
+    for (i, step) in explanation.steps.iter().enumerate() {
+        println!("  step {}: {}", i, step);
+    }
+    println!();
+
+    for attempt in &explanation.attempts {
+        let start_surface = &tokens[attempt.start_token].surface;
+        match &attempt.failure {
+            None => {
+                let matched: String = tokens[attempt.start_token..attempt.end_token]
+                    .iter()
+                    .map(|t| t.surface.as_str())
+                    .collect();
+                println!(
+                    "[{}] {}: MATCH \"{}\"",
+                    attempt.start_token, start_surface, matched
+                );
+            }
+            Some(failure) => {
+                let token = match (&failure.token_index, &failure.token_surface) {
+                    (Some(index), Some(surface)) => format!("token {} \"{}\"", index, surface),
+                    _ => "end of input".to_string(),
+                };
+                println!(
+                    "[{}] {}: {}/{} steps, failed at step {} {} on {}: {}",
+                    attempt.start_token,
+                    start_surface,
+                    attempt.matched_steps,
+                    explanation.steps.len(),
+                    failure.step,
+                    failure.matcher,
+                    token,
+                    failure.reason
+                );
+            }
+        }
+    }
+
+    if let Some(closest) = explanation.closest() {
+        println!("\nClosest: start token {}", closest.start_token);
+    }
+}
@@ -0,0 +1,93 @@
+use grammar_lib::evaluation::{evaluate, AnnotatedSentence, EvaluationReport};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+/// Evaluate pattern precision/recall against an annotated corpus.
+///
+/// Input is JSONL (one `AnnotatedSentence` per line) from a file argument or stdin:
+/// `{"sentence": "...", "tokens": [...], "expected": [{"pattern_name": "...", "start_char": 0, "end_char": 4}]}`
+///
+/// Usage: grammar-eval [CORPUS.jsonl] [--json] [--min-confusions N]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let as_json = args.iter().any(|arg| arg == "--json");
+    let min_confusions: usize = args
+        .iter()
+        .position(|arg| arg == "--min-confusions")
+        .and_then(|i| args.get(i + 1))
+        .map(|n| n.parse())
+        .transpose()?
+        .unwrap_or(2);
+    let corpus_path = args
+        .iter()
+        .enumerate()
+        .find(|(i, arg)| {
+            !arg.starts_with("--") && (*i == 0 || args[i - 1] != "--min-confusions")
+        })
+        .map(|(_, arg)| arg.clone());
+
+    let reader: Box<dyn BufRead> = match &corpus_path {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+
+    let mut corpus = Vec::new();
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let sentence: AnnotatedSentence = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid annotation on line {}: {}", line_number + 1, e))?;
+        corpus.push(sentence);
+    }
+
+    let report = evaluate(&corpus);
+
+    if as_json {
+        println!("{}", serde_json::to_string(&report)?);
+    } else {
+        print_report(&report, min_confusions);
+    }
+
+    Ok(())
+}
+
+fn print_report(report: &EvaluationReport, min_confusions: usize) {
+    println!("Evaluated {} sentences\n", report.sentences);
+    println!(
+        "{:<36} {:>5} {:>5} {:>5} {:>5} {:>6} {:>6} {:>6}",
+        "pattern", "TP", "FP", "FN", "span", "prec", "rec", "F1"
+    );
+
+    for (pattern_name, stats) in &report.patterns {
+        println!(
+            "{:<36} {:>5} {:>5} {:>5} {:>5} {:>6.2} {:>6.2} {:>6.2}",
+            pattern_name,
+            stats.true_positives,
+            stats.false_positives,
+            stats.false_negatives,
+            stats.boundary_errors,
+            stats.precision(),
+            stats.recall(),
+            stats.f1()
+        );
+    }
+
+    let frequent: Vec<_> = report
+        .confusions
+        .iter()
+        .filter(|c| c.count >= min_confusions)
+        .collect();
+
+    println!("\nFrequent confusions (expected -> predicted):");
+    if frequent.is_empty() {
+        println!("  (none)");
+    }
+    for confusion in frequent {
+        println!(
+            "  {:>4}  {} -> {}",
+            confusion.count, confusion.expected, confusion.predicted
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use crate::pattern_matcher::{PatternAlternative, PatternMatch};
+use crate::KagomeToken;
+
+/// Score subtracted from a reading whose context doesn't fit.
+/// Large enough that a fitting reading always beats a non-fitting one.
+const CONTEXT_PENALTY: f32 = 5.0;
+
+/// Checks whether the tokens around the marker support a reading
+type ContextCheck = fn(tokens: &[KagomeToken], marker_idx: usize) -> bool;
+
+/// Mutually exclusive readings of the same surface. They all share a marker token
+/// (そう, よう, ので) and differ in what precedes or follows it.
+struct ExclusiveGroup {
+    marker: &'static str,
+    members: &'static [(&'static str, ContextCheck)],
+}
+
+static EXCLUSIVE_GROUPS: &[ExclusiveGroup] = &[
+    ExclusiveGroup {
+        marker: "そう",
+        members: &[
+            ("sou_desu_appearance", appearance_stem),
+            ("sou_desu_hearsay", plain_form_stem),
+            ("sou_desu_hearsay_na", copula_stem),
+        ],
+    },
+    ExclusiveGroup {
+        marker: "よう",
+        members: &[
+            ("you_ni_naru", followed_by_naru),
+            ("you_ni_suru", followed_by_suru),
+            ("you_ni_standalone", followed_by_neither),
+        ],
+    },
+    ExclusiveGroup {
+        marker: "ので",
+        members: &[
+            ("node_verb", verb_stem),
+            ("node_adjective", adjective_stem),
+            ("node_nominal", na_stem),
+        ],
+    },
+];
+
+/// Resolve competing interpretations of the same span.
+///
+/// For each exclusive group, matches sharing the same marker token are compared by
+/// confidence, with a penalty for readings whose context doesn't fit. Only matches
+/// of the winning reading are kept; the other readings are recorded in their
+/// `alternatives` so consumers can show "possibly X".
+pub(crate) fn disambiguate(matches: &mut Vec<PatternMatch>, tokens: &[KagomeToken]) {
+    let mut removed = vec![false; matches.len()];
+
+    for group in EXCLUSIVE_GROUPS {
+        // marker token index -> indices of competing matches
+        let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+
+        for (idx, m) in matches.iter().enumerate() {
+            if !group
+                .members
+                .iter()
+                .any(|(name, _)| *name == m.pattern_name)
+            {
+                continue;
+            }
+            let marker_idx = tokens.iter().position(|t| {
+                t.surface == group.marker && t.start >= m.start_char && t.end <= m.end_char
+            });
+            if let Some(marker_idx) = marker_idx {
+                clusters.entry(marker_idx).or_default().push(idx);
+            }
+        }
+
+        for (marker_idx, cluster) in clusters {
+            // Best contextual score per reading
+            let mut scores: Vec<(&'static str, f32)> = Vec::new();
+            for &idx in &cluster {
+                let m = &matches[idx];
+                let score = contextual_score(group, m, tokens, marker_idx);
+                match scores.iter_mut().find(|(name, _)| *name == m.pattern_name) {
+                    Some((_, best)) => *best = best.max(score),
+                    None => scores.push((m.pattern_name, score)),
+                }
+            }
+
+            if scores.len() < 2 {
+                continue;
+            }
+
+            scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let winner = scores[0].0;
+            let alternatives: Vec<_> = scores[1..]
+                .iter()
+                .map(|&(pattern_name, confidence)| PatternAlternative {
+                    pattern_name,
+                    confidence,
+                })
+                .collect();
+
+            for idx in cluster {
+                if matches[idx].pattern_name == winner {
+                    let score = contextual_score(group, &matches[idx], tokens, marker_idx);
+                    matches[idx].confidence = score;
+                    matches[idx].alternatives = alternatives.clone();
+                } else {
+                    removed[idx] = true;
+                }
+            }
+        }
+    }
+
+    let mut idx = 0;
+    matches.retain(|_| {
+        let keep = !removed[idx];
+        idx += 1;
+        keep
+    });
+}
+
+fn contextual_score(
+    group: &ExclusiveGroup,
+    m: &PatternMatch,
+    tokens: &[KagomeToken],
+    marker_idx: usize,
+) -> f32 {
+    let fits = group
+        .members
+        .iter()
+        .find(|(name, _)| *name == m.pattern_name)
+        .is_some_and(|(_, check)| check(tokens, marker_idx));
+
+    if fits {
+        m.confidence
+    } else {
+        m.confidence - CONTEXT_PENALTY
+    }
+}
+
+// ========== Context checks ==========
+
+fn preceding(tokens: &[KagomeToken], marker_idx: usize) -> Option<&KagomeToken> {
+    marker_idx.checked_sub(1).map(|i| &tokens[i])
+}
+
+fn conjugation_form(token: &KagomeToken) -> Option<&str> {
+    token.features.get(5).map(|f| f.as_str())
+}
+
+fn is_pos(token: &KagomeToken, pos: &str) -> bool {
+    token.pos.first().is_some_and(|p| p == pos)
+}
+
+fn is_na_adjective_stem(token: &KagomeToken) -> bool {
+    is_pos(token, "名詞") && token.pos.get(1).is_some_and(|sub| sub == "形容動詞語幹")
+}
+
+/// そう (appearance) attaches to a verb stem, i-adjective stem, or na-adjective stem
+fn appearance_stem(tokens: &[KagomeToken], marker_idx: usize) -> bool {
+    preceding(tokens, marker_idx).is_some_and(|t| {
+        (is_pos(t, "動詞") && conjugation_form(t) == Some("連用形"))
+            || (is_pos(t, "形容詞") && conjugation_form(t) == Some("ガル接続"))
+            || is_na_adjective_stem(t)
+    })
+}
+
+/// そう (hearsay) attaches to a plain form: 食べるそう, 高いそう, 食べたそう
+fn plain_form_stem(tokens: &[KagomeToken], marker_idx: usize) -> bool {
+    preceding(tokens, marker_idx).is_some_and(|t| {
+        (is_pos(t, "動詞") || is_pos(t, "形容詞") || is_pos(t, "助動詞"))
+            && conjugation_form(t) == Some("基本形")
+    })
+}
+
+/// そう (hearsay) after a noun or na-adjective needs the copula: 静かだそう
+fn copula_stem(tokens: &[KagomeToken], marker_idx: usize) -> bool {
+    preceding(tokens, marker_idx).is_some_and(|t| t.surface == "だ")
+}
+
+/// Verb following ように (skipping the に particle)
+fn verb_after_you_ni(tokens: &[KagomeToken], marker_idx: usize) -> Option<&KagomeToken> {
+    tokens
+        .get(marker_idx + 1)
+        .filter(|t| t.surface == "に")
+        .and_then(|_| tokens.get(marker_idx + 2))
+        .filter(|t| is_pos(t, "動詞"))
+}
+
+fn followed_by_naru(tokens: &[KagomeToken], marker_idx: usize) -> bool {
+    verb_after_you_ni(tokens, marker_idx).is_some_and(|t| t.base_form == "なる")
+}
+
+fn followed_by_suru(tokens: &[KagomeToken], marker_idx: usize) -> bool {
+    verb_after_you_ni(tokens, marker_idx).is_some_and(|t| t.base_form == "する")
+}
+
+fn followed_by_neither(tokens: &[KagomeToken], marker_idx: usize) -> bool {
+    !followed_by_naru(tokens, marker_idx) && !followed_by_suru(tokens, marker_idx)
+}
+
+fn verb_stem(tokens: &[KagomeToken], marker_idx: usize) -> bool {
+    preceding(tokens, marker_idx).is_some_and(|t| is_pos(t, "動詞"))
+}
+
+fn adjective_stem(tokens: &[KagomeToken], marker_idx: usize) -> bool {
+    preceding(tokens, marker_idx).is_some_and(|t| is_pos(t, "形容詞"))
+}
+
+/// Nouns and na-adjectives take な before ので: 雨なので, 静かなので
+fn na_stem(tokens: &[KagomeToken], marker_idx: usize) -> bool {
+    preceding(tokens, marker_idx).is_some_and(|t| t.surface == "な")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PatternCategory;
+
+    fn make_token(
+        surface: &str,
+        base_form: &str,
+        start: u32,
+        pos: &[&str],
+        form: &str,
+    ) -> KagomeToken {
+        KagomeToken {
+            id: 0,
+            start,
+            end: start + surface.chars().count() as u32,
+            surface: surface.to_string(),
+            class: String::new(),
+            pos: pos.iter().map(|p| p.to_string()).collect(),
+            base_form: base_form.to_string(),
+            reading: String::new(),
+            pronunciation: String::new(),
+            features: vec![
+                pos[0].to_string(),
+                "*".to_string(),
+                "*".to_string(),
+                "*".to_string(),
+                "*".to_string(),
+                form.to_string(),
+            ],
+        }
+    }
+
+    fn make_match(
+        pattern_name: &'static str,
+        start_char: u32,
+        end_char: u32,
+        confidence: f32,
+    ) -> PatternMatch {
+        PatternMatch {
+            confidence,
+            pattern_name,
+            category: PatternCategory::Construction,
+            start_char,
+            end_char,
+            alternatives: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_na_adjective_sou_is_appearance() {
+        // 静かそうです: both sou_desu readings match the na-adjective stem
+        let tokens = vec![
+            make_token("静か", "静か", 0, &["名詞", "形容動詞語幹"], "*"),
+            make_token("そう", "そう", 2, &["名詞", "特殊"], "*"),
+            make_token("です", "です", 4, &["助動詞"], "基本形"),
+        ];
+        let mut matches = vec![
+            make_match("sou_desu_hearsay", 0, 6, 10.0),
+            make_match("sou_desu_appearance", 0, 6, 9.5),
+        ];
+
+        disambiguate(&mut matches, &tokens);
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].pattern_name, "sou_desu_appearance");
+        assert_eq!(matches[0].confidence, 9.5);
+        assert_eq!(matches[0].alternatives.len(), 1);
+        assert_eq!(matches[0].alternatives[0].pattern_name, "sou_desu_hearsay");
+        assert!(matches[0].alternatives[0].confidence < matches[0].confidence);
+    }
+
+    #[test]
+    fn test_you_ni_naru_beats_standalone() {
+        // 話せるようになる
+        let tokens = vec![
+            make_token("話せる", "話せる", 0, &["動詞", "自立"], "基本形"),
+            make_token("よう", "よう", 3, &["名詞", "非自立"], "*"),
+            make_token("に", "に", 5, &["助詞", "副詞化"], "*"),
+            make_token("なる", "なる", 6, &["動詞", "自立"], "基本形"),
+        ];
+        let mut matches = vec![
+            make_match("you_ni_standalone", 0, 6, 7.5),
+            make_match("you_ni_naru", 0, 8, 11.0),
+            make_match("potential_godan", 0, 3, 6.0),
+        ];
+
+        disambiguate(&mut matches, &tokens);
+
+        let names: Vec<_> = matches.iter().map(|m| m.pattern_name).collect();
+        assert_eq!(names, vec!["you_ni_naru", "potential_godan"]);
+        assert_eq!(matches[0].alternatives[0].pattern_name, "you_ni_standalone");
+    }
+
+    #[test]
+    fn test_single_reading_untouched() {
+        let tokens = vec![
+            make_token("雨", "雨", 0, &["名詞", "一般"], "*"),
+            make_token("な", "だ", 1, &["助動詞"], "体言接続"),
+            make_token("ので", "ので", 2, &["助詞", "接続助詞"], "*"),
+        ];
+        let mut matches = vec![make_match("node_nominal", 0, 4, 6.0)];
+
+        disambiguate(&mut matches, &tokens);
+
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].alternatives.is_empty());
+        assert_eq!(matches[0].confidence, 6.0);
+    }
+}
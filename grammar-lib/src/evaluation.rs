@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{KagomeToken, PatternCategory, PatternMatch};
+
+/// A sentence from an annotated corpus with the pattern spans a human expects to be detected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotatedSentence {
+    pub sentence: String,
+    pub tokens: Vec<KagomeToken>,
+    #[serde(default)]
+    pub expected: Vec<ExpectedSpan>,
+}
+
+/// Expected pattern span (character positions, same convention as `PatternMatch`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpectedSpan {
+    pub pattern_name: String,
+    pub start_char: u32,
+    pub end_char: u32,
+}
+
+/// Per-pattern counts. A prediction with the right name but a different span is a
+/// boundary error rather than a false positive plus a false negative.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PatternStats {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub boundary_errors: usize,
+}
+
+impl PatternStats {
+    pub fn precision(&self) -> f32 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives + self.boundary_errors,
+        )
+    }
+
+    pub fn recall(&self) -> f32 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives + self.boundary_errors,
+        )
+    }
+
+    pub fn f1(&self) -> f32 {
+        let (precision, recall) = (self.precision(), self.recall());
+        if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        }
+    }
+}
+
+/// Expected pattern that was reported as a different, overlapping pattern
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Confusion {
+    pub expected: String,
+    pub predicted: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EvaluationReport {
+    pub sentences: usize,
+    pub patterns: BTreeMap<String, PatternStats>,
+    /// Sorted by count (descending)
+    pub confusions: Vec<Confusion>,
+}
+
+/// Run the analyzer over an annotated corpus and compare its matches with the annotations.
+///
+/// Every Construction match is evaluated. Conjugation matches are only evaluated for
+/// patterns that appear somewhere in the annotations, since corpora rarely label them.
+pub fn evaluate(corpus: &[AnnotatedSentence]) -> EvaluationReport {
+    let annotated: HashSet<&str> = corpus
+        .iter()
+        .flat_map(|s| s.expected.iter().map(|e| e.pattern_name.as_str()))
+        .collect();
+
+    let mut report = EvaluationReport {
+        sentences: corpus.len(),
+        ..Default::default()
+    };
+    let mut confusions = HashMap::new();
+
+    for annotated_sentence in corpus {
+        let result = crate::analyze(&annotated_sentence.sentence, &annotated_sentence.tokens);
+        let predicted: Vec<_> = result
+            .grammar_matches
+            .iter()
+            .filter(|m| {
+                m.category == PatternCategory::Construction || annotated.contains(m.pattern_name)
+            })
+            .collect();
+
+        score_sentence(
+            &annotated_sentence.expected,
+            &predicted,
+            &mut report.patterns,
+            &mut confusions,
+        );
+    }
+
+    report.confusions = confusions
+        .into_iter()
+        .map(|((expected, predicted), count)| Confusion {
+            expected,
+            predicted,
+            count,
+        })
+        .collect();
+    report.confusions.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.expected.cmp(&b.expected))
+            .then_with(|| a.predicted.cmp(&b.predicted))
+    });
+
+    report
+}
+
+/// Score one sentence: exact matches first, then same-pattern overlaps (boundary errors),
+/// then whatever is left over becomes false positives / false negatives.
+fn score_sentence(
+    expected: &[ExpectedSpan],
+    predicted: &[&PatternMatch],
+    stats: &mut BTreeMap<String, PatternStats>,
+    confusions: &mut HashMap<(String, String), usize>,
+) {
+    // The matcher can report the same pattern at the same span more than once
+    let mut seen = HashSet::new();
+    let predicted: Vec<_> = predicted
+        .iter()
+        .filter(|m| seen.insert((m.pattern_name, m.start_char, m.end_char)))
+        .collect();
+
+    let mut expected_used = vec![false; expected.len()];
+    let mut predicted_used = vec![false; predicted.len()];
+
+    for (p_idx, p) in predicted.iter().enumerate() {
+        let exact = expected.iter().enumerate().position(|(e_idx, e)| {
+            !expected_used[e_idx]
+                && e.pattern_name == p.pattern_name
+                && e.start_char == p.start_char
+                && e.end_char == p.end_char
+        });
+        if let Some(e_idx) = exact {
+            expected_used[e_idx] = true;
+            predicted_used[p_idx] = true;
+            stats_for(stats, p.pattern_name).true_positives += 1;
+        }
+    }
+
+    for (p_idx, p) in predicted.iter().enumerate() {
+        if predicted_used[p_idx] {
+            continue;
+        }
+        let overlapping = expected.iter().enumerate().position(|(e_idx, e)| {
+            !expected_used[e_idx] && e.pattern_name == p.pattern_name && overlaps(e, p)
+        });
+        if let Some(e_idx) = overlapping {
+            expected_used[e_idx] = true;
+            predicted_used[p_idx] = true;
+            stats_for(stats, p.pattern_name).boundary_errors += 1;
+        }
+    }
+
+    for (p_idx, p) in predicted.iter().enumerate() {
+        if predicted_used[p_idx] {
+            continue;
+        }
+        stats_for(stats, p.pattern_name).false_positives += 1;
+
+        for e in expected.iter().filter(|e| e.pattern_name != p.pattern_name) {
+            if overlaps(e, p) {
+                *confusions
+                    .entry((e.pattern_name.clone(), p.pattern_name.to_string()))
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    for (e_idx, e) in expected.iter().enumerate() {
+        if !expected_used[e_idx] {
+            stats_for(stats, &e.pattern_name).false_negatives += 1;
+        }
+    }
+}
+
+fn stats_for<'a>(
+    stats: &'a mut BTreeMap<String, PatternStats>,
+    pattern_name: &str,
+) -> &'a mut PatternStats {
+    stats.entry(pattern_name.to_string()).or_default()
+}
+
+fn overlaps(expected: &ExpectedSpan, predicted: &PatternMatch) -> bool {
+    expected.start_char < predicted.end_char && predicted.start_char < expected.end_char
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f32 / denominator as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_match(pattern_name: &'static str, start_char: u32, end_char: u32) -> PatternMatch {
+        PatternMatch {
+            confidence: 10.0,
+            pattern_name,
+            category: PatternCategory::Construction,
+            start_char,
+            end_char,
+            alternatives: Vec::new(),
+        }
+    }
+
+    fn make_expected(pattern_name: &str, start_char: u32, end_char: u32) -> ExpectedSpan {
+        ExpectedSpan {
+            pattern_name: pattern_name.to_string(),
+            start_char,
+            end_char,
+        }
+    }
+
+    #[test]
+    fn test_score_sentence_counts() {
+        let expected = vec![
+            make_expected("te_shimau", 0, 5),
+            make_expected("sou_desu_hearsay", 6, 12),
+            make_expected("node_verb", 13, 17),
+        ];
+        let exact = make_match("te_shimau", 0, 5);
+        let wrong_reading = make_match("sou_desu_appearance", 6, 11);
+        let shifted = make_match("node_verb", 12, 17);
+        let spurious = make_match("rashii", 20, 23);
+        let predicted = vec![&exact, &wrong_reading, &shifted, &spurious];
+
+        let mut stats = BTreeMap::new();
+        let mut confusions = HashMap::new();
+        score_sentence(&expected, &predicted, &mut stats, &mut confusions);
+
+        assert_eq!(stats["te_shimau"].true_positives, 1);
+        assert_eq!(stats["node_verb"].boundary_errors, 1);
+        assert_eq!(stats["node_verb"].false_positives, 0);
+        assert_eq!(stats["sou_desu_hearsay"].false_negatives, 1);
+        assert_eq!(stats["sou_desu_appearance"].false_positives, 1);
+        assert_eq!(stats["rashii"].false_positives, 1);
+        assert_eq!(
+            confusions[&("sou_desu_hearsay".to_string(), "sou_desu_appearance".to_string())],
+            1
+        );
+        assert_eq!(confusions.len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_predictions_counted_once() {
+        let expected = vec![make_expected("te_iru", 0, 4)];
+        let first = make_match("te_iru", 0, 4);
+        let duplicate = make_match("te_iru", 0, 4);
+
+        let mut stats = BTreeMap::new();
+        let mut confusions = HashMap::new();
+        score_sentence(&expected, &[&first, &duplicate], &mut stats, &mut confusions);
+
+        assert_eq!(stats["te_iru"].true_positives, 1);
+        assert_eq!(stats["te_iru"].false_positives, 0);
+    }
+
+    #[test]
+    fn test_precision_recall() {
+        let stats = PatternStats {
+            true_positives: 6,
+            false_positives: 2,
+            false_negatives: 3,
+            boundary_errors: 1,
+        };
+        assert!((stats.precision() - 6.0 / 9.0).abs() < 1e-6);
+        assert!((stats.recall() - 0.6).abs() < 1e-6);
+        assert_eq!(PatternStats::default().f1(), 0.0);
+    }
+}
@@ -0,0 +1,505 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::pattern_matcher::{GrammarPattern, PatternMatcher, TokenMatcher};
+use crate::KagomeToken;
+
+/// Why a pattern step didn't accept a token
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FailureReason {
+    /// Ran out of tokens before the pattern was complete
+    EndOfTokens,
+    /// Token has the wrong part of speech
+    PosMismatch { expected: String, found: String },
+    /// Verb is in the wrong conjugation form (`features[5]`)
+    ConjugationForm {
+        expected: &'static str,
+        found: String,
+    },
+    BaseForm {
+        expected: &'static str,
+        found: String,
+    },
+    Surface {
+        expected: &'static str,
+        found: String,
+    },
+    /// A custom matcher rejected the token
+    CustomRejected { matcher: String },
+    /// The wildcard had to stop at a token before the rest of the pattern matched
+    WildcardStop { stop_condition: String },
+    /// Nested wildcards aren't supported by the matcher
+    NestedWildcard,
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureReason::EndOfTokens => write!(f, "end of tokens"),
+            FailureReason::PosMismatch { expected, found } => {
+                write!(f, "POS mismatch: expected {}, found {}", expected, found)
+            }
+            FailureReason::ConjugationForm { expected, found } => {
+                write!(
+                    f,
+                    "conjugation form: expected {}, found {}",
+                    expected, found
+                )
+            }
+            FailureReason::BaseForm { expected, found } => {
+                write!(f, "base form: expected {}, found {}", expected, found)
+            }
+            FailureReason::Surface { expected, found } => {
+                write!(f, "surface: expected {}, found {}", expected, found)
+            }
+            FailureReason::CustomRejected { matcher } => write!(f, "{} rejected token", matcher),
+            FailureReason::WildcardStop { stop_condition } => {
+                write!(f, "wildcard stop condition hit ({})", stop_condition)
+            }
+            FailureReason::NestedWildcard => write!(f, "nested wildcards are not supported"),
+        }
+    }
+}
+
+/// The step that ended a partial match
+#[derive(Debug, Clone, Serialize)]
+pub struct StepFailure {
+    /// Index into the pattern's matcher list
+    pub step: usize,
+    pub matcher: String,
+    /// Token the step was checked against (None at end of tokens)
+    pub token_index: Option<usize>,
+    pub token_surface: Option<String>,
+    pub reason: FailureReason,
+}
+
+/// How far a pattern got when matching from one start position
+#[derive(Debug, Clone, Serialize)]
+pub struct PartialMatch {
+    pub start_token: usize,
+    /// Number of pattern steps satisfied (skipped optional steps count as satisfied)
+    pub matched_steps: usize,
+    /// Token index after the last consumed token
+    pub end_token: usize,
+    /// None if the pattern matched
+    pub failure: Option<StepFailure>,
+}
+
+impl PartialMatch {
+    pub fn is_match(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    pub pattern_name: &'static str,
+    /// Human-readable description of each pattern step
+    pub steps: Vec<String>,
+    /// One entry per start position
+    pub attempts: Vec<PartialMatch>,
+}
+
+impl Explanation {
+    /// A full match if there is one, otherwise the attempt that satisfied the most steps
+    pub fn closest(&self) -> Option<&PartialMatch> {
+        self.attempts
+            .iter()
+            .find(|a| a.is_match())
+            .or_else(|| self.attempts.iter().rev().max_by_key(|a| a.matched_steps))
+    }
+}
+
+/// Explain how a pattern matches (or fails to match) a token list.
+/// Returns None if no pattern has that name.
+pub fn explain(pattern_name: &str, tokens: &[KagomeToken]) -> Option<Explanation> {
+    let pattern = crate::patterns::get_all_patterns()
+        .into_iter()
+        .map(|(pattern, _)| pattern)
+        .find(|p| p.name == pattern_name)?;
+
+    Some(Explanation {
+        pattern_name: pattern.name,
+        steps: pattern.tokens.iter().map(describe_matcher).collect(),
+        attempts: (0..tokens.len())
+            .map(|start| explain_at(&pattern, tokens, start))
+            .collect(),
+    })
+}
+
+/// Mirrors `PatternMatcher::match_pattern_at`, recording where it stops
+fn explain_at(pattern: &GrammarPattern, tokens: &[KagomeToken], start: usize) -> PartialMatch {
+    let mut pos = start;
+
+    for (step, matcher) in pattern.tokens.iter().enumerate() {
+        match matcher {
+            TokenMatcher::Wildcard {
+                min,
+                max,
+                stop_conditions,
+            } => {
+                return explain_wildcard(
+                    pattern,
+                    tokens,
+                    start,
+                    step,
+                    pos,
+                    *min,
+                    *max,
+                    stop_conditions,
+                );
+            }
+
+            TokenMatcher::Optional(inner) => {
+                if pos < tokens.len() && PatternMatcher::token_matches(inner, &tokens[pos]).0 {
+                    pos += 1;
+                }
+            }
+
+            _ => {
+                if let Some(failure) = check_step(step, matcher, tokens, pos) {
+                    return PartialMatch {
+                        start_token: start,
+                        matched_steps: step,
+                        end_token: pos,
+                        failure: Some(failure),
+                    };
+                }
+                pos += 1;
+            }
+        }
+    }
+
+    PartialMatch {
+        start_token: start,
+        matched_steps: pattern.tokens.len(),
+        end_token: pos,
+        failure: None,
+    }
+}
+
+/// Mirrors `PatternMatcher::match_with_wildcard`. If a stop condition cut the search
+/// short that is reported; otherwise the skip count that got furthest is reported.
+#[allow(clippy::too_many_arguments)]
+fn explain_wildcard(
+    pattern: &GrammarPattern,
+    tokens: &[KagomeToken],
+    start: usize,
+    wildcard_step: usize,
+    current_pos: usize,
+    min: usize,
+    max: usize,
+    stop_conditions: &[TokenMatcher],
+) -> PartialMatch {
+    let mut best: Option<PartialMatch> = None;
+
+    for skip_count in min..=max {
+        let check_pos = current_pos + skip_count;
+
+        if check_pos >= tokens.len() {
+            break;
+        }
+
+        for offset in 0..skip_count {
+            let token_index = current_pos + offset;
+            let token = &tokens[token_index];
+
+            let stop_condition = if token.pos.first().is_some_and(|pos| pos == "記号") {
+                Some("punctuation".to_string())
+            } else {
+                stop_conditions
+                    .iter()
+                    .find(|c| PatternMatcher::token_matches(c, token).0)
+                    .map(describe_matcher)
+            };
+
+            if let Some(stop_condition) = stop_condition {
+                return PartialMatch {
+                    start_token: start,
+                    matched_steps: wildcard_step,
+                    end_token: token_index,
+                    failure: Some(StepFailure {
+                        step: wildcard_step,
+                        matcher: describe_matcher(&pattern.tokens[wildcard_step]),
+                        token_index: Some(token_index),
+                        token_surface: Some(token.surface.clone()),
+                        reason: FailureReason::WildcardStop { stop_condition },
+                    }),
+                };
+            }
+        }
+
+        // Remaining matchers are all required after a wildcard (see match_remaining_pattern_with_pos)
+        let mut pos = check_pos;
+        let mut attempt = None;
+        for (step, matcher) in pattern.tokens.iter().enumerate().skip(wildcard_step + 1) {
+            let failure = if matches!(matcher, TokenMatcher::Wildcard { .. }) {
+                Some(StepFailure {
+                    step,
+                    matcher: describe_matcher(matcher),
+                    token_index: None,
+                    token_surface: None,
+                    reason: FailureReason::NestedWildcard,
+                })
+            } else {
+                check_step(step, matcher, tokens, pos)
+            };
+
+            if let Some(failure) = failure {
+                attempt = Some(PartialMatch {
+                    start_token: start,
+                    matched_steps: step,
+                    end_token: pos,
+                    failure: Some(failure),
+                });
+                break;
+            }
+            pos += 1;
+        }
+
+        let Some(attempt) = attempt else {
+            return PartialMatch {
+                start_token: start,
+                matched_steps: pattern.tokens.len(),
+                end_token: pos,
+                failure: None,
+            };
+        };
+
+        if best
+            .as_ref()
+            .is_none_or(|b| attempt.matched_steps > b.matched_steps)
+        {
+            best = Some(attempt);
+        }
+    }
+
+    best.unwrap_or(PartialMatch {
+        start_token: start,
+        matched_steps: wildcard_step,
+        end_token: current_pos,
+        failure: Some(StepFailure {
+            step: wildcard_step,
+            matcher: describe_matcher(&pattern.tokens[wildcard_step]),
+            token_index: None,
+            token_surface: None,
+            reason: FailureReason::EndOfTokens,
+        }),
+    })
+}
+
+/// Check a required step, returning the failure if it doesn't match
+fn check_step(
+    step: usize,
+    matcher: &TokenMatcher,
+    tokens: &[KagomeToken],
+    pos: usize,
+) -> Option<StepFailure> {
+    let Some(token) = tokens.get(pos) else {
+        return Some(StepFailure {
+            step,
+            matcher: describe_matcher(matcher),
+            token_index: None,
+            token_surface: None,
+            reason: FailureReason::EndOfTokens,
+        });
+    };
+
+    if PatternMatcher::token_matches(matcher, token).0 {
+        return None;
+    }
+
+    Some(StepFailure {
+        step,
+        matcher: describe_matcher(matcher),
+        token_index: Some(pos),
+        token_surface: Some(token.surface.clone()),
+        reason: failure_reason(matcher, token),
+    })
+}
+
+/// Work out why `token_matches` rejected a token. Checks run in the same order.
+fn failure_reason(matcher: &TokenMatcher, token: &KagomeToken) -> FailureReason {
+    let pos = token.pos.first().cloned().unwrap_or_default();
+
+    match matcher {
+        TokenMatcher::Verb {
+            conjugation_form,
+            base_form,
+        } => {
+            if pos != "動詞" {
+                return FailureReason::PosMismatch {
+                    expected: "動詞".to_string(),
+                    found: pos,
+                };
+            }
+            if let Some(expected) = conjugation_form {
+                let found = token.features.get(5).cloned().unwrap_or_default();
+                if &found != expected {
+                    return FailureReason::ConjugationForm { expected, found };
+                }
+            }
+            match base_form {
+                Some(expected) => FailureReason::BaseForm {
+                    expected,
+                    found: token.base_form.clone(),
+                },
+                None => FailureReason::PosMismatch {
+                    expected: "動詞".to_string(),
+                    found: pos,
+                },
+            }
+        }
+
+        TokenMatcher::Adjective { base_form } => {
+            let is_na_adjective =
+                pos == "名詞" && token.pos.get(1).is_some_and(|sub| sub == "形容動詞語幹");
+            match base_form {
+                Some(expected) if pos == "形容詞" || is_na_adjective => {
+                    FailureReason::BaseForm {
+                        expected,
+                        found: token.base_form.clone(),
+                    }
+                }
+                _ => FailureReason::PosMismatch {
+                    expected: "形容詞 or 形容動詞語幹".to_string(),
+                    found: token.pos.join(","),
+                },
+            }
+        }
+
+        TokenMatcher::Surface(expected) => FailureReason::Surface {
+            expected,
+            found: token.surface.clone(),
+        },
+
+        TokenMatcher::Custom(custom) => FailureReason::CustomRejected {
+            matcher: format!("{:?}", custom),
+        },
+
+        TokenMatcher::Optional(inner) => failure_reason(inner, token),
+
+        TokenMatcher::Wildcard { .. } => FailureReason::NestedWildcard,
+
+        // Any never fails on an existing token
+        TokenMatcher::Any => FailureReason::EndOfTokens,
+    }
+}
+
+/// Short description of a matcher for debugging output
+pub(crate) fn describe_matcher(matcher: &TokenMatcher) -> String {
+    match matcher {
+        TokenMatcher::Verb {
+            conjugation_form,
+            base_form,
+        } => format!(
+            "Verb(form={}, base={})",
+            conjugation_form.unwrap_or("*"),
+            base_form.unwrap_or("*")
+        ),
+        TokenMatcher::Adjective { base_form } => {
+            format!("Adjective(base={})", base_form.unwrap_or("*"))
+        }
+        TokenMatcher::Surface(surface) => format!("Surface({})", surface),
+        TokenMatcher::Any => "Any".to_string(),
+        TokenMatcher::Custom(custom) => format!("{:?}", custom),
+        TokenMatcher::Wildcard { min, max, .. } => format!("Wildcard({}..={})", min, max),
+        TokenMatcher::Optional(inner) => format!("Optional({})", describe_matcher(inner)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_token(surface: &str, base_form: &str, pos: &str, form: &str) -> KagomeToken {
+        KagomeToken {
+            id: 0,
+            start: 0,
+            end: 0,
+            surface: surface.to_string(),
+            class: String::new(),
+            pos: vec![pos.to_string()],
+            base_form: base_form.to_string(),
+            reading: String::new(),
+            pronunciation: String::new(),
+            features: vec![
+                pos.to_string(),
+                "*".to_string(),
+                "*".to_string(),
+                "*".to_string(),
+                "*".to_string(),
+                form.to_string(),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_unknown_pattern() {
+        assert!(explain("not_a_pattern", &[]).is_none());
+    }
+
+    #[test]
+    fn test_conjugation_form_failure() {
+        // 食べるたい: tai_form needs 連用形
+        let tokens = vec![
+            make_token("食べる", "食べる", "動詞", "基本形"),
+            make_token("たい", "たい", "助動詞", "基本形"),
+        ];
+        let explanation = explain("tai_form", &tokens).unwrap();
+        let closest = explanation.closest().unwrap();
+
+        let failure = closest.failure.as_ref().unwrap();
+        assert_eq!(failure.step, 0);
+        assert_eq!(failure.token_index, Some(0));
+        assert_eq!(
+            failure.reason,
+            FailureReason::ConjugationForm {
+                expected: "連用形",
+                found: "基本形".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_full_match_and_surface_failure() {
+        let tokens = vec![
+            make_token("雨", "雨", "名詞", "*"),
+            make_token("な", "だ", "助動詞", "体言接続"),
+            make_token("ので", "ので", "助詞", "*"),
+        ];
+        let explanation = explain("node_nominal", &tokens).unwrap();
+
+        assert_eq!(explanation.attempts.len(), 3);
+        assert!(explanation.attempts[0].is_match());
+        assert_eq!(explanation.attempts[0].end_token, 3);
+
+        let failure = explanation.attempts[1].failure.as_ref().unwrap();
+        assert_eq!(failure.step, 1);
+        assert_eq!(
+            failure.reason,
+            FailureReason::Surface {
+                expected: "な",
+                found: "ので".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_wildcard_stop_condition() {
+        // が、話せる: punctuation stops the wildcard before the potential verb
+        let tokens = vec![
+            make_token("が", "が", "助詞", "*"),
+            make_token("、", "、", "記号", "*"),
+            make_token("話せる", "話せる", "動詞", "基本形"),
+        ];
+        let explanation = explain("potential_ga_verb", &tokens).unwrap();
+        let failure = explanation.attempts[0].failure.as_ref().unwrap();
+
+        assert_eq!(failure.step, 1);
+        assert_eq!(failure.token_index, Some(1));
+        assert!(matches!(failure.reason, FailureReason::WildcardStop { .. }));
+    }
+}
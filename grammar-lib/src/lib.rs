@@ -1,4 +1,5 @@
 // Internal implementation modules
+mod disambiguation;
 mod matchers;
 mod pattern_matcher;
 mod pattern_registry;
@@ -6,6 +7,8 @@ mod patterns;
 
 // Public API modules
 pub mod compounds;
+pub mod evaluation;
+pub mod explain;
 pub mod text_utils;
 pub mod token_combiner;
 pub mod types;
@@ -13,12 +16,15 @@ pub mod vocabulary;
 
 // Re-export types needed by consumers
 pub use compounds::{find_compound_spans, CompoundSpan};
-pub use pattern_matcher::{PatternCategory, PatternMatch};
-pub use text_utils::{char_pos_to_byte_pos, pattern_text};
-pub use pattern_registry::get_jlpt_level;
+pub use pattern_matcher::{PatternAlternative, PatternCategory, PatternMatch};
+pub use text_utils::{char_pos_to_byte_pos, is_kana, pattern_text, to_hiragana, to_katakana};
+pub use pattern_registry::{get_jlpt_level, list_patterns, PatternInfo};
 pub use token_combiner::{combine_conjugation_tokens, select_best_patterns};
 pub use types::{AnalysisResult, KagomeToken};
-pub use vocabulary::{extract_vocabulary, VocabWord};
+pub use vocabulary::{
+    extract_vocabulary, extract_vocabulary_tokens, extract_vocabulary_with_spans, VocabOccurrence,
+    VocabToken, VocabWord,
+};
 
 // Internal helpers
 use patterns::create_pattern_matcher;
@@ -23,6 +23,15 @@ pub struct PatternMatch {
     /// 0-indexed character position where pattern ends (NOT a byte offset)
     /// To extract text in Rust, convert to byte position first using char_indices()
     pub end_char: u32,
+    /// Competing readings of the same span that lost disambiguation (highest score first)
+    pub alternatives: Vec<PatternAlternative>,
+}
+
+/// A lower-scoring interpretation kept alongside the winning match ("possibly X")
+#[derive(Debug, Clone, Serialize)]
+pub struct PatternAlternative {
+    pub pattern_name: &'static str,
+    pub confidence: f32,
 }
 
 #[derive(Debug, Clone)]
@@ -115,6 +124,9 @@ impl PatternMatcher {
         // Extend construction patterns to include adjacent auxiliary verbs
         Self::extend_with_auxiliary_verbs(&mut matches, tokens);
 
+        // Keep only the best-fitting reading where patterns compete for the same span
+        crate::disambiguation::disambiguate(&mut matches, tokens);
+
         // Sort by confidence (descending), then by character length (descending)
         matches.sort_by(|a, b| {
             b.confidence
@@ -271,6 +283,7 @@ impl PatternMatcher {
             category: pattern.category,
             start_char,
             end_char,
+            alternatives: Vec::new(),
         })
     }
 
@@ -386,7 +399,7 @@ impl PatternMatcher {
     }
 
     /// Check if a token matcher matches a given token, returning match status and specificity score
-    fn token_matches(matcher: &TokenMatcher, token: &KagomeToken) -> (bool, f32) {
+    pub(crate) fn token_matches(matcher: &TokenMatcher, token: &KagomeToken) -> (bool, f32) {
         match matcher {
             TokenMatcher::Verb {
                 conjugation_form,
@@ -1,6 +1,10 @@
 use std::collections::HashMap;
 use std::sync::LazyLock;
 
+use serde::Serialize;
+
+use crate::PatternCategory;
+
 #[derive(Debug, Clone)]
 pub struct PatternMetadata {
     pub jlpt_level: &'static str,
@@ -41,6 +45,28 @@ pub fn get_jlpt_level(pattern_name: &str) -> &'static str {
     PATTERN_REGISTRY.get_jlpt_level(pattern_name)
 }
 
+/// Summary of a registered pattern
+#[derive(Debug, Clone, Serialize)]
+pub struct PatternInfo {
+    pub name: &'static str,
+    pub jlpt_level: &'static str,
+    pub category: PatternCategory,
+    pub priority: u8,
+}
+
+/// All registered patterns in declaration order (grouped by JLPT level)
+pub fn list_patterns() -> Vec<PatternInfo> {
+    crate::patterns::get_all_patterns()
+        .into_iter()
+        .map(|(pattern, jlpt_level)| PatternInfo {
+            name: pattern.name,
+            jlpt_level,
+            category: pattern.category,
+            priority: pattern.priority,
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -15,3 +15,50 @@ pub fn pattern_text(sentence: &str, pattern: &PatternMatch) -> String {
     let end_byte = char_pos_to_byte_pos(sentence, pattern.end_char as usize);
     sentence[start_byte..end_byte].to_string()
 }
+
+/// Offset between a hiragana and its katakana counterpart (ぁ U+3041 → ァ U+30A1)
+const KANA_OFFSET: u32 = 0x60;
+
+/// Convert hiragana to katakana, leaving other characters unchanged: たべる → タベル
+pub fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' | 'ゝ' | 'ゞ' => char::from_u32(c as u32 + KANA_OFFSET).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Convert katakana to hiragana, leaving other characters unchanged: タベル → たべる
+pub fn to_hiragana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            'ァ'..='ヶ' | 'ヽ' | 'ヾ' => char::from_u32(c as u32 - KANA_OFFSET).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// True if every character is hiragana, katakana or the long vowel mark
+pub fn is_kana(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| matches!(c, 'ぁ'..='ゖ' | 'ゝ' | 'ゞ' | 'ァ'..='ヺ' | 'ー' | 'ヽ' | 'ヾ'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kana_conversion() {
+        assert_eq!(to_katakana("たべる"), "タベル");
+        assert_eq!(to_katakana("食べる"), "食ベル");
+        assert_eq!(to_hiragana("カケル"), "かける");
+        assert_eq!(to_hiragana("コーヒー"), "こーひー");
+        assert!(is_kana("かける"));
+        assert!(is_kana("コーヒー"));
+        assert!(!is_kana("食べる"));
+        assert!(!is_kana(""));
+    }
+}
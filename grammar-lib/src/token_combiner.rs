@@ -263,6 +263,7 @@ mod tests {
             category: PatternCategory::Conjugation,
             start_char,
             end_char,
+            alternatives: Vec::new(),
         }
     }
 
@@ -1,6 +1,6 @@
 use crate::KagomeToken;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 /// A vocabulary word extracted from tokens
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -37,6 +37,14 @@ impl VocabWord {
     }
 }
 
+/// A vocabulary word and the character spans where it appears in a line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VocabOccurrence {
+    pub word: VocabWord,
+    /// (start, end) character positions, one per occurrence, in line order
+    pub spans: Vec<(u32, u32)>,
+}
+
 /// Extract vocabulary from combined tokens.
 ///
 /// With combined tokens (from `analyze()`), auxiliary tokens are already merged
@@ -48,25 +56,69 @@ impl VocabWord {
 /// # Returns
 /// A vector of unique vocabulary words (deduplicated)
 pub fn extract_vocabulary(tokens: &[KagomeToken]) -> Vec<VocabWord> {
-    let mut words = Vec::new();
-    let mut seen = HashSet::new();
+    extract_vocabulary_with_spans(tokens)
+        .into_iter()
+        .map(|occurrence| occurrence.word)
+        .collect()
+}
 
-    for token in tokens {
-        let word = VocabWord::from_token(token);
+/// One occurrence of a vocabulary word in a line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VocabToken {
+    pub word: VocabWord,
+    /// Position among the line's combined tokens
+    pub token_index: u32,
+    /// (start, end) character positions in the line
+    pub start: u32,
+    pub end: u32,
+    /// The word as written, e.g. 見ていた for 見る
+    pub surface: String,
+}
 
-        // Skip if not a content word
-        if !word.is_content_word() {
-            continue;
-        }
+/// Extract every occurrence of a content word from combined tokens, in line order.
+///
+/// Unlike `extract_vocabulary`, nothing is deduplicated: a word used three times
+/// yields three tokens.
+pub fn extract_vocabulary_tokens(tokens: &[KagomeToken]) -> Vec<VocabToken> {
+    tokens
+        .iter()
+        .enumerate()
+        .filter_map(|(index, token)| {
+            let word = VocabWord::from_token(token);
+            word.is_content_word().then(|| VocabToken {
+                word,
+                token_index: index as u32,
+                start: token.start,
+                end: token.end,
+                surface: token.surface.clone(),
+            })
+        })
+        .collect()
+}
 
-        // Deduplicate
-        if !seen.contains(&word) {
-            seen.insert(word.clone());
-            words.push(word);
+/// Extract vocabulary from combined tokens, keeping where each word occurs.
+///
+/// Words are deduplicated like `extract_vocabulary`, but every occurrence's span
+/// is kept, so 見ていた is reported as 見る with the span of the whole
+/// conjugated form.
+pub fn extract_vocabulary_with_spans(tokens: &[KagomeToken]) -> Vec<VocabOccurrence> {
+    let mut occurrences: Vec<VocabOccurrence> = Vec::new();
+    let mut index_by_word: HashMap<VocabWord, usize> = HashMap::new();
+
+    for token in extract_vocabulary_tokens(tokens) {
+        let span = (token.start, token.end);
+        if let Some(&index) = index_by_word.get(&token.word) {
+            occurrences[index].spans.push(span);
+        } else {
+            index_by_word.insert(token.word.clone(), occurrences.len());
+            occurrences.push(VocabOccurrence {
+                word: token.word,
+                spans: vec![span],
+            });
         }
     }
 
-    words
+    occurrences
 }
 
 #[cfg(test)]
@@ -134,4 +186,65 @@ mod tests {
         assert_eq!(words[0].base_form, "見る");
         assert_eq!(words[1].base_form, "猫");
     }
+
+    #[test]
+    fn test_extract_vocabulary_with_spans_keeps_every_occurrence() {
+        let token = |start: u32, end: u32, surface: &str, base_form: &str| KagomeToken {
+            id: start,
+            start,
+            end,
+            surface: surface.to_string(),
+            class: String::new(),
+            pos: vec!["動詞".to_string()],
+            base_form: base_form.to_string(),
+            reading: String::new(),
+            pronunciation: String::new(),
+            features: vec![],
+        };
+        // 見ていた、見た
+        let tokens = vec![
+            token(0, 4, "見ていた", "見る"),
+            KagomeToken {
+                pos: vec!["記号".to_string()],
+                ..token(4, 5, "、", "、")
+            },
+            token(5, 7, "見た", "見る"),
+        ];
+
+        let occurrences = extract_vocabulary_with_spans(&tokens);
+
+        assert_eq!(occurrences.len(), 1);
+        assert_eq!(occurrences[0].word.base_form, "見る");
+        assert_eq!(occurrences[0].spans, vec![(0, 4), (5, 7)]);
+    }
+
+    #[test]
+    fn test_extract_vocabulary_tokens_keeps_surface_forms() {
+        let token = |start: u32, end: u32, surface: &str, pos: &str, base_form: &str| KagomeToken {
+            id: start,
+            start,
+            end,
+            surface: surface.to_string(),
+            class: String::new(),
+            pos: vec![pos.to_string()],
+            base_form: base_form.to_string(),
+            reading: String::new(),
+            pronunciation: String::new(),
+            features: vec![],
+        };
+        // 食べた、食べる
+        let tokens = vec![
+            token(0, 3, "食べた", "動詞", "食べる"),
+            token(3, 4, "、", "記号", "、"),
+            token(4, 7, "食べる", "動詞", "食べる"),
+        ];
+
+        let vocab_tokens = extract_vocabulary_tokens(&tokens);
+
+        let found: Vec<(u32, u32, u32, &str)> = vocab_tokens
+            .iter()
+            .map(|t| (t.token_index, t.start, t.end, t.surface.as_str()))
+            .collect();
+        assert_eq!(found, vec![(0, 0, 3, "食べた"), (2, 4, 7, "食べる")]);
+    }
 }
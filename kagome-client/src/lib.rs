@@ -60,7 +60,7 @@ pub struct KagomeServer {
 impl KagomeServer {
     /// Start a Kagome server on the specified port.
     pub fn start(port: u16) -> Result<Self, KagomeError> {
-        println!("Starting Kagome server on port {}...", port);
+        eprintln!("Starting Kagome server on port {}...", port);
 
         let process = Command::new("kagome")
             .args(["server", "-http", &format!(":{}", port)])
@@ -80,7 +80,7 @@ impl KagomeServer {
         let timeout_secs = 30;
         for _ in 0..timeout_secs {
             if client.get(format!("{}/", base_url)).send().is_ok() {
-                println!("Kagome server started successfully");
+                eprintln!("Kagome server started successfully");
                 return Ok(KagomeServer {
                     process,
                     client,
@@ -117,10 +117,10 @@ impl KagomeServer {
 
     /// Shutdown the Kagome server gracefully.
     pub fn shutdown(mut self) -> Result<(), KagomeError> {
-        println!("Shutting down Kagome server...");
+        eprintln!("Shutting down Kagome server...");
         let _ = self.process.kill();
         let _ = self.process.wait();
-        println!("Kagome server shut down");
+        eprintln!("Kagome server shut down");
         Ok(())
     }
 }
@@ -1,3 +1,5 @@
 fn main() {
-    tauri_build::build()
+    // Only the app needs Tauri's build step; the CLI and API server build without it
+    #[cfg(feature = "app")]
+    tauri_build::build();
 }
@@ -1,6 +1,8 @@
 use crate::analysis::kagome_server::{KagomeServer, KagomeServerExt};
-use crate::analysis::unified_analyzer::analyze_batch;
+use crate::analysis::unified_analyzer::{analyze_batch, WordTokens};
+use crate::db::word::EXCLUDED_POS_SQL;
 use crate::error::Error;
+use crate::jobs::JobContext;
 use rusqlite::{Connection, Transaction};
 use std::collections::{HashMap, HashSet};
 use std::sync::LazyLock;
@@ -28,26 +30,44 @@ static POS_CACHE: LazyLock<HashMap<Vec<String>, String>> = LazyLock::new(|| {
         .collect()
 });
 
-pub fn create_reverse_index(conn: &mut Connection) -> Result<(), Error> {
-    println!("Creating reverse index and analyzing grammar patterns...");
+/// Analyzes every transcript and rebuilds the word and grammar pattern indexes, or with
+/// `incremental` only adds the lines imported since the last analysis. Progress is reported
+/// per batch of lines; cancelling before the final write leaves the indexes as they were.
+pub fn create_reverse_index(
+    conn: &mut Connection,
+    incremental: bool,
+    job: &JobContext,
+) -> Result<(), Error> {
+    eprintln!("Creating reverse index and analyzing grammar patterns...");
 
     let server = KagomeServer::start_default()?;
 
-    println!("Processing transcripts and analyzing grammar patterns...");
+    eprintln!("Processing transcripts and analyzing grammar patterns...");
 
-    let total_transcripts: i64 =
-        conn.query_row("SELECT COUNT(*) FROM transcripts", [], |row| row.get(0))?;
-    println!(
+    let filter = if incremental {
+        "WHERE id NOT IN (SELECT transcript_id FROM analyzed_transcripts)"
+    } else {
+        ""
+    };
+    let total_transcripts: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM transcripts {}", filter),
+        [],
+        |row| row.get(0),
+    )?;
+    eprintln!(
         "Processing {} total transcripts with streaming...",
         total_transcripts
     );
 
     let batch_size = 1000;
-    let mut all_words = HashMap::new(); // Store raw words first (no corrections yet)
+    let mut all_words: HashMap<_, WordTokens> = HashMap::new(); // Store raw words first (no corrections yet)
     let mut all_grammar_patterns = HashMap::new();
 
-    let mut stmt =
-        conn.prepare("SELECT id, episode_id, text FROM transcripts ORDER BY episode_id, line_id")?;
+    let mut analyzed_ids = Vec::with_capacity(total_transcripts as usize);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, episode_id, text FROM transcripts {} ORDER BY episode_id, line_id",
+        filter
+    ))?;
     let transcript_iter = stmt.query_map([], |row| {
         Ok((
             row.get::<_, i64>(0)?,    // transcript_id
@@ -58,23 +78,27 @@ pub fn create_reverse_index(conn: &mut Connection) -> Result<(), Error> {
 
     let mut batch = Vec::with_capacity(batch_size);
     let mut batch_count = 0;
-    let total_batches = (total_transcripts as usize / batch_size) + 1;
+    let total_batches = (total_transcripts as usize).div_ceil(batch_size);
+    job.progress("Analyzing transcripts", 0, total_batches);
 
     for transcript_result in transcript_iter {
         let transcript = transcript_result?;
+        analyzed_ids.push(transcript.0);
         batch.push(transcript);
 
         if batch.len() >= batch_size {
-            batch_count += 1;
-            println!("Processing batch {}/{}", batch_count, total_batches);
-
+            job.check_cancelled()?;
             let results = analyze_batch(&batch, &server)?;
-
-            for (word_key, transcript_ids) in results.words {
-                all_words
-                    .entry((word_key.base_form, word_key.reading, word_key.pos))
-                    .or_insert_with(HashSet::new)
-                    .extend(transcript_ids);
+            batch_count += 1;
+            job.progress("Analyzing transcripts", batch_count, total_batches);
+
+            for (word_key, word_tokens) in results.words {
+                merge_word_tokens(
+                    all_words
+                        .entry((word_key.base_form, word_key.reading, word_key.pos))
+                        .or_default(),
+                    word_tokens,
+                );
             }
 
             for (episode_id, collector) in results.grammar_patterns {
@@ -89,16 +113,18 @@ pub fn create_reverse_index(conn: &mut Connection) -> Result<(), Error> {
     }
 
     if !batch.is_empty() {
-        batch_count += 1;
-        println!("Processing final batch {}/{}", batch_count, total_batches);
-
+        job.check_cancelled()?;
         let results = analyze_batch(&batch, &server)?;
+        batch_count += 1;
+        job.progress("Analyzing transcripts", batch_count, total_batches);
 
-        for (word_key, transcript_ids) in results.words {
-            all_words
-                .entry((word_key.base_form, word_key.reading, word_key.pos))
-                .or_insert_with(HashSet::new)
-                .extend(transcript_ids);
+        for (word_key, word_tokens) in results.words {
+            merge_word_tokens(
+                all_words
+                    .entry((word_key.base_form, word_key.reading, word_key.pos))
+                    .or_default(),
+                word_tokens,
+            );
         }
 
         for (episode_id, collector) in results.grammar_patterns {
@@ -111,13 +137,14 @@ pub fn create_reverse_index(conn: &mut Connection) -> Result<(), Error> {
 
     drop(stmt);
 
-    println!(
+    eprintln!(
         "Applying reading corrections to {} unique words...",
         all_words.len()
     );
 
     let unique_base_forms: HashSet<String> = all_words
         .keys()
+        .filter(|(_, reading, _)| reading.is_empty())
         .map(|(base_form, _, _)| base_form.clone())
         .collect();
 
@@ -125,24 +152,36 @@ pub fn create_reverse_index(conn: &mut Connection) -> Result<(), Error> {
     let reading_corrections =
         crate::analysis::morphology::get_base_form_readings(&base_forms_vec, &server)?;
 
-    let mut all_corrected_words = HashMap::new();
-    for ((base_form, reading, pos), transcript_ids) in all_words {
-        let final_reading = reading_corrections
-            .get(base_form.as_str())
-            .cloned()
-            .unwrap_or(reading);
-
-        all_corrected_words
-            .entry((base_form, final_reading, pos))
-            .or_insert_with(HashSet::new)
-            .extend(transcript_ids);
+    let mut all_corrected_words: HashMap<_, WordTokens> = HashMap::new();
+    for ((base_form, reading, pos), word_tokens) in all_words {
+        // Words seen in dictionary form keep their reading, which tells homographs like
+        // 方 (ホウ/カタ) apart; inflected ones take the base form's most common reading
+        let final_reading = if reading.is_empty() {
+            reading_corrections
+                .get(base_form.as_str())
+                .cloned()
+                .unwrap_or(reading)
+        } else {
+            reading
+        };
+
+        merge_word_tokens(
+            all_corrected_words
+                .entry((base_form, final_reading, pos))
+                .or_default(),
+            word_tokens,
+        );
     }
 
+    // The write below replaces the indexes in one transaction, so this is the last point at
+    // which cancelling leaves them untouched
+    job.check_cancelled()?;
+    job.progress("Writing indexes", 0, 1);
     let tx = conn.transaction()?;
 
     create_main_indexes_tx(&tx)?;
 
-    println!("Processing grammar pattern occurrences...");
+    eprintln!("Processing grammar pattern occurrences...");
 
     let mut all_pattern_occurrences = Vec::new();
     let mut pattern_names = std::collections::HashSet::new();
@@ -166,7 +205,7 @@ pub fn create_reverse_index(conn: &mut Connection) -> Result<(), Error> {
 
     // Show a preview of pattern matches
     if !all_pattern_occurrences.is_empty() {
-        println!("\nSample grammar pattern matches:");
+        eprintln!("\nSample grammar pattern matches:");
         let sample_size = 3.min(all_pattern_occurrences.len());
 
         for (pattern_name, transcript_id, _confidence, start_char, end_char) in
@@ -191,13 +230,13 @@ pub fn create_reverse_index(conn: &mut Connection) -> Result<(), Error> {
                     .unwrap_or(text.len());
 
                 let matched_text = &text[byte_start..byte_end];
-                println!(
+                eprintln!(
                     "  - Pattern: {}, Transcript ID: {}\n    Matched text: \"{}\"",
                     pattern_name, transcript_id, matched_text
                 );
             }
         }
-        println!();
+        eprintln!();
     }
 
     let mut pattern_id_cache = std::collections::HashMap::new();
@@ -226,8 +265,14 @@ pub fn create_reverse_index(conn: &mut Connection) -> Result<(), Error> {
 
     let total_pattern_occurrences = final_occurrences.len();
 
+    // Occurrences are inserted OR IGNORE, so a full run clears the old ones, and the ones
+    // disambiguation now rejects, first
+    if !incremental {
+        tx.execute("DELETE FROM grammar_pattern_occurrences", [])?;
+    }
+
     if !final_occurrences.is_empty() {
-        println!(
+        eprintln!(
             "Inserting {} grammar pattern occurrences...",
             total_pattern_occurrences
         );
@@ -237,9 +282,10 @@ pub fn create_reverse_index(conn: &mut Connection) -> Result<(), Error> {
         )?;
     }
 
-    batch_insert_words_and_occurrences(&tx, &all_corrected_words)?;
+    batch_insert_words_and_occurrences(&tx, &all_corrected_words, incremental)?;
+    mark_analyzed(&tx, &analyzed_ids, incremental)?;
 
-    println!(
+    eprintln!(
         "Total pattern occurrences inserted: {}",
         total_pattern_occurrences
     );
@@ -248,67 +294,161 @@ pub fn create_reverse_index(conn: &mut Connection) -> Result<(), Error> {
 
     server.shutdown()?;
 
-    println!("Reverse index created successfully!");
+    eprintln!("Reverse index created successfully!");
 
     process_jlpt_data(conn)?;
 
     Ok(())
 }
 
+/// A word's identity: dictionary form, reading and coarse part of speech (名詞, 動詞, ...).
+/// Finer distinctions such as 名詞-一般 vs 名詞-副詞可能 don't make separate words.
+type WordIdentity<'a> = (&'a str, &'a str, &'a str);
+
 fn batch_insert_words_and_occurrences(
     tx: &Transaction,
-    word_map: &HashMap<(String, String, Vec<String>), HashSet<i64>>,
+    word_map: &HashMap<(String, String, Vec<String>), WordTokens>,
+    incremental: bool,
 ) -> Result<(), Error> {
-    let word_keys: Vec<_> = word_map.keys().collect();
+    // A full run analyzed every line again, so occurrences are rebuilt rather than added to.
+    // This also splits occurrences that databases from before `pos_group` merged into one word.
+    if !incremental {
+        tx.execute_batch("DELETE FROM word_tokens; DELETE FROM word_occurrences;")?;
+    }
+
+    // Keys with the same identity collapse into one `words` row, stored with the POS of the
+    // key with the most tokens, so merge their tokens to keep every occurrence of that row.
+    let mut words: HashMap<WordIdentity, (&Vec<String>, usize, WordTokens)> = HashMap::new();
+    for ((word, reading, pos), word_tokens) in word_map {
+        let pos_group = pos.first().map_or("", String::as_str);
+        let count = word_tokens.values().map(Vec::len).sum();
+        let entry = words
+            .entry((word.as_str(), reading.as_str(), pos_group))
+            .or_insert_with(|| (pos, 0, WordTokens::new()));
+        if count > entry.1 || (count == entry.1 && pos < entry.0) {
+            entry.0 = pos;
+            entry.1 = count;
+        }
+        merge_word_tokens(&mut entry.2, word_tokens.clone());
+    }
+
+    let word_keys: Vec<_> = words.iter().collect();
     for chunk in word_keys.chunks(1000) {
-        let placeholders: Vec<String> = chunk.iter().map(|_| "(?, ?, ?)".to_string()).collect();
+        let placeholders: Vec<String> = chunk.iter().map(|_| "(?, ?, ?, ?)".to_string()).collect();
         let sql = format!(
-            "INSERT OR IGNORE INTO words (word, reading, pos) VALUES {}",
+            "INSERT OR IGNORE INTO words (word, reading, pos, pos_group) VALUES {}",
             placeholders.join(", ")
         );
 
         let mut params = Vec::new();
-        for (word, reading, pos) in chunk {
-            let pos_json = if let Some(cached) = POS_CACHE.get(pos) {
+        for ((word, reading, pos_group), (pos, _, _)) in chunk {
+            let pos_json = if let Some(cached) = POS_CACHE.get(*pos) {
                 cached.as_str()
             } else {
                 &serde_json::to_string(pos).unwrap()
             };
 
-            params.push(word.clone());
-            params.push(reading.clone());
+            params.push(word.to_string());
+            params.push(reading.to_string());
             params.push(pos_json.to_string());
+            params.push(pos_group.to_string());
         }
 
         tx.execute(&sql, rusqlite::params_from_iter(params))?;
     }
 
-    let mut stmt_get_word_id = tx.prepare("SELECT id FROM words WHERE word = ?")?;
+    let mut stmt_get_word_id =
+        tx.prepare("SELECT id FROM words WHERE word = ?1 AND reading = ?2 AND pos_group = ?3")?;
+
+    for ((word, reading, pos_group), (_, _, word_tokens)) in words {
+        let word_id: i64 =
+            stmt_get_word_id.query_row([word, reading, pos_group], |row| row.get(0))?;
+
+        let occurrence_vec: Vec<_> = word_tokens.into_iter().collect();
+        for chunk in occurrence_vec.chunks(1000) {
+            let placeholders: Vec<String> = chunk.iter().map(|_| "(?, ?, ?)".to_string()).collect();
+            let sql = format!(
+                "INSERT OR IGNORE INTO word_occurrences (word_id, transcript_id, spans) VALUES {}",
+                placeholders.join(", ")
+            );
+
+            let mut params = Vec::new();
+            for (transcript_id, tokens) in chunk {
+                let spans: Vec<_> = tokens
+                    .iter()
+                    .map(|token| (token.start_char, token.end_char))
+                    .collect();
+                params.push(word_id.to_string());
+                params.push(transcript_id.to_string());
+                params.push(serde_json::to_string(&spans).unwrap());
+            }
 
-    for ((word, _, _), transcript_ids) in word_map {
-        let word_id: i64 = stmt_get_word_id.query_row([word], |row| row.get(0))?;
+            tx.execute(&sql, rusqlite::params_from_iter(params))?;
+        }
 
-        let transcript_vec: Vec<_> = transcript_ids.iter().collect();
-        for chunk in transcript_vec.chunks(1000) {
-            let placeholders: Vec<String> = chunk.iter().map(|_| "(?, ?)".to_string()).collect();
+        let token_rows: Vec<_> = occurrence_vec
+            .iter()
+            .flat_map(|(transcript_id, tokens)| {
+                tokens.iter().map(move |token| (transcript_id, token))
+            })
+            .collect();
+        for chunk in token_rows.chunks(1000) {
+            let placeholders: Vec<String> = chunk
+                .iter()
+                .map(|_| "(?, ?, ?, ?, ?, ?)".to_string())
+                .collect();
             let sql = format!(
-                "INSERT OR IGNORE INTO word_occurrences (word_id, transcript_id) VALUES {}",
+                "INSERT OR IGNORE INTO word_tokens
+                 (word_id, transcript_id, token_index, start_char, end_char, surface) VALUES {}",
                 placeholders.join(", ")
             );
 
             let mut params = Vec::new();
-            for &&transcript_id in chunk {
+            for (transcript_id, token) in chunk {
                 params.push(word_id.to_string());
                 params.push(transcript_id.to_string());
+                params.push(token.token_index.to_string());
+                params.push(token.start_char.to_string());
+                params.push(token.end_char.to_string());
+                params.push(token.surface.clone());
             }
 
             tx.execute(&sql, rusqlite::params_from_iter(params))?;
         }
     }
 
+    // Words no longer seen, e.g. a merged row whose senses now have rows of their own
+    tx.execute(
+        "DELETE FROM words WHERE id NOT IN (SELECT word_id FROM word_occurrences)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Records the lines this run analyzed, for the next incremental run
+fn mark_analyzed(tx: &Transaction, transcript_ids: &[i64], incremental: bool) -> Result<(), Error> {
+    if !incremental {
+        tx.execute("DELETE FROM analyzed_transcripts", [])?;
+    }
+    let mut stmt =
+        tx.prepare("INSERT OR IGNORE INTO analyzed_transcripts (transcript_id) VALUES (?1)")?;
+    for transcript_id in transcript_ids {
+        stmt.execute([transcript_id])?;
+    }
     Ok(())
 }
 
+/// Adds `source` tokens into `target`, keeping each line's tokens in order and unique
+fn merge_word_tokens(target: &mut WordTokens, source: WordTokens) {
+    for (transcript_id, tokens) in source {
+        let line_tokens = target.entry(transcript_id).or_default();
+        line_tokens.extend(tokens);
+        line_tokens.sort_unstable();
+        line_tokens.dedup();
+    }
+}
+
 fn create_main_indexes_tx(tx: &Transaction) -> Result<(), Error> {
     tx.execute(
         "CREATE INDEX IF NOT EXISTS idx_words_word_reading ON words(word, reading)",
@@ -322,21 +462,14 @@ fn create_main_indexes_tx(tx: &Transaction) -> Result<(), Error> {
     Ok(())
 }
 
-fn process_jlpt_data(conn: &mut Connection) -> Result<(), Error> {
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
-
-    if !std::path::Path::new("jlpt_levels.csv").exists() {
-        println!("JLPT levels CSV not found, skipping JLPT processing");
-        return Ok(());
-    }
+/// `word,level` rows, built into the binary so analysis doesn't depend on the working directory
+const JLPT_LEVELS_CSV: &str = include_str!("../../jlpt_levels.csv");
 
-    println!("Processing JLPT data...");
+fn process_jlpt_data(conn: &mut Connection) -> Result<(), Error> {
+    eprintln!("Processing JLPT data...");
     let tx = conn.transaction()?;
 
-    let reader = BufReader::new(File::open("jlpt_levels.csv")?);
-    for line in reader.lines() {
-        let line = line?;
+    for line in JLPT_LEVELS_CSV.lines() {
         if line.trim().is_empty() || line.starts_with("word,") {
             continue;
         }
@@ -354,7 +487,8 @@ fn process_jlpt_data(conn: &mut Connection) -> Result<(), Error> {
 
     tx.execute("DELETE FROM episode_jlpt_stats", [])?;
     tx.execute(
-        "
+        &format!(
+            "
         INSERT INTO episode_jlpt_stats (episode_id, n5_pct, n4_pct, n3_pct, n2_pct, n1_pct)
         SELECT 
             e.id,
@@ -368,22 +502,16 @@ fn process_jlpt_data(conn: &mut Connection) -> Result<(), Error> {
         JOIN word_occurrences wo ON wo.transcript_id = t.id
         JOIN words w ON w.id = wo.word_id
         LEFT JOIN jlpt_levels jl ON jl.word = w.word
-        WHERE NOT (
-            -- Proper nouns
-            (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '固有名詞')
-            -- Fillers, others, interjections
-            OR JSON_EXTRACT(w.pos, '$[0]') IN ('フィラー', 'その他')
-            OR (JSON_EXTRACT(w.pos, '$[0]') = '感動詞' AND JSON_EXTRACT(w.pos, '$[1]') = '間投')
-            -- Numbers
-            OR (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '数')
-        )
+        WHERE NOT {}
         GROUP BY e.id
     ",
+            EXCLUDED_POS_SQL
+        ),
         [],
     )?;
 
     tx.commit()?;
-    println!("JLPT processing completed!");
+    eprintln!("JLPT processing completed!");
 
     debug_jlpt_filtering(conn)?;
 
@@ -391,25 +519,23 @@ fn process_jlpt_data(conn: &mut Connection) -> Result<(), Error> {
 }
 
 fn debug_jlpt_filtering(conn: &Connection) -> Result<(), Error> {
-    println!("\n=== JLPT Filtering Debug Info ===");
+    eprintln!("\n=== JLPT Filtering Debug Info ===");
 
     // Total words before filtering
     let total_words: i32 = conn.query_row("SELECT COUNT(*) FROM words", [], |row| row.get(0))?;
-    println!("Total words in database: {}", total_words);
+    eprintln!("Total words in database: {}", total_words);
 
     // Words after POS filtering
     let filtered_words: i32 = conn.query_row(
-        "SELECT COUNT(*) FROM words w 
-         WHERE NOT (
-             (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '固有名詞')
-             OR JSON_EXTRACT(w.pos, '$[0]') IN ('フィラー', 'その他')
-             OR (JSON_EXTRACT(w.pos, '$[0]') = '感動詞' AND JSON_EXTRACT(w.pos, '$[1]') = '間投')
-             OR (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '数')
-         )",
+        &format!(
+            "SELECT COUNT(*) FROM words w 
+         WHERE NOT {}",
+            EXCLUDED_POS_SQL
+        ),
         [],
         |row| row.get(0),
     )?;
-    println!(
+    eprintln!(
         "Words after POS filtering: {} ({:.1}%)",
         filtered_words,
         filtered_words as f64 / total_words as f64 * 100.0
@@ -417,44 +543,38 @@ fn debug_jlpt_filtering(conn: &Connection) -> Result<(), Error> {
 
     // Words with JLPT levels
     let jlpt_words: i32 = conn.query_row(
-        "SELECT COUNT(*) FROM words w 
+        &format!(
+            "SELECT COUNT(*) FROM words w 
          JOIN jlpt_levels jl ON jl.word = w.word
-         WHERE NOT (
-             (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '固有名詞')
-             OR JSON_EXTRACT(w.pos, '$[0]') IN ('フィラー', 'その他')
-             OR (JSON_EXTRACT(w.pos, '$[0]') = '感動詞' AND JSON_EXTRACT(w.pos, '$[1]') = '間投')
-             OR (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '数')
-         )",
+         WHERE NOT {}",
+            EXCLUDED_POS_SQL
+        ),
         [],
         |row| row.get(0),
     )?;
-    println!(
+    eprintln!(
         "Words with JLPT levels (after filtering): {} ({:.1}%)",
         jlpt_words,
         jlpt_words as f64 / filtered_words as f64 * 100.0
     );
 
     // JLPT level distribution
-    println!("\nJLPT Level Distribution:");
-    let mut stmt = conn.prepare(
+    eprintln!("\nJLPT Level Distribution:");
+    let mut stmt = conn.prepare(&format!(
         "SELECT jl.level, COUNT(*) as count
          FROM words w 
          JOIN jlpt_levels jl ON jl.word = w.word
-         WHERE NOT (
-             (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '固有名詞')
-             OR JSON_EXTRACT(w.pos, '$[0]') IN ('フィラー', 'その他')
-             OR (JSON_EXTRACT(w.pos, '$[0]') = '感動詞' AND JSON_EXTRACT(w.pos, '$[1]') = '間投')
-             OR (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '数')
-         )
+         WHERE NOT {}
          GROUP BY jl.level ORDER BY jl.level DESC",
-    )?;
+        EXCLUDED_POS_SQL
+    ))?;
 
     let level_rows =
         stmt.query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, i32>(1)?)))?;
 
     for row in level_rows {
         let (level, count) = row?;
-        println!(
+        eprintln!(
             "  N{}: {} words ({:.1}%)",
             level,
             count,
@@ -462,20 +582,16 @@ fn debug_jlpt_filtering(conn: &Connection) -> Result<(), Error> {
         );
     }
 
-    println!("\nSample words WITHOUT JLPT levels (after filtering):");
-    let mut sample_stmt = conn.prepare(
+    eprintln!("\nSample words WITHOUT JLPT levels (after filtering):");
+    let mut sample_stmt = conn.prepare(&format!(
         "SELECT w.word, JSON_EXTRACT(w.pos, '$[0]') as pos1, JSON_EXTRACT(w.pos, '$[1]') as pos2
          FROM words w 
          LEFT JOIN jlpt_levels jl ON jl.word = w.word
          WHERE jl.word IS NULL
-         AND NOT (
-             (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '固有名詞')
-             OR JSON_EXTRACT(w.pos, '$[0]') IN ('フィラー', 'その他')
-             OR (JSON_EXTRACT(w.pos, '$[0]') = '感動詞' AND JSON_EXTRACT(w.pos, '$[1]') = '間投')
-             OR (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '数')
-         )
+         AND NOT {}
          LIMIT 20",
-    )?;
+        EXCLUDED_POS_SQL
+    ))?;
 
     let sample_rows = sample_stmt.query_map([], |row| {
         Ok((
@@ -487,23 +603,19 @@ fn debug_jlpt_filtering(conn: &Connection) -> Result<(), Error> {
 
     for row in sample_rows {
         let (word, pos1, pos2) = row?;
-        println!("  {} ({}, {})", word, pos1, pos2);
+        eprintln!("  {} ({}, {})", word, pos1, pos2);
     }
 
-    println!("\nPOS breakdown of words WITHOUT JLPT levels (after filtering):");
-    let mut pos_stmt = conn.prepare(
+    eprintln!("\nPOS breakdown of words WITHOUT JLPT levels (after filtering):");
+    let mut pos_stmt = conn.prepare(&format!(
         "SELECT JSON_EXTRACT(w.pos, '$[0]') as pos1, COUNT(*) as count
          FROM words w 
          LEFT JOIN jlpt_levels jl ON jl.word = w.word
          WHERE jl.word IS NULL
-         AND NOT (
-             (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '固有名詞')
-             OR JSON_EXTRACT(w.pos, '$[0]') IN ('フィラー', 'その他')
-             OR (JSON_EXTRACT(w.pos, '$[0]') = '感動詞' AND JSON_EXTRACT(w.pos, '$[1]') = '間投')
-             OR (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '数')
-         )
+         AND NOT {}
          GROUP BY pos1 ORDER BY count DESC LIMIT 10",
-    )?;
+        EXCLUDED_POS_SQL
+    ))?;
 
     let pos_rows = pos_stmt.query_map([], |row| {
         Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
@@ -511,9 +623,53 @@ fn debug_jlpt_filtering(conn: &Connection) -> Result<(), Error> {
 
     for row in pos_rows {
         let (pos, count) = row?;
-        println!("  {}: {} words", pos, count);
+        eprintln!("  {}: {} words", pos, count);
     }
 
-    println!("=== End Debug Info ===\n");
+    eprintln!("=== End Debug Info ===\n");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::unified_analyzer::WordToken;
+    use crate::test_utils::{create_test_db, create_test_hierarchy};
+
+    #[test]
+    fn test_full_run_drops_words_no_longer_seen() {
+        let (_file, mut handler) = create_test_db();
+        let (_show, _episode, transcript) = create_test_hierarchy(&handler);
+        let line = |surface: &str| -> WordTokens {
+            let token = WordToken {
+                token_index: 0,
+                start_char: 0,
+                end_char: 1,
+                surface: surface.to_string(),
+            };
+            HashMap::from([(transcript.id.unwrap() as i64, vec![token])])
+        };
+        let key = |reading: &str, pos: &str| {
+            let pos = vec![pos.to_string(), "一般".to_string()];
+            ("方".to_string(), reading.to_string(), pos)
+        };
+
+        // The first run reads 方 as ホウ, the second, e.g. after better disambiguation, as カタ
+        for reading in ["ホウ", "カタ"] {
+            let word_map = HashMap::from([(key(reading, "名詞"), line("方"))]);
+            let tx = handler.conn.transaction().unwrap();
+            batch_insert_words_and_occurrences(&tx, &word_map, false).unwrap();
+            tx.commit().unwrap();
+        }
+
+        let readings: Vec<String> = handler
+            .conn
+            .prepare("SELECT reading FROM words")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(readings, vec!["カタ".to_string()]);
+    }
+}
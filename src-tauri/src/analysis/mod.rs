@@ -1,4 +1,5 @@
 pub mod japanese_analyzer;
 pub mod kagome_server;
 pub mod morphology;
+pub mod query_tokenizer;
 pub mod unified_analyzer;
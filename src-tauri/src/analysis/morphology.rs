@@ -41,7 +41,7 @@ pub fn get_base_form_readings(
     let total_chunks = base_forms.len().div_ceil(CHUNK_SIZE);
 
     for (chunk_idx, chunk) in base_forms.chunks(CHUNK_SIZE).enumerate() {
-        println!(
+        eprintln!(
             "Processing reading corrections batch {}/{} ({} words)",
             chunk_idx + 1,
             total_chunks,
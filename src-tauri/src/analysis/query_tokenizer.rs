@@ -0,0 +1,58 @@
+use crate::analysis::kagome_server::KagomeServer;
+use crate::error::Error;
+use grammar_lib::{extract_vocabulary, AnalysisResult, KagomeToken};
+use std::sync::Mutex;
+
+/// Separate from the indexing server (6061) so searching during an analysis run works
+const QUERY_SERVER_PORT: u16 = 6063;
+
+/// Tokenizes search input. The Kagome server is only started the first time a query
+/// needs lemmatizing, and is shut down when the tokenizer is dropped.
+pub struct QueryTokenizer {
+    port: u16,
+    server: Mutex<Option<KagomeServer>>,
+}
+
+impl Default for QueryTokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueryTokenizer {
+    pub fn new() -> Self {
+        Self::with_port(QUERY_SERVER_PORT)
+    }
+
+    /// A tokenizer with its Kagome server on `port`, for processes running next to the app
+    pub fn with_port(port: u16) -> Self {
+        QueryTokenizer {
+            port,
+            server: Mutex::new(None),
+        }
+    }
+
+    /// Returns the dictionary forms of the content words in `text`, e.g. 食べていた → [食べる]
+    pub fn lemmatize(&self, text: &str) -> Result<Vec<String>, Error> {
+        let result = self.analyze(text)?;
+
+        Ok(extract_vocabulary(&result.tokens)
+            .into_iter()
+            .map(|word| word.base_form)
+            .filter(|base_form| base_form != "*")
+            .collect())
+    }
+
+    /// Tokenizes `text` and runs the grammar analysis on it
+    pub fn analyze(&self, text: &str) -> Result<AnalysisResult, Error> {
+        let mut server = self.server.lock().unwrap();
+        if server.is_none() {
+            *server = Some(KagomeServer::start(self.port)?);
+        }
+        let tokens = server.as_ref().unwrap().tokenize(text, "normal")?;
+
+        // Convert kagome_client::KagomeToken -> grammar_lib::KagomeToken via serde
+        let tokens: Vec<KagomeToken> = serde_json::from_value(serde_json::to_value(tokens)?)?;
+        Ok(grammar_lib::analyze(text, &tokens))
+    }
+}
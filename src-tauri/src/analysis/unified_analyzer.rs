@@ -2,12 +2,26 @@ use crate::analysis::kagome_server::KagomeServer;
 use crate::analysis::morphology::process_batch_with_kagome_server;
 use crate::db::grammar_pattern::GrammarPatternCollector;
 use crate::error::Error;
-use grammar_lib::{extract_vocabulary, KagomeToken, PatternCategory, VocabWord};
-use std::collections::{HashMap, HashSet};
+use grammar_lib::{extract_vocabulary_tokens, KagomeToken, PatternCategory, VocabWord};
+use std::collections::HashMap;
+
+/// One occurrence of a word in a line
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WordToken {
+    /// Position among the line's combined tokens
+    pub token_index: u32,
+    pub start_char: u32,
+    pub end_char: u32,
+    /// The word as written in the line, e.g. 食べた for 食べる
+    pub surface: String,
+}
+
+/// transcript_id -> occurrences of a word in that line
+pub type WordTokens = HashMap<i64, Vec<WordToken>>;
 
 #[derive(Debug)]
 pub struct UnifiedAnalysisResult {
-    pub words: HashMap<VocabWord, HashSet<i64>>, // vocabulary word -> transcript_ids
+    pub words: HashMap<VocabWord, WordTokens>, // vocabulary word -> tokens per transcript
     pub grammar_patterns: HashMap<i32, GrammarPatternCollector>, // episode_id -> collector
 }
 
@@ -19,8 +33,7 @@ pub fn analyze_batch(
     let token_arrays = process_batch_with_kagome_server(batch, server)?;
 
     let estimated_word_capacity = (batch.len() * 18) * 10 / 7;
-    let mut words: HashMap<VocabWord, HashSet<i64>> =
-        HashMap::with_capacity(estimated_word_capacity);
+    let mut words: HashMap<VocabWord, WordTokens> = HashMap::with_capacity(estimated_word_capacity);
     let estimated_episodes = (batch.len() / 20).max(1);
     let mut grammar_collectors = HashMap::with_capacity(estimated_episodes);
 
@@ -51,9 +64,24 @@ pub fn analyze_batch(
                 }
 
                 // Extract vocabulary from combined tokens (no auxiliary indices needed)
-                let vocab_words = extract_vocabulary(&result.tokens);
-                for word in vocab_words {
-                    words.entry(word).or_default().insert(transcript_id);
+                for token in extract_vocabulary_tokens(&result.tokens) {
+                    let mut word = token.word;
+                    // The reading is the word's own only when written in dictionary form, e.g.
+                    // not タベ for 食べた. Cleared readings are looked up from the base form.
+                    if token.surface != word.base_form {
+                        word.reading.clear();
+                    }
+                    words
+                        .entry(word)
+                        .or_default()
+                        .entry(transcript_id)
+                        .or_default()
+                        .push(WordToken {
+                            token_index: token.token_index,
+                            start_char: token.start,
+                            end_char: token.end,
+                            surface: token.surface,
+                        });
                 }
             }
         }
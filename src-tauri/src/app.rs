@@ -0,0 +1,530 @@
+//! The Tauri app: its managed state and the commands the frontend invokes.
+
+use crate::analysis::query_tokenizer::QueryTokenizer;
+use crate::corpus::{Corpora, CorpusInfo, DEFAULT_CORPUS};
+use crate::db::coverage::{CoverageOptions, EpisodeCoverage, ShowCoverage};
+use crate::db::grammar_pattern::{PatternQuery, PatternStats, RankedPattern};
+use crate::db::search::{SearchOptions, SearchResult, TextQuery};
+use crate::db::user_word::{IPlusOneLine, IPlusOneOptions, WordImportOptions, WordStatus};
+use crate::db::word::SurfaceForm;
+use crate::db::DbHandler;
+use crate::error::Error;
+use crate::export::anki::{AnkiExportOptions, ExportSummary};
+use crate::export::cards::CardTarget;
+use crate::export::cut_list::{format_cut_list, CutListOptions};
+use crate::export::frequency::{write_frequency_list, FrequencyEntry, FrequencyListOptions};
+use crate::export::study_list::{format_study_list, StudyListOptions, StudyWord};
+use crate::jobs::{EmitFn, JobEvent, JobId, JobKind, Jobs};
+use crate::subtitle_importer::process_srt_directory as parse_subtitles_from_directory;
+use crate::DATA_DIR_VAR;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use tauri::{AppHandle, Emitter, Manager};
+
+struct SubtitleDatabase(Mutex<DbHandler>);
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() -> Result<(), Error> {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_shell::init())
+        .setup(|app| {
+            // Corpora live in the platform's app data directory unless overridden, since the
+            // working directory of a packaged app may be / or read-only
+            let data_dir = match std::env::var_os(DATA_DIR_VAR) {
+                Some(dir) => PathBuf::from(dir),
+                None => app.path().app_data_dir()?,
+            };
+            let corpora = Corpora::new(&data_dir)?;
+
+            // Databases from before corpora were created in the working directory
+            if corpora.list()?.is_empty() {
+                let legacy_db = std::env::current_dir()
+                    .map(|dir| dir.join("transcripts.db"))
+                    .unwrap_or_default();
+                if legacy_db.is_file() {
+                    println!(
+                        "Importing {} as corpus '{}'",
+                        legacy_db.display(),
+                        DEFAULT_CORPUS
+                    );
+                    corpora.import(DEFAULT_CORPUS, &legacy_db)?;
+                }
+            }
+
+            // Opening migrates the database to the current schema
+            let subtitle_db = SubtitleDatabase(Mutex::new(corpora.open_active()?));
+
+            println!("Databases initialized successfully.");
+
+            // Store the database in the app's managed state for later use
+            app.manage(subtitle_db);
+            app.manage(corpora);
+            app.manage(Jobs::default());
+            app.manage(QueryTokenizer::new());
+
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            list_corpora,
+            create_corpus,
+            switch_corpus,
+            delete_corpus,
+            import_subtitles_from_directory,
+            analyze_japanese_transcripts,
+            cancel_job,
+            get_all_shows,
+            search_word_with_context,
+            get_word_surface_forms,
+            search_grammar_pattern_with_context,
+            search_query_with_context,
+            search_text_with_context,
+            get_grammar_pattern_stats,
+            get_top_grammar_patterns,
+            set_show_video_path_pattern,
+            export_cut_list,
+            export_anki_cards,
+            set_word_status,
+            import_word_list,
+            find_i_plus_one_sentences,
+            set_episode_watched,
+            get_episode_coverage,
+            rank_episodes_by_coverage,
+            get_show_coverage,
+            get_pre_study_list,
+            export_pre_study_list,
+            get_frequency_list,
+            export_frequency_list,
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+
+    Ok(())
+}
+
+/// Lists the corpora, marking the open one as active
+#[tauri::command]
+fn list_corpora(corpora: State<Corpora>) -> Result<Vec<CorpusInfo>, String> {
+    corpora.list().map_err(|err| err.to_string())
+}
+
+/// Creates an empty corpus, e.g. "Dramas"; `switch_corpus` opens it
+#[tauri::command]
+fn create_corpus(name: String, corpora: State<Corpora>) -> Result<CorpusInfo, String> {
+    corpora.create(&name).map_err(|err| err.to_string())
+}
+
+/// Opens a corpus in place of the current one; later commands use its database
+#[tauri::command]
+fn switch_corpus(
+    name: String,
+    corpora: State<Corpora>,
+    database: State<SubtitleDatabase>,
+) -> Result<(), String> {
+    let mut db = database.0.lock().unwrap();
+    *db = corpora.open(&name).map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+/// Deletes a corpus and its database files. The open corpus can't be deleted, nor any corpus
+/// while a job runs, as it may be working on it.
+#[tauri::command]
+fn delete_corpus(name: String, corpora: State<Corpora>, jobs: State<Jobs>) -> Result<(), String> {
+    if !jobs.running().is_empty() {
+        return Err("Corpora can't be deleted while a job is running".to_string());
+    }
+    corpora.delete(&name).map_err(|err| err.to_string())
+}
+
+/// Forwards a job's events to the UI as `job` events
+fn job_emitter(app: &AppHandle) -> EmitFn {
+    let app = app.clone();
+    Arc::new(move |event: JobEvent| {
+        if let Err(err) = app.emit("job", &event) {
+            eprintln!("Failed to emit job event: {}", err);
+        }
+    })
+}
+
+/// Path of the open corpus, for a job's own connection. The lock is only held to read it, so
+/// searches keep running on the app's connection while the job works.
+fn job_db_path(database: &SubtitleDatabase) -> Result<String, String> {
+    let db = database
+        .0
+        .lock()
+        .map_err(|e| format!("Database lock error: {}", e))?;
+    db.path()
+        .map(str::to_string)
+        .ok_or_else(|| "The open corpus has no database file".to_string())
+}
+
+/// Starts importing the subtitle files in `root_dir` in the background. Progress, unreadable
+/// files and the result arrive as `job` events.
+#[tauri::command]
+fn import_subtitles_from_directory(
+    root_dir: String,
+    app: AppHandle,
+    database: State<SubtitleDatabase>,
+    jobs: State<Jobs>,
+) -> Result<JobId, String> {
+    let path = job_db_path(&database)?;
+    jobs.spawn(JobKind::Import, job_emitter(&app), move |job| {
+        let show_entries = parse_subtitles_from_directory(Path::new(&root_dir), job)?;
+        let episode_count: usize = show_entries.iter().map(|show| show.episodes.len()).sum();
+        let line_count = DbHandler::new(&path)?.import_show_entries(&show_entries, job)?;
+        Ok(format!(
+            "Imported {} lines from {} episodes of {} shows",
+            line_count,
+            episode_count,
+            show_entries.len()
+        ))
+    })
+    .map_err(|err| err.to_string())
+}
+
+/// Starts analyzing the transcripts of the open corpus in the background, see
+/// `import_subtitles_from_directory`
+#[tauri::command]
+fn analyze_japanese_transcripts(
+    app: AppHandle,
+    database: State<SubtitleDatabase>,
+    jobs: State<Jobs>,
+) -> Result<JobId, String> {
+    let path = job_db_path(&database)?;
+    jobs.spawn(JobKind::Analysis, job_emitter(&app), move |job| {
+        DbHandler::new(&path)?.create_reverse_index(false, job)?;
+        Ok("Japanese transcript analysis completed successfully!".to_string())
+    })
+    .map_err(|err| err.to_string())
+}
+
+/// Asks a running job to stop after its current batch. Whatever it did so far is rolled back.
+#[tauri::command]
+fn cancel_job(job_id: JobId, jobs: State<Jobs>) -> bool {
+    jobs.cancel(job_id)
+}
+
+#[tauri::command]
+fn get_all_shows(database: State<SubtitleDatabase>) -> Result<Vec<(i32, String)>, String> {
+    let mut db = database.0.lock().unwrap();
+    let shows_ids = db.get_show_id_name_pairs().map_err(|err| err.to_string())?;
+    Ok(shows_ids)
+}
+
+/// Searches for a word by dictionary form, reading or inflected form. If several words match,
+/// the result lists them as "candidates"; pass the chosen one's id as `word_id` to search it.
+#[tauri::command]
+fn search_word_with_context(
+    word: String,
+    word_id: Option<i32>,
+    enabled_show_ids: Vec<i32>,
+    options: Option<SearchOptions>,
+    database: State<SubtitleDatabase>,
+    tokenizer: State<QueryTokenizer>,
+) -> Result<SearchResult, String> {
+    let options = options.unwrap_or_default();
+    let db = database.0.lock().unwrap();
+    let results = match word_id {
+        Some(word_id) => {
+            db.search_word_id_with_context(&word, word_id, &enabled_show_ids, &options)
+        }
+        None => db.search_word_with_context(&word, &enabled_show_ids, &options, |text| {
+            // Without a tokenizer, exact and kana matches still work
+            tokenizer.lemmatize(text).or_else(|err| {
+                eprintln!("Could not lemmatize search input: {}", err);
+                Ok(Vec::new())
+            })
+        }),
+    };
+    results.map_err(|err| err.to_string())
+}
+
+/// Lists how a word is written in the subtitles (食べた, 食べない, ...) with each form's count
+#[tauri::command]
+fn get_word_surface_forms(
+    word_id: i32,
+    database: State<SubtitleDatabase>,
+) -> Result<Vec<SurfaceForm>, String> {
+    let db = database.0.lock().unwrap();
+    db.get_word_surface_forms(word_id)
+        .map_err(|err| err.to_string())
+}
+
+/// Searches by grammar pattern name, or by JLPT level ('n3') to match every pattern at that level
+#[tauri::command]
+fn search_grammar_pattern_with_context(
+    pattern_name: Option<String>,
+    jlpt_level: Option<String>,
+    enabled_show_ids: Vec<i32>,
+    min_confidence: Option<f64>,
+    options: Option<SearchOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<SearchResult, String> {
+    let query = match (&pattern_name, &jlpt_level) {
+        (Some(name), _) => PatternQuery::Name(name),
+        (None, Some(level)) => PatternQuery::Level(level),
+        (None, None) => return Err("A pattern name or JLPT level is required".to_string()),
+    };
+
+    let db = database.0.lock().unwrap();
+    db.search_grammar_pattern_with_context(
+        query,
+        &enabled_show_ids,
+        min_confidence.unwrap_or(0.0),
+        &options.unwrap_or_default(),
+    )
+    .map_err(|err| err.to_string())
+}
+
+/// Searches with a compound query, e.g. `食べる pattern:te_shimau level:>=n3 -show:"Some Show"`
+#[tauri::command]
+fn search_query_with_context(
+    query: String,
+    enabled_show_ids: Vec<i32>,
+    options: Option<SearchOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<SearchResult, String> {
+    let db = database.0.lock().unwrap();
+    db.search_query_with_context(&query, &enabled_show_ids, &options.unwrap_or_default())
+        .map_err(|err| err.to_string())
+}
+
+/// Searches line text for whitespace-separated phrases, or for a regular expression if `regex` is set
+#[tauri::command]
+fn search_text_with_context(
+    text: String,
+    regex: Option<bool>,
+    enabled_show_ids: Vec<i32>,
+    options: Option<SearchOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<SearchResult, String> {
+    let query = if regex.unwrap_or(false) {
+        TextQuery::Regex(&text)
+    } else {
+        TextQuery::Phrase(&text)
+    };
+
+    let db = database.0.lock().unwrap();
+    db.search_text_with_context(query, &enabled_show_ids, &options.unwrap_or_default())
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+fn get_grammar_pattern_stats(
+    pattern_name: String,
+    database: State<SubtitleDatabase>,
+) -> Result<Option<PatternStats>, String> {
+    let db = database.0.lock().unwrap();
+    db.get_pattern_stats(&pattern_name)
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+fn get_top_grammar_patterns(
+    show_id: i32,
+    jlpt_level: Option<String>,
+    limit: Option<usize>,
+    database: State<SubtitleDatabase>,
+) -> Result<Vec<RankedPattern>, String> {
+    let db = database.0.lock().unwrap();
+    db.get_top_patterns_for_show(show_id, jlpt_level.as_deref(), limit.unwrap_or(20))
+        .map_err(|err| err.to_string())
+}
+
+/// Sets the pattern that locates a show's video files, e.g. `/anime/{show}/{name}.mkv`
+#[tauri::command]
+fn set_show_video_path_pattern(
+    show_id: i32,
+    pattern: Option<String>,
+    database: State<SubtitleDatabase>,
+) -> Result<(), String> {
+    let db = database.0.lock().unwrap();
+    db.set_show_video_path_pattern(show_id, pattern.as_deref())
+        .map_err(|err| err.to_string())
+}
+
+/// Writes a CSV or EDL cut list of clips covering the given lines to `path`, returning the
+/// number of clips
+#[tauri::command]
+fn export_cut_list(
+    transcript_ids: Vec<i32>,
+    path: String,
+    options: Option<CutListOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<usize, String> {
+    let options = options.unwrap_or_default();
+    let db = database.0.lock().unwrap();
+    let cuts = db
+        .get_cuts(&transcript_ids, &options.clips)
+        .map_err(|err| err.to_string())?;
+    std::fs::write(&path, format_cut_list(&cuts, &options))
+        .map_err(|err| format!("Failed to write {}: {}", path, err))?;
+    Ok(cuts.len())
+}
+
+/// Writes sentence cards for the given lines as an Anki package, TSV or CSV to `path`. Lines
+/// already exported for the same target are skipped unless `options.include_exported` is set.
+#[tauri::command]
+fn export_anki_cards(
+    transcript_ids: Vec<i32>,
+    target: Option<CardTarget>,
+    path: String,
+    options: Option<AnkiExportOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<ExportSummary, String> {
+    let db = database.0.lock().unwrap();
+    db.export_anki_cards(
+        &transcript_ids,
+        &target.unwrap_or_default(),
+        &options.unwrap_or_default(),
+        Path::new(&path),
+    )
+    .map_err(|err| err.to_string())
+}
+
+/// Marks a word as known, learning or ignored, or clears its status with `None`. With
+/// `word_id` only that sense of the word is marked.
+#[tauri::command]
+fn set_word_status(
+    word: String,
+    word_id: Option<i32>,
+    status: Option<WordStatus>,
+    database: State<SubtitleDatabase>,
+) -> Result<(), String> {
+    let db = database.0.lock().unwrap();
+    db.set_word_status(&word, word_id, status)
+        .map_err(|err| err.to_string())
+}
+
+/// Imports a plain word list or an Anki "Notes in Plain Text" export, returning the number of
+/// words imported
+#[tauri::command]
+fn import_word_list(
+    path: String,
+    options: Option<WordImportOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<usize, String> {
+    let mut db = database.0.lock().unwrap();
+    db.import_word_list(&path, &options.unwrap_or_default())
+        .map_err(|err| err.to_string())
+}
+
+/// Finds lines in the given shows with exactly one unknown word, most common words first
+#[tauri::command]
+fn find_i_plus_one_sentences(
+    shows: Vec<i32>,
+    options: Option<IPlusOneOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<Vec<IPlusOneLine>, String> {
+    let db = database.0.lock().unwrap();
+    db.find_i_plus_one(&shows, &options.unwrap_or_default())
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+fn set_episode_watched(
+    episode_id: i32,
+    watched: bool,
+    database: State<SubtitleDatabase>,
+) -> Result<(), String> {
+    let db = database.0.lock().unwrap();
+    db.set_episode_watched(episode_id, watched)
+        .map_err(|err| err.to_string())
+}
+
+/// Share of an episode's word tokens the user knows, and its most frequent unknown words
+#[tauri::command]
+fn get_episode_coverage(
+    episode_id: i32,
+    options: Option<CoverageOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<EpisodeCoverage, String> {
+    let db = database.0.lock().unwrap();
+    db.get_episode_coverage(episode_id, &options.unwrap_or_default())
+        .map_err(|err| err.to_string())
+}
+
+/// Episodes of the given shows ranked by coverage; with `unwatched_only` this answers "what
+/// can I follow best next"
+#[tauri::command]
+fn rank_episodes_by_coverage(
+    shows: Vec<i32>,
+    options: Option<CoverageOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<Vec<EpisodeCoverage>, String> {
+    let db = database.0.lock().unwrap();
+    db.rank_episodes_by_coverage(&shows, &options.unwrap_or_default())
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+fn get_show_coverage(
+    shows: Vec<i32>,
+    options: Option<CoverageOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<Vec<ShowCoverage>, String> {
+    let db = database.0.lock().unwrap();
+    db.get_show_coverage(&shows, &options.unwrap_or_default())
+        .map_err(|err| err.to_string())
+}
+
+/// The 20-50 words of an episode most worth learning before watching it, each with an example
+/// line
+#[tauri::command]
+fn get_pre_study_list(
+    episode_id: i32,
+    options: Option<StudyListOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<Vec<StudyWord>, String> {
+    let db = database.0.lock().unwrap();
+    db.get_study_list(episode_id, &options.unwrap_or_default())
+        .map_err(|err| err.to_string())
+}
+
+/// Writes an episode's pre-study list to `path` as JSON or CSV, returning the number of words
+#[tauri::command]
+fn export_pre_study_list(
+    episode_id: i32,
+    path: String,
+    options: Option<StudyListOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<usize, String> {
+    let options = options.unwrap_or_default();
+    let db = database.0.lock().unwrap();
+    let words = db
+        .get_study_list(episode_id, &options)
+        .map_err(|err| err.to_string())?;
+    let contents = format_study_list(&words, options.format).map_err(|err| err.to_string())?;
+    std::fs::write(&path, contents).map_err(|err| format!("Failed to write {}: {}", path, err))?;
+    Ok(words.len())
+}
+
+/// Word frequencies across the selected shows (all by default), most frequent first
+#[tauri::command]
+fn get_frequency_list(
+    options: Option<FrequencyListOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<Vec<FrequencyEntry>, String> {
+    let db = database.0.lock().unwrap();
+    db.get_frequency_list(&options.unwrap_or_default())
+        .map_err(|err| err.to_string())
+}
+
+/// Writes a frequency list to `path` as CSV or as a Yomitan frequency dictionary, returning
+/// the number of words
+#[tauri::command]
+fn export_frequency_list(
+    path: String,
+    options: Option<FrequencyListOptions>,
+    database: State<SubtitleDatabase>,
+) -> Result<usize, String> {
+    let options = options.unwrap_or_default();
+    let db = database.0.lock().unwrap();
+    let entries = db
+        .get_frequency_list(&options)
+        .map_err(|err| err.to_string())?;
+    write_frequency_list(&entries, &options, Path::new(&path)).map_err(|err| err.to_string())?;
+    Ok(entries.len())
+}
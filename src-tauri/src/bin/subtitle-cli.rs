@@ -0,0 +1,9 @@
+//! Import, indexing, search and export without the app window, see `--help`
+
+fn main() {
+    let args = std::env::args().skip(1).collect();
+    if let Err(err) = japanese_subtitle_parser_lib::cli::run(args) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,513 @@
+//! Headless command line interface over the same database and analysis code as the app, for
+//! batch jobs and scripts. Results are printed to stdout as JSON; progress and log messages go
+//! to stderr.
+
+use crate::analysis::query_tokenizer::QueryTokenizer;
+use crate::corpus::{Corpora, DEFAULT_CORPUS};
+use crate::db::grammar_pattern::PatternQuery;
+use crate::db::search::SearchOptions;
+use crate::db::DbHandler;
+use crate::error::Error;
+use crate::export::anki::AnkiExportOptions;
+use crate::export::cards::CardTarget;
+use crate::export::cut_list::{format_cut_list, CutListOptions};
+use crate::export::frequency::{write_frequency_list, FrequencyListOptions};
+use crate::export::study_list::{format_study_list, StudyListOptions};
+use crate::jobs::{JobContext, JobKind, JobUpdate};
+use crate::server::{self, ApiServer};
+use crate::subtitle_importer::process_srt_directory;
+use crate::DATA_DIR_VAR;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const USAGE: &str = "Usage:
+  subtitle-cli import <dir>                        import the .srt files in <dir>, one folder per show
+  subtitle-cli index [--incremental]               analyze the lines; --incremental only those imported since the last run
+  subtitle-cli search <word> [--word-id ID]        lines containing a word (dictionary, kana or inflected form)
+  subtitle-cli grammar <pattern|n5..n1> [--min-confidence X]
+                                                   lines matching a grammar pattern, or any pattern of a JLPT level
+  subtitle-cli stats [<pattern>]                   row counts, or a grammar pattern's occurrences by show
+  subtitle-cli export frequency <path>             word frequency list as CSV or a Yomitan dictionary
+  subtitle-cli export study-list <episode_id> <path>
+  subtitle-cli export anki <path> --lines ID,... [--word-id ID] [--pattern NAME]
+                                                   sentence cards for the given lines, about a word or grammar pattern
+  subtitle-cli export cut-list <path> --lines ID,...
+  subtitle-cli serve [--port N] [--allow-origin ORIGIN]
+                                                   local HTTP/JSON API on 127.0.0.1 (default port 6080); --allow-origin
+                                                   (repeatable) lets a browser extension call it, e.g. chrome-extension://<id>
+
+Database (default: the active corpus in $SUBTITLE_PARSER_DATA_DIR):
+  --db PATH           a database file, created if missing
+  --data-dir DIR      the app's data directory
+  --corpus NAME       a corpus other than the active one
+
+Options:
+  --show NAME         only these shows (repeatable; default: all) for search, grammar and export frequency
+  --options JSON      the options object of the matching app command, e.g. '{\"limit\": 100}'";
+
+/// Port of the CLI's own kagome, apart from the app's and the API server's so all can run at once
+const TOKENIZER_PORT: u16 = 6065;
+
+#[derive(Debug, Default)]
+struct CliArgs {
+    positional: Vec<String>,
+    db: Option<String>,
+    data_dir: Option<String>,
+    corpus: Option<String>,
+    shows: Vec<String>,
+    /// Raw JSON, parsed by the command into its options type
+    options: Option<String>,
+    lines: Vec<i32>,
+    word_id: Option<i32>,
+    pattern: Option<String>,
+    min_confidence: Option<f64>,
+    incremental: bool,
+    port: Option<u16>,
+    allowed_origins: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ImportSummary {
+    shows: usize,
+    episodes: usize,
+    lines: usize,
+    skipped_files: Vec<SkippedFile>,
+}
+
+#[derive(Debug, Serialize)]
+struct SkippedFile {
+    path: String,
+    error: String,
+}
+
+/// Runs the command in `args` (without the program name) and prints its result as JSON
+pub fn run(args: Vec<String>) -> Result<(), Error> {
+    let args = parse_args(args)?;
+    match args.positional.first().map(String::as_str) {
+        Some("help") => println!("{}", USAGE),
+        Some(_) => println!("{}", serde_json::to_string(&execute(&args)?)?),
+        None => return Err(Error::Other(USAGE.to_string())),
+    }
+    Ok(())
+}
+
+fn parse_args(raw: Vec<String>) -> Result<CliArgs, Error> {
+    let mut args = CliArgs::default();
+
+    let mut iter = raw.into_iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .ok_or_else(|| Error::Other(format!("Missing value for {}", name)))
+        };
+
+        match arg.as_str() {
+            "--db" => args.db = Some(value("--db")?),
+            "--data-dir" => args.data_dir = Some(value("--data-dir")?),
+            "--corpus" => args.corpus = Some(value("--corpus")?),
+            "--show" => args.shows.push(value("--show")?),
+            "--options" => args.options = Some(value("--options")?),
+            "--lines" => {
+                for id in value("--lines")?.split(',').filter(|id| !id.is_empty()) {
+                    args.lines.push(parse_number("--lines", id)?);
+                }
+            }
+            "--word-id" => args.word_id = Some(parse_number("--word-id", &value("--word-id")?)?),
+            "--pattern" => args.pattern = Some(value("--pattern")?),
+            "--min-confidence" => {
+                args.min_confidence = Some(parse_number(
+                    "--min-confidence",
+                    &value("--min-confidence")?,
+                )?)
+            }
+            "--incremental" => args.incremental = true,
+            "--port" => args.port = Some(parse_number("--port", &value("--port")?)?),
+            "--allow-origin" => args.allowed_origins.push(value("--allow-origin")?),
+            "-h" | "--help" => args.positional.insert(0, "help".to_string()),
+            flag if flag.starts_with("--") => {
+                return Err(Error::Other(format!(
+                    "Unknown option: {}\n\n{}",
+                    flag, USAGE
+                )))
+            }
+            _ => args.positional.push(arg),
+        }
+    }
+
+    Ok(args)
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, Error> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::Other(format!("Invalid value for {}: {}", name, value)))
+}
+
+fn execute(args: &CliArgs) -> Result<Value, Error> {
+    let command = args.positional[0].as_str();
+    // Opened once the command is known, so a typo doesn't create and migrate a database
+    let db = || open_database(args);
+
+    match command {
+        "import" => run_import(&mut db()?, args),
+        "index" => run_index(&mut db()?, args),
+        "search" => run_search(&mut db()?, args),
+        "grammar" => run_grammar(&mut db()?, args),
+        "stats" => run_stats(&db()?, args),
+        "export" => run_export(&mut db()?, args),
+        "serve" => run_serve(&db()?, args),
+        other => Err(Error::Other(format!(
+            "Unknown command: {}\n\n{}",
+            other, USAGE
+        ))),
+    }
+}
+
+/// The database from `--db`, or a corpus in the app's data directory. Corpora are opened
+/// without making them the app's active corpus.
+fn open_database(args: &CliArgs) -> Result<DbHandler, Error> {
+    let path = match &args.db {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let data_dir = args
+                .data_dir
+                .as_ref()
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os(DATA_DIR_VAR).map(PathBuf::from))
+                .ok_or_else(|| {
+                    Error::Other(format!(
+                        "No database given. Pass --db or --data-dir, or set {}.",
+                        DATA_DIR_VAR
+                    ))
+                })?;
+            let corpora = Corpora::new(&data_dir)?;
+            let name = match &args.corpus {
+                Some(name) => name.clone(),
+                None => corpora
+                    .active()?
+                    .unwrap_or_else(|| DEFAULT_CORPUS.to_string()),
+            };
+            corpora
+                .list()?
+                .into_iter()
+                .find(|corpus| corpus.name == name)
+                .map(|corpus| PathBuf::from(corpus.path))
+                .ok_or_else(|| Error::Other(format!("Corpus '{}' not found", name)))?
+        }
+    };
+
+    let handler = DbHandler::new(&path.to_string_lossy())?;
+    handler.create_tables()?;
+    Ok(handler)
+}
+
+// ========== Commands ==========
+
+fn run_import(db: &mut DbHandler, args: &CliArgs) -> Result<Value, Error> {
+    let root_dir = positional(args, 1, "import <dir>")?;
+    let skipped_files = Arc::new(Mutex::new(Vec::new()));
+    let job = cli_job(JobKind::Import, skipped_files.clone());
+
+    let show_entries = process_srt_directory(Path::new(root_dir), &job)?;
+    let lines = db.import_show_entries(&show_entries, &job)?;
+
+    let skipped_files = std::mem::take(&mut *skipped_files.lock().unwrap());
+    Ok(serde_json::to_value(ImportSummary {
+        shows: show_entries.len(),
+        episodes: show_entries.iter().map(|show| show.episodes.len()).sum(),
+        lines,
+        skipped_files,
+    })?)
+}
+
+fn run_index(db: &mut DbHandler, args: &CliArgs) -> Result<Value, Error> {
+    let job = cli_job(JobKind::Analysis, Arc::default());
+    db.create_reverse_index(args.incremental, &job)?;
+    Ok(table_counts(db))
+}
+
+fn run_search(db: &mut DbHandler, args: &CliArgs) -> Result<Value, Error> {
+    let word = positional(args, 1, "search <word>")?;
+    let shows = show_ids(db, &args.shows)?;
+    let options: SearchOptions = options(args)?;
+
+    let result = match args.word_id {
+        Some(word_id) => db.search_word_id_with_context(word, word_id, &shows, &options)?,
+        None => {
+            let tokenizer = QueryTokenizer::with_port(TOKENIZER_PORT);
+            db.search_word_with_context(word, &shows, &options, |text| {
+                // Without a tokenizer, exact and kana matches still work
+                tokenizer.lemmatize(text).or_else(|err| {
+                    eprintln!("Could not lemmatize search input: {}", err);
+                    Ok(Vec::new())
+                })
+            })?
+        }
+    };
+    Ok(serde_json::to_value(result)?)
+}
+
+fn run_grammar(db: &mut DbHandler, args: &CliArgs) -> Result<Value, Error> {
+    let pattern = positional(args, 1, "grammar <pattern|n5..n1>")?.to_lowercase();
+    let query = if is_jlpt_level(&pattern) {
+        PatternQuery::Level(&pattern)
+    } else {
+        PatternQuery::Name(&pattern)
+    };
+    let shows = show_ids(db, &args.shows)?;
+
+    let result = db.search_grammar_pattern_with_context(
+        query,
+        &shows,
+        args.min_confidence.unwrap_or(0.0),
+        &options(args)?,
+    )?;
+    Ok(serde_json::to_value(result)?)
+}
+
+fn run_stats(db: &DbHandler, args: &CliArgs) -> Result<Value, Error> {
+    match args.positional.get(1) {
+        Some(pattern) => {
+            let stats = db
+                .get_pattern_stats(pattern)?
+                .ok_or_else(|| Error::Other(format!("No occurrences of pattern {}", pattern)))?;
+            Ok(serde_json::to_value(stats)?)
+        }
+        None => Ok(table_counts(db)),
+    }
+}
+
+fn run_export(db: &mut DbHandler, args: &CliArgs) -> Result<Value, Error> {
+    let kind = positional(args, 1, "export <frequency|study-list|anki|cut-list> ...")?;
+    match kind {
+        "frequency" => {
+            let path = positional(args, 2, "export frequency <path>")?;
+            let mut options: FrequencyListOptions = options(args)?;
+            if !args.shows.is_empty() {
+                options.shows = Some(show_ids(db, &args.shows)?);
+            }
+            let entries = db.get_frequency_list(&options)?;
+            write_frequency_list(&entries, &options, Path::new(path))?;
+            Ok(json!({ "path": path, "words": entries.len() }))
+        }
+        "study-list" => {
+            let usage = "export study-list <episode_id> <path>";
+            let episode_id = parse_number("<episode_id>", positional(args, 2, usage)?)?;
+            let path = positional(args, 3, usage)?;
+            let options: StudyListOptions = options(args)?;
+            let words = db.get_study_list(episode_id, &options)?;
+            std::fs::write(path, format_study_list(&words, options.format)?)?;
+            Ok(json!({ "path": path, "words": words.len() }))
+        }
+        "anki" => {
+            let path = positional(args, 2, "export anki <path> --lines ID,...")?;
+            let options: AnkiExportOptions = options(args)?;
+            let target = CardTarget {
+                word_id: args.word_id,
+                pattern_name: args.pattern.clone(),
+            };
+            let summary = db.export_anki_cards(&args.lines, &target, &options, Path::new(path))?;
+            Ok(serde_json::to_value(summary)?)
+        }
+        "cut-list" => {
+            let path = positional(args, 2, "export cut-list <path> --lines ID,...")?;
+            let options: CutListOptions = options(args)?;
+            let cuts = db.get_cuts(&args.lines, &options.clips)?;
+            std::fs::write(path, format_cut_list(&cuts, &options))?;
+            Ok(json!({ "path": path, "clips": cuts.len() }))
+        }
+        other => Err(Error::Other(format!(
+            "Unknown export: {}\n\n{}",
+            other, USAGE
+        ))),
+    }
+}
+
+/// Serves the database until the process is stopped
+fn run_serve(db: &DbHandler, args: &CliArgs) -> Result<Value, Error> {
+    let path = db
+        .path()
+        .ok_or_else(|| Error::Other("serve needs a database file".to_string()))?;
+    let server = ApiServer::start(
+        path,
+        args.port.unwrap_or(server::DEFAULT_PORT),
+        args.allowed_origins.clone(),
+    )?;
+    eprintln!("Listening on http://127.0.0.1:{}", server.port());
+    server.wait();
+    Ok(Value::Null)
+}
+
+// ========== Helpers ==========
+
+fn positional<'a>(args: &'a CliArgs, index: usize, usage: &str) -> Result<&'a str, Error> {
+    args.positional
+        .get(index)
+        .map(String::as_str)
+        .ok_or_else(|| Error::Other(format!("Usage: subtitle-cli {}", usage)))
+}
+
+/// `--options` parsed as a command's options, or its defaults
+fn options<T: DeserializeOwned + Default>(args: &CliArgs) -> Result<T, Error> {
+    match &args.options {
+        Some(json) => Ok(serde_json::from_str(json)?),
+        None => Ok(T::default()),
+    }
+}
+
+/// Ids of the named shows, or of every show if none are named
+fn show_ids(db: &mut DbHandler, names: &[String]) -> Result<Vec<i32>, Error> {
+    let shows = db.get_show_id_name_pairs()?;
+    if names.is_empty() {
+        return Ok(shows.into_iter().map(|(id, _)| id).collect());
+    }
+    names
+        .iter()
+        .map(|name| {
+            shows
+                .iter()
+                .find(|(_, show)| show == name)
+                .map(|(id, _)| *id)
+                .ok_or_else(|| Error::Other(format!("Show '{}' not found", name)))
+        })
+        .collect()
+}
+
+fn is_jlpt_level(text: &str) -> bool {
+    matches!(text, "n1" | "n2" | "n3" | "n4" | "n5")
+}
+
+fn table_counts(db: &DbHandler) -> Value {
+    Value::Object(
+        db.table_counts()
+            .into_iter()
+            .map(|(table, count)| (table.to_string(), Value::from(count)))
+            .collect(),
+    )
+}
+
+/// A job that logs progress to stderr, at most once per percent, and collects skipped files
+fn cli_job(kind: JobKind, skipped_files: Arc<Mutex<Vec<SkippedFile>>>) -> JobContext {
+    let last_reported = Mutex::new((String::new(), usize::MAX));
+    JobContext::new(
+        0,
+        kind,
+        Arc::default(),
+        Arc::new(move |event| match event.update {
+            JobUpdate::Progress {
+                phase,
+                current,
+                total,
+                eta_secs,
+            } => {
+                let percent = (current * 100).checked_div(total).unwrap_or(100);
+                let mut last_reported = last_reported.lock().unwrap();
+                if *last_reported != (phase.clone(), percent) {
+                    let eta = eta_secs.map_or(String::new(), |secs| format!(", ~{}s left", secs));
+                    eprintln!("{}: {}/{} ({}%{})", phase, current, total, percent, eta);
+                    *last_reported = (phase, percent);
+                }
+            }
+            JobUpdate::FileError { path, error } => {
+                eprintln!("Skipped {}: {}", path, error);
+                skipped_files
+                    .lock()
+                    .unwrap()
+                    .push(SkippedFile { path, error });
+            }
+            JobUpdate::Finished { .. } => {}
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> CliArgs {
+        parse_args(args.iter().map(|arg| arg.to_string()).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = args(&[
+            "--db",
+            "test.db",
+            "search",
+            "食べる",
+            "--show",
+            "Show A",
+            "--show",
+            "Show B",
+            "--lines",
+            "1,2,3",
+            "--pattern",
+            "te_shimau",
+            "--options",
+            r#"{"limit": 5}"#,
+        ]);
+        assert_eq!(parsed.positional, vec!["search", "食べる"]);
+        assert_eq!(parsed.db.as_deref(), Some("test.db"));
+        assert_eq!(parsed.shows, vec!["Show A", "Show B"]);
+        assert_eq!(parsed.lines, vec![1, 2, 3]);
+        assert_eq!(parsed.pattern.as_deref(), Some("te_shimau"));
+        assert_eq!(options::<SearchOptions>(&parsed).unwrap().limit, 5);
+
+        assert!(parse_args(vec!["--lines".to_string(), "1,x".to_string()]).is_err());
+        assert!(parse_args(vec!["--unknown".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_import_and_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let show_dir = dir.path().join("subtitles").join("Test Show");
+        std::fs::create_dir_all(&show_dir).unwrap();
+        std::fs::write(
+            show_dir.join("Test Show 01.srt"),
+            "1\n00:00:01,000 --> 00:00:02,000\n食べる\n\n2\n00:00:03,000 --> 00:00:04,000\n見る\n",
+        )
+        .unwrap();
+        std::fs::write(show_dir.join("broken.srt"), "not a subtitle").unwrap();
+        let db_path = dir.path().join("test.db");
+        let db = db_path.to_str().unwrap();
+        let subtitles = dir.path().join("subtitles");
+
+        let error = execute(&args(&["--db", db, "imprt", subtitles.to_str().unwrap()]));
+        assert!(error.is_err());
+        assert!(!db_path.exists());
+
+        let summary = execute(&args(&["--db", db, "import", subtitles.to_str().unwrap()])).unwrap();
+        assert_eq!(summary["shows"], 1);
+        assert_eq!(summary["episodes"], 1);
+        assert_eq!(summary["lines"], 2);
+        assert_eq!(summary["skipped_files"].as_array().unwrap().len(), 1);
+
+        let stats = execute(&args(&["--db", db, "stats"])).unwrap();
+        assert_eq!(stats["transcripts"], 2);
+        assert_eq!(stats["analyzed_transcripts"], 0);
+
+        let error = execute(&args(&["--db", db, "search", "食べる", "--show", "Other"]));
+        assert!(error.is_err());
+
+        // Corpora are found through the data directory, by name or as the active one
+        let data_dir = dir.path().join("data");
+        let corpora = Corpora::new(&data_dir).unwrap();
+        corpora.import("Imported", &db_path).unwrap();
+        let data_dir = data_dir.to_str().unwrap();
+        let stats = execute(&args(&[
+            "--data-dir",
+            data_dir,
+            "--corpus",
+            "Imported",
+            "stats",
+        ]))
+        .unwrap();
+        assert_eq!(stats["shows"], 1);
+        assert!(execute(&args(&["--data-dir", data_dir, "stats"])).is_err());
+        assert_eq!(corpora.active().unwrap(), None);
+    }
+}
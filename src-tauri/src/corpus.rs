@@ -0,0 +1,265 @@
+//! Named corpora ("Anime", "Dramas", "Class set"), each its own SQLite database with separate
+//! indexes, kept in `<data dir>/corpora/<name>.db`. The active corpus is remembered in
+//! `<data dir>/corpora.json`.
+
+use crate::db::DbHandler;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Corpus created on first run
+pub const DEFAULT_CORPUS: &str = "Default";
+
+#[derive(Debug, Serialize)]
+pub struct CorpusInfo {
+    pub name: String,
+    pub path: String,
+    /// Size of the database file in bytes
+    pub size: u64,
+    pub active: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Settings {
+    active: Option<String>,
+}
+
+pub struct Corpora {
+    data_dir: PathBuf,
+}
+
+impl Corpora {
+    /// Keeps corpora in `data_dir`, creating it if needed
+    pub fn new(data_dir: &Path) -> Result<Self, Error> {
+        std::fs::create_dir_all(data_dir.join("corpora"))?;
+        Ok(Corpora {
+            data_dir: data_dir.to_path_buf(),
+        })
+    }
+
+    /// All corpora, by name
+    pub fn list(&self) -> Result<Vec<CorpusInfo>, Error> {
+        let active = self.active()?;
+        let mut corpora = Vec::new();
+        for entry in std::fs::read_dir(self.data_dir.join("corpora"))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "db") {
+                let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+                corpora.push(CorpusInfo {
+                    name: name.to_string(),
+                    path: path.to_string_lossy().into_owned(),
+                    size: std::fs::metadata(&path)?.len(),
+                    active: active.as_deref() == Some(name),
+                });
+            }
+        }
+        corpora.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(corpora)
+    }
+
+    /// Creates an empty corpus
+    pub fn create(&self, name: &str) -> Result<CorpusInfo, Error> {
+        let path = self.path(name)?;
+        if path.exists() {
+            return Err(Error::Other(format!("Corpus '{}' already exists", name)));
+        }
+        DbHandler::new(&path.to_string_lossy())?.create_tables()?;
+        Ok(CorpusInfo {
+            name: name.to_string(),
+            path: path.to_string_lossy().into_owned(),
+            size: std::fs::metadata(&path)?.len(),
+            active: false,
+        })
+    }
+
+    /// Opens a corpus, migrating it to the current schema, and makes it the active one
+    pub fn open(&self, name: &str) -> Result<DbHandler, Error> {
+        let path = self.path(name)?;
+        if !path.exists() {
+            return Err(Error::Other(format!("Corpus '{}' not found", name)));
+        }
+        let handler = DbHandler::new(&path.to_string_lossy())?;
+        handler.create_tables()?;
+        self.save(&Settings {
+            active: Some(name.to_string()),
+        })?;
+        Ok(handler)
+    }
+
+    /// Opens the corpus used last, creating the default corpus on first run
+    pub fn open_active(&self) -> Result<DbHandler, Error> {
+        let name = match self.active()? {
+            Some(name) if self.path(&name)?.exists() => name,
+            _ => {
+                if !self.path(DEFAULT_CORPUS)?.exists() {
+                    self.create(DEFAULT_CORPUS)?;
+                }
+                DEFAULT_CORPUS.to_string()
+            }
+        };
+        self.open(&name)
+    }
+
+    /// Deletes a corpus with its WAL files and migration backups. The active corpus can't be
+    /// deleted while it's open.
+    pub fn delete(&self, name: &str) -> Result<(), Error> {
+        if self.active()?.as_deref() == Some(name) {
+            return Err(Error::Other(format!(
+                "Corpus '{}' is in use. Switch to another corpus before deleting it.",
+                name
+            )));
+        }
+        let path = self.path(name)?;
+        if !path.exists() {
+            return Err(Error::Other(format!("Corpus '{}' not found", name)));
+        }
+        let file_name = format!("{}.db", name);
+        for entry in std::fs::read_dir(self.data_dir.join("corpora"))? {
+            let entry = entry?;
+            let entry_name = entry.file_name();
+            if is_corpus_file(&entry_name.to_string_lossy(), &file_name) {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies an existing database in as a new corpus, e.g. one from before corpora existed
+    pub fn import(&self, name: &str, source: &Path) -> Result<(), Error> {
+        let path = self.path(name)?;
+        if path.exists() {
+            return Err(Error::Other(format!("Corpus '{}' already exists", name)));
+        }
+        // Unlike copying the file, this includes changes still in the WAL
+        rusqlite::Connection::open(source)?.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Name of the active corpus, if one was opened before
+    pub fn active(&self) -> Result<Option<String>, Error> {
+        let path = self.data_dir.join("corpora.json");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let settings: Settings = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(settings.active)
+    }
+
+    fn save(&self, settings: &Settings) -> Result<(), Error> {
+        std::fs::write(
+            self.data_dir.join("corpora.json"),
+            serde_json::to_string_pretty(settings)?,
+        )?;
+        Ok(())
+    }
+
+    /// Database file of a corpus. Names become file names, so path separators, control
+    /// characters and leading dots are refused.
+    fn path(&self, name: &str) -> Result<PathBuf, Error> {
+        let valid = !name.trim().is_empty()
+            && name.trim() == name
+            && name.chars().count() <= 64
+            && !name.starts_with('.')
+            && !name.chars().any(|c| {
+                c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+            });
+        if !valid {
+            return Err(Error::Other(format!("Invalid corpus name '{}'", name)));
+        }
+        Ok(self.data_dir.join("corpora").join(format!("{}.db", name)))
+    }
+}
+
+/// The database file itself, its WAL and journal files, and migration backups
+/// (`<name>.db.v3.bak`, see `db::migrations`)
+fn is_corpus_file(entry: &str, file_name: &str) -> bool {
+    match entry.strip_prefix(file_name) {
+        Some("" | "-wal" | "-shm" | "-journal") => true,
+        Some(rest) => rest
+            .strip_prefix(".v")
+            .and_then(|rest| rest.strip_suffix(".bak"))
+            .is_some_and(|version| {
+                !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit())
+            }),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corpus_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let corpora = Corpora::new(dir.path()).unwrap();
+
+        // First run creates and opens the default corpus
+        corpora.open_active().unwrap();
+        assert_eq!(corpora.active().unwrap().as_deref(), Some(DEFAULT_CORPUS));
+
+        corpora.create("Class set").unwrap();
+        assert!(corpora.create("Class set").is_err());
+        let names: Vec<(String, bool)> = corpora
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|corpus| (corpus.name, corpus.active))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Class set".to_string(), false),
+                (DEFAULT_CORPUS.to_string(), true)
+            ]
+        );
+
+        let handler = corpora.open("Class set").unwrap();
+        handler
+            .conn
+            .execute(
+                "INSERT INTO shows (name, show_type) VALUES ('Test Show', 'Anime')",
+                [],
+            )
+            .unwrap();
+        drop(handler);
+        // Remembered for the next start, with separate data
+        assert_eq!(corpora.active().unwrap().as_deref(), Some("Class set"));
+        let shows: i64 = corpora
+            .open(DEFAULT_CORPUS)
+            .unwrap()
+            .conn
+            .query_row("SELECT COUNT(*) FROM shows", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(shows, 0);
+
+        assert!(corpora.delete(DEFAULT_CORPUS).is_err());
+        corpora.delete("Class set").unwrap();
+        assert!(corpora.open("Class set").is_err());
+        assert_eq!(corpora.list().unwrap().len(), 1);
+        // Only the default corpus's files are left
+        assert!(std::fs::read_dir(dir.path().join("corpora"))
+            .unwrap()
+            .all(|entry| entry
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with(DEFAULT_CORPUS)));
+
+        assert!(is_corpus_file("Anime.db.v3.bak", "Anime.db"));
+        assert!(!is_corpus_file("Anime.db.db", "Anime.db"));
+
+        let legacy = dir.path().join("transcripts.db");
+        DbHandler::new(legacy.to_str().unwrap())
+            .unwrap()
+            .create_tables()
+            .unwrap();
+        corpora.import("Imported", &legacy).unwrap();
+        corpora.open("Imported").unwrap();
+
+        assert!(corpora.create("../escape").is_err());
+        assert!(corpora.create("").is_err());
+    }
+}
@@ -1,10 +1,16 @@
 // db.rs
+pub mod clip;
+pub mod coverage;
 pub mod episode;
 pub mod grammar_pattern;
+pub mod migrations;
 pub mod model;
-mod search;
+pub mod search;
 pub mod show;
+// Searches read transcripts in bulk; the model is only used to set up tests
+#[cfg(test)]
 pub mod transcript;
 pub mod transcript_database;
+pub mod user_word;
 pub mod word;
 pub use self::transcript_database::DbHandler;
@@ -0,0 +1,96 @@
+//! Audio/video clip ranges around matched lines, for cutting flashcard clips
+
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_PADDING_MS: i64 = 500;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ClipOptions {
+    /// Milliseconds added before and after each line
+    pub padding_ms: i64,
+    /// Padded clips closer than this are merged into one, so back-and-forth dialogue becomes a
+    /// single clip. Overlapping clips are always merged.
+    pub merge_gap_ms: i64,
+}
+
+impl Default for ClipOptions {
+    fn default() -> Self {
+        Self {
+            padding_ms: DEFAULT_PADDING_MS,
+            merge_gap_ms: 0,
+        }
+    }
+}
+
+/// A time range within one episode and the lines it covers
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClipRange {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub transcript_ids: Vec<i32>,
+}
+
+/// Pads each `(transcript_id, time_start, time_end)` line and merges overlapping or nearby
+/// ranges. Lines must belong to the same episode.
+pub fn compute_clips(lines: &[(i32, i64, i64)], options: &ClipOptions) -> Vec<ClipRange> {
+    let mut lines = lines.to_vec();
+    lines.sort_by_key(|&(_, start, end)| (start, end));
+
+    let mut clips: Vec<ClipRange> = Vec::new();
+    for (id, start, end) in lines {
+        let start = (start - options.padding_ms).max(0);
+        let end = end + options.padding_ms;
+        match clips.last_mut() {
+            Some(clip) if start <= clip.end_ms + options.merge_gap_ms => {
+                clip.end_ms = clip.end_ms.max(end);
+                clip.transcript_ids.push(id);
+            }
+            _ => clips.push(ClipRange {
+                start_ms: start,
+                end_ms: end,
+                transcript_ids: vec![id],
+            }),
+        }
+    }
+    clips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_clips_pads_and_merges() {
+        let lines = [(3, 10_000, 12_000), (1, 300, 2_000), (2, 2_500, 4_000)];
+        let options = ClipOptions {
+            padding_ms: 500,
+            merge_gap_ms: 0,
+        };
+
+        let clips = compute_clips(&lines, &options);
+        assert_eq!(
+            clips,
+            vec![
+                ClipRange {
+                    start_ms: 0,
+                    end_ms: 4_500,
+                    transcript_ids: vec![1, 2]
+                },
+                ClipRange {
+                    start_ms: 9_500,
+                    end_ms: 12_500,
+                    transcript_ids: vec![3]
+                },
+            ]
+        );
+
+        let options = ClipOptions {
+            padding_ms: 0,
+            merge_gap_ms: 6_000,
+        };
+        let clips = compute_clips(&lines, &options);
+        assert_eq!(clips.len(), 1);
+        assert_eq!((clips[0].start_ms, clips[0].end_ms), (300, 12_000));
+    }
+}
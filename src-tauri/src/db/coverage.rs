@@ -0,0 +1,325 @@
+//! How much of an episode or show the user can already follow: the share of its word tokens
+//! that are known (see `user_word`), and the unknown words that come up most.
+//!
+//! Coverage counts occurrences, not unique words, so a word said ten times weighs ten times
+//! as much. Tokens are the spans stored per line in `word_occurrences`.
+
+use crate::db::user_word::{is_known_sql, USER_WORD_JOIN};
+use crate::db::word::TOKENS_SQL;
+use crate::error::Error;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CoverageOptions {
+    /// Count words being learned as known
+    pub learning_is_known: bool,
+    /// Leave watched episodes out of rankings
+    pub unwatched_only: bool,
+    /// Maximum episodes in a ranking, and unknown words listed for an episode
+    pub limit: usize,
+}
+
+impl Default for CoverageOptions {
+    fn default() -> Self {
+        Self {
+            learning_is_known: false,
+            unwatched_only: false,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EpisodeCoverage {
+    pub episode_id: i32,
+    pub show_id: i32,
+    pub show: String,
+    pub episode: Option<i32>,
+    pub episode_name: String,
+    pub season: Option<i32>,
+    pub watched: bool,
+    pub total_tokens: i64,
+    pub known_tokens: i64,
+    /// `known_tokens / total_tokens`, or 0 for an episode without analyzed lines
+    pub coverage: f64,
+    /// Most frequent unknown words first; only filled in for a single episode
+    pub unknown_words: Vec<UnknownWord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShowCoverage {
+    pub show_id: i32,
+    pub show: String,
+    pub total_tokens: i64,
+    pub known_tokens: i64,
+    pub coverage: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct UnknownWord {
+    pub word_id: i32,
+    pub word: String,
+    pub reading: Option<String>,
+    /// Tokens of the word in the episode
+    pub count: i64,
+}
+
+/// Coverage of one episode, with its most frequent unknown words
+pub fn get_episode_coverage(
+    conn: &Connection,
+    episode_id: i32,
+    options: &CoverageOptions,
+) -> Result<EpisodeCoverage, Error> {
+    let mut episodes = query_episode_coverage(
+        conn,
+        "e.id = ?",
+        vec![Value::Integer(episode_id as i64)],
+        options,
+        1,
+    )?;
+    let mut episode = episodes
+        .pop()
+        .ok_or_else(|| Error::Other(format!("Episode {} not found", episode_id)))?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT w.id, w.word, w.reading, SUM({tokens}) AS count
+         FROM transcripts t
+         JOIN word_occurrences wo ON wo.transcript_id = t.id
+         JOIN words w ON w.id = wo.word_id
+         {user_words}
+         WHERE t.episode_id = ?1 AND NOT {known}
+         GROUP BY w.id
+         ORDER BY count DESC, w.id
+         LIMIT ?2",
+        tokens = TOKENS_SQL,
+        user_words = USER_WORD_JOIN,
+        known = is_known_sql(options.learning_is_known)
+    ))?;
+    episode.unknown_words = stmt
+        .query_map(params![episode_id, options.limit as i64], |row| {
+            Ok(UnknownWord {
+                word_id: row.get(0)?,
+                word: row.get(1)?,
+                reading: row.get(2)?,
+                count: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(episode)
+}
+
+/// Episodes of the given shows, highest coverage first, e.g. to pick what to watch next
+pub fn rank_episodes_by_coverage(
+    conn: &Connection,
+    shows: &[i32],
+    options: &CoverageOptions,
+) -> Result<Vec<EpisodeCoverage>, Error> {
+    let mut filter = "e.show_id IN (SELECT value FROM json_each(?))".to_string();
+    if options.unwatched_only {
+        filter.push_str(" AND NOT e.watched");
+    }
+    query_episode_coverage(
+        conn,
+        &filter,
+        vec![Value::Text(serde_json::to_string(shows)?)],
+        options,
+        options.limit,
+    )
+}
+
+/// Coverage of each of the given shows over all its episodes, highest first
+pub fn get_show_coverage(
+    conn: &Connection,
+    shows: &[i32],
+    options: &CoverageOptions,
+) -> Result<Vec<ShowCoverage>, Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT s.id, s.name,
+                COALESCE(SUM({tokens}), 0) AS total,
+                COALESCE(SUM(CASE WHEN {known} THEN {tokens} ELSE 0 END), 0) AS known
+         FROM shows s
+         LEFT JOIN episodes e ON e.show_id = s.id
+         LEFT JOIN transcripts t ON t.episode_id = e.id
+         LEFT JOIN word_occurrences wo ON wo.transcript_id = t.id
+         LEFT JOIN words w ON w.id = wo.word_id
+         {user_words}
+         WHERE s.id IN (SELECT value FROM json_each(?1))
+         GROUP BY s.id
+         ORDER BY known * 1.0 / NULLIF(total, 0) DESC NULLS LAST, s.id",
+        tokens = TOKENS_SQL,
+        user_words = USER_WORD_JOIN,
+        known = is_known_sql(options.learning_is_known)
+    ))?;
+    let shows = stmt
+        .query_map(params![serde_json::to_string(shows)?], |row| {
+            let total_tokens = row.get(2)?;
+            let known_tokens = row.get(3)?;
+            Ok(ShowCoverage {
+                show_id: row.get(0)?,
+                show: row.get(1)?,
+                total_tokens,
+                known_tokens,
+                coverage: coverage(known_tokens, total_tokens),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(shows)
+}
+
+/// Per-episode token counts for episodes matching `filter` (over `e`), highest coverage first
+fn query_episode_coverage(
+    conn: &Connection,
+    filter: &str,
+    mut params: Vec<Value>,
+    options: &CoverageOptions,
+    limit: usize,
+) -> Result<Vec<EpisodeCoverage>, Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT e.id, s.id, s.name, e.episode_number, e.name, e.season, e.watched,
+                COALESCE(SUM({tokens}), 0) AS total,
+                COALESCE(SUM(CASE WHEN {known} THEN {tokens} ELSE 0 END), 0) AS known
+         FROM episodes e
+         JOIN shows s ON s.id = e.show_id
+         LEFT JOIN transcripts t ON t.episode_id = e.id
+         LEFT JOIN word_occurrences wo ON wo.transcript_id = t.id
+         LEFT JOIN words w ON w.id = wo.word_id
+         {user_words}
+         WHERE {filter}
+         GROUP BY e.id
+         ORDER BY known * 1.0 / NULLIF(total, 0) DESC NULLS LAST, e.id
+         LIMIT ?",
+        tokens = TOKENS_SQL,
+        user_words = USER_WORD_JOIN,
+        known = is_known_sql(options.learning_is_known),
+        filter = filter
+    ))?;
+    params.push(Value::Integer(limit as i64));
+
+    let episodes = stmt
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            let total_tokens = row.get(7)?;
+            let known_tokens = row.get(8)?;
+            Ok(EpisodeCoverage {
+                episode_id: row.get(0)?,
+                show_id: row.get(1)?,
+                show: row.get(2)?,
+                episode: row.get(3)?,
+                episode_name: row.get(4)?,
+                season: row.get(5)?,
+                watched: row.get(6)?,
+                total_tokens,
+                known_tokens,
+                coverage: coverage(known_tokens, total_tokens),
+                unknown_words: Vec::new(),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(episodes)
+}
+
+fn coverage(known_tokens: i64, total_tokens: i64) -> f64 {
+    if total_tokens == 0 {
+        0.0
+    } else {
+        known_tokens as f64 / total_tokens as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::episode::Episode;
+    use crate::db::user_word::{set_word_status, WordStatus};
+    use crate::db::word::{Word, WordOccurrence};
+    use crate::test_utils::{
+        create_test_db, create_test_episode, create_test_show, create_test_transcript,
+    };
+
+    #[test]
+    fn test_coverage_by_occurrence() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let easy = create_test_episode(&handler, &show, "Episode 1", Some(1));
+        let hard = create_test_episode(&handler, &show, "Episode 2", Some(2));
+        let empty = create_test_episode(&handler, &show, "Episode 3", Some(3));
+
+        let mut words = Vec::new();
+        for text in ["猫", "魚", "犬"] {
+            let mut word = Word::new(text.to_string(), None, "[]".to_string());
+            word.insert(conn).unwrap();
+            words.push(word.id.unwrap());
+        }
+        // Episode 1: 猫 three times, 魚 once; episode 2: 猫 once, 魚 twice, 犬 once
+        for (episode, line_id, occurrences) in [
+            (
+                &easy,
+                1,
+                vec![(words[0], vec![(0, 1), (2, 3)]), (words[1], vec![(4, 5)])],
+            ),
+            (&easy, 2, vec![(words[0], vec![(0, 1)])]),
+            (
+                &hard,
+                1,
+                vec![(words[0], vec![(0, 1)]), (words[1], vec![(2, 3), (4, 5)])],
+            ),
+            (&hard, 2, vec![(words[2], vec![(0, 1)])]),
+        ] {
+            let transcript = create_test_transcript(&handler, episode, line_id, 0, 1000, "猫");
+            for (word_id, spans) in occurrences {
+                WordOccurrence::new(word_id, transcript.id.unwrap(), spans)
+                    .insert(conn)
+                    .unwrap();
+            }
+        }
+        set_word_status(conn, "猫", None, Some(WordStatus::Known)).unwrap();
+        set_word_status(conn, "犬", None, Some(WordStatus::Learning)).unwrap();
+
+        let options = CoverageOptions::default();
+        let episode = get_episode_coverage(conn, hard.id.unwrap(), &options).unwrap();
+        assert_eq!((episode.known_tokens, episode.total_tokens), (1, 4));
+        let unknown: Vec<(&str, i64)> = episode
+            .unknown_words
+            .iter()
+            .map(|word| (word.word.as_str(), word.count))
+            .collect();
+        assert_eq!(unknown, vec![("魚", 2), ("犬", 1)]);
+
+        let shows = [show.id.unwrap()];
+        let ranked = rank_episodes_by_coverage(conn, &shows, &options).unwrap();
+        let order: Vec<(i32, f64)> = ranked
+            .iter()
+            .map(|episode| (episode.episode_id, episode.coverage))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (easy.id.unwrap(), 0.75),
+                (hard.id.unwrap(), 0.25),
+                (empty.id.unwrap(), 0.0)
+            ]
+        );
+
+        Episode::set_watched(conn, easy.id.unwrap(), true).unwrap();
+        let options = CoverageOptions {
+            learning_is_known: true,
+            unwatched_only: true,
+            limit: 1,
+        };
+        let ranked = rank_episodes_by_coverage(conn, &shows, &options).unwrap();
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].episode_id, hard.id.unwrap());
+        assert_eq!(ranked[0].coverage, 0.5);
+
+        let show_coverage = get_show_coverage(conn, &shows, &CoverageOptions::default()).unwrap();
+        assert_eq!(
+            (show_coverage[0].known_tokens, show_coverage[0].total_tokens),
+            (4, 8)
+        );
+    }
+}
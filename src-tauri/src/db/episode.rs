@@ -11,6 +11,8 @@ pub struct Episode {
     pub show_id: i32,
     pub name: String,
     pub episode_number: Option<i32>,
+    /// Season number, if the file name has one (S02E05)
+    pub season: Option<i32>,
 }
 
 impl Episode {
@@ -21,17 +23,37 @@ impl Episode {
             show_id,
             name,
             episode_number,
+            season: None,
         }
     }
 
-    /// Inserts the episode into the database
+    /// Inserts the episode into the database, or takes the id of the episode with the same
+    /// show, season and number if there is one
     pub fn insert(&mut self, conn: &Connection) -> Result<(), Error> {
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO episodes (show_id, name, episode_number, season) VALUES (?1, ?2, ?3, ?4)",
+            params![self.show_id, self.name, self.episode_number, self.season],
+        )?;
+        if inserted > 0 {
+            // Convert the last inserted row id to i32 and assign it to the episode's id field
+            crate::db::model::set_id_from_last_insert(&mut self.id, conn);
+        } else {
+            self.id = Some(conn.query_row(
+                "SELECT id FROM episodes
+                 WHERE show_id = ?1 AND IFNULL(season, 0) = IFNULL(?2, 0) AND episode_number = ?3",
+                params![self.show_id, self.season, self.episode_number],
+                |row| row.get(0),
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Marks an episode as watched or unwatched, for coverage rankings
+    pub fn set_watched(conn: &Connection, episode_id: i32, watched: bool) -> Result<(), Error> {
         conn.execute(
-            "INSERT OR IGNORE INTO episodes (show_id, name, episode_number) VALUES (?1, ?2, ?3)",
-            params![self.show_id, self.name, self.episode_number],
+            "UPDATE episodes SET watched = ?1 WHERE id = ?2",
+            params![watched, episode_id],
         )?;
-        // Convert the last inserted row id to i32 and assign it to the episode's id field
-        crate::db::model::set_id_from_last_insert(&mut self.id, conn);
         Ok(())
     }
 
@@ -39,8 +61,8 @@ impl Episode {
     #[cfg(test)]
     pub fn update(&self, conn: &Connection) -> Result<(), Error> {
         conn.execute(
-            "UPDATE episodes SET show_id = ?1, name = ?2, episode_number = ?3 WHERE id = ?4",
-            params![self.show_id, self.name, self.episode_number, self.id],
+            "UPDATE episodes SET show_id = ?1, name = ?2, episode_number = ?3, season = ?4 WHERE id = ?5",
+            params![self.show_id, self.name, self.episode_number, self.season, self.id],
         )?;
         Ok(())
     }
@@ -53,15 +75,18 @@ impl Episode {
     }
 
     /// Retrieves an episode from the database by ID
+    #[cfg(test)]
     pub fn get_by_id(conn: &Connection, id: i32) -> Result<Episode, Error> {
-        let mut stmt =
-            conn.prepare("SELECT id, show_id, name, episode_number FROM episodes WHERE id = ?1")?;
+        let mut stmt = conn.prepare(
+            "SELECT id, show_id, name, episode_number, season FROM episodes WHERE id = ?1",
+        )?;
         let episode = stmt.query_row(params![id], |row| {
             Ok(Episode {
                 id: Some(row.get(0)?),
                 show_id: row.get(1)?,
                 name: row.get(2)?,
                 episode_number: row.get(3)?,
+                season: row.get(4)?,
             })
         })?;
         Ok(episode)
@@ -70,13 +95,14 @@ impl Episode {
     /// Retrieves all episodes for a specific show
     #[cfg(test)]
     pub fn get_all_for_show(conn: &Connection, show_id: i32) -> Result<Vec<Episode>, Error> {
-        let mut stmt = conn.prepare("SELECT id, show_id, name, episode_number FROM episodes WHERE show_id = ?1 ORDER BY episode_number NULLS LAST")?;
+        let mut stmt = conn.prepare("SELECT id, show_id, name, episode_number, season FROM episodes WHERE show_id = ?1 ORDER BY episode_number NULLS LAST")?;
         let episodes_iter = stmt.query_map(params![show_id], |row| {
             Ok(Episode {
                 id: Some(row.get(0)?),
                 show_id: row.get(1)?,
                 name: row.get(2)?,
                 episode_number: row.get(3)?,
+                season: row.get(4)?,
             })
         })?;
 
@@ -88,14 +114,16 @@ impl Episode {
     /// Searches for episodes by name
     #[cfg(test)]
     pub fn search_by_name(conn: &Connection, search_term: &str) -> Result<Vec<Episode>, Error> {
-        let mut stmt = conn
-            .prepare("SELECT id, show_id, name, episode_number FROM episodes WHERE name LIKE ?1")?;
+        let mut stmt = conn.prepare(
+            "SELECT id, show_id, name, episode_number, season FROM episodes WHERE name LIKE ?1",
+        )?;
         let episodes_iter = stmt.query_map(params![format!("%{}%", search_term)], |row| {
             Ok(Episode {
                 id: Some(row.get(0)?),
                 show_id: row.get(1)?,
                 name: row.get(2)?,
                 episode_number: row.get(3)?,
+                season: row.get(4)?,
             })
         })?;
 
@@ -123,6 +151,7 @@ mod tests {
         let show = create_test_show(&handler, "Test Show", "Anime");
 
         let mut episode = Episode::new(show.id.unwrap(), "Test Episode".to_string(), Some(1));
+        episode.season = Some(2);
         episode.insert(&conn).unwrap();
 
         assert!(episode.id.is_some());
@@ -130,6 +159,57 @@ mod tests {
         let retrieved_episode = Episode::get_by_id(&conn, episode.id.unwrap()).unwrap();
         assert_eq!(retrieved_episode.name, "Test Episode");
         assert_eq!(retrieved_episode.episode_number, Some(1));
+        assert_eq!(retrieved_episode.season, Some(2));
+    }
+
+    #[test]
+    fn test_insert_episode_of_another_season() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+
+        let mut first = Episode::new(show.id.unwrap(), "S01E05".to_string(), Some(5));
+        first.season = Some(1);
+        first.insert(conn).unwrap();
+        let mut second = Episode::new(show.id.unwrap(), "S02E05".to_string(), Some(5));
+        second.season = Some(2);
+        second.insert(conn).unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(
+            Episode::get_by_id(conn, second.id.unwrap()).unwrap().season,
+            Some(2)
+        );
+
+        // Inserting an existing episode again gives its id
+        let mut again = Episode::new(show.id.unwrap(), "S01E05".to_string(), Some(5));
+        again.season = Some(1);
+        again.insert(conn).unwrap();
+        assert_eq!(again.id, first.id);
+        assert_eq!(
+            Episode::get_all_for_show(conn, show.id.unwrap())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_insert_episode_without_season_twice() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+
+        let mut first = Episode::new(show.id.unwrap(), "Episode 5".to_string(), Some(5));
+        first.insert(conn).unwrap();
+        let mut again = Episode::new(show.id.unwrap(), "Episode 5".to_string(), Some(5));
+        again.insert(conn).unwrap();
+        assert_eq!(again.id, first.id);
+        assert_eq!(
+            Episode::get_all_for_show(conn, show.id.unwrap())
+                .unwrap()
+                .len(),
+            1
+        );
     }
 
     #[test]
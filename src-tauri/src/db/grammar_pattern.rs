@@ -1,5 +1,6 @@
 use crate::error::Error;
-use rusqlite::Connection;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
 
 #[derive(Debug, Clone)]
 pub struct GrammarPatternOccurrence {
@@ -109,6 +110,278 @@ impl GrammarPatternCollector {
     }
 }
 
+/// Occurrence statistics for one grammar pattern across the database
+#[derive(Debug, Serialize)]
+pub struct PatternStats {
+    pub pattern_name: String,
+    pub jlpt_level: String,
+    pub total_occurrences: i64,
+    pub episode_count: i64,
+    pub show_count: i64,
+    /// Sorted by occurrences (descending)
+    pub shows: Vec<ShowPatternStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShowPatternStats {
+    pub show_id: i32,
+    pub show_name: String,
+    pub occurrences: i64,
+    pub episode_count: i64,
+    /// Occurrences per 1,000 transcript lines in the show
+    pub per_thousand_lines: f64,
+    pub first_episode: Option<FirstEpisode>,
+}
+
+/// Earliest episode (by episode number) in which a pattern appears
+#[derive(Debug, Serialize)]
+pub struct FirstEpisode {
+    pub episode_id: i32,
+    pub name: String,
+    pub episode_number: Option<i32>,
+}
+
+/// A pattern's ranking within a single show
+#[derive(Debug, Serialize)]
+pub struct RankedPattern {
+    pub pattern_name: String,
+    pub jlpt_level: String,
+    pub occurrences: i64,
+    pub episode_count: i64,
+    pub per_thousand_lines: f64,
+}
+
+/// Gets statistics for a pattern, broken down by show. Returns None if the pattern
+/// has never been recorded.
+pub fn get_pattern_stats(
+    conn: &Connection,
+    pattern_name: &str,
+) -> Result<Option<PatternStats>, Error> {
+    let Some((pattern_id, jlpt_level)) = conn
+        .query_row(
+            "SELECT id, jlpt_level FROM grammar_patterns WHERE pattern_name = ?1",
+            [pattern_name],
+            |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let (total_occurrences, episode_count, show_count) = conn.query_row(
+        "SELECT COUNT(*), COUNT(DISTINCT e.id), COUNT(DISTINCT e.show_id)
+         FROM grammar_pattern_occurrences gpo
+         JOIN transcripts t ON t.id = gpo.transcript_id
+         JOIN episodes e ON e.id = t.episode_id
+         WHERE gpo.pattern_id = ?1",
+        [pattern_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT s.id, s.name, COUNT(*) AS occurrences, COUNT(DISTINCT e.id),
+                (SELECT COUNT(*) FROM transcripts t2
+                 JOIN episodes e2 ON e2.id = t2.episode_id
+                 WHERE e2.show_id = s.id)
+         FROM grammar_pattern_occurrences gpo
+         JOIN transcripts t ON t.id = gpo.transcript_id
+         JOIN episodes e ON e.id = t.episode_id
+         JOIN shows s ON s.id = e.show_id
+         WHERE gpo.pattern_id = ?1
+         GROUP BY s.id
+         ORDER BY occurrences DESC, s.name",
+    )?;
+    let rows = stmt
+        .query_map([pattern_id], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, i64>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut shows = Vec::with_capacity(rows.len());
+    for (show_id, show_name, occurrences, show_episode_count, line_count) in rows {
+        shows.push(ShowPatternStats {
+            show_id,
+            show_name,
+            occurrences,
+            episode_count: show_episode_count,
+            per_thousand_lines: per_thousand(occurrences, line_count),
+            first_episode: get_first_episode(conn, pattern_id, show_id)?,
+        });
+    }
+
+    Ok(Some(PatternStats {
+        pattern_name: pattern_name.to_string(),
+        jlpt_level,
+        total_occurrences,
+        episode_count,
+        show_count,
+        shows,
+    }))
+}
+
+/// Gets the most common patterns in a show, optionally limited to one JLPT level ('n3' etc.)
+pub fn get_top_patterns_for_show(
+    conn: &Connection,
+    show_id: i32,
+    jlpt_level: Option<&str>,
+    limit: usize,
+) -> Result<Vec<RankedPattern>, Error> {
+    let line_count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM transcripts t
+         JOIN episodes e ON e.id = t.episode_id
+         WHERE e.show_id = ?1",
+        [show_id],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT gp.pattern_name, gp.jlpt_level, COUNT(*) AS occurrences, COUNT(DISTINCT e.id)
+         FROM grammar_pattern_occurrences gpo
+         JOIN grammar_patterns gp ON gp.id = gpo.pattern_id
+         JOIN transcripts t ON t.id = gpo.transcript_id
+         JOIN episodes e ON e.id = t.episode_id
+         WHERE e.show_id = ?1 AND (?2 IS NULL OR gp.jlpt_level = ?2)
+         GROUP BY gp.id
+         ORDER BY occurrences DESC, gp.pattern_name
+         LIMIT ?3",
+    )?;
+    let patterns = stmt
+        .query_map(params![show_id, jlpt_level, limit as i64], |row| {
+            let occurrences: i64 = row.get(2)?;
+            Ok(RankedPattern {
+                pattern_name: row.get(0)?,
+                jlpt_level: row.get(1)?,
+                occurrences,
+                episode_count: row.get(3)?,
+                per_thousand_lines: per_thousand(occurrences, line_count),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(patterns)
+}
+
+/// Selects patterns for a grammar search: a single pattern or every pattern at a JLPT level
+#[derive(Debug, Clone, Copy)]
+pub enum PatternQuery<'a> {
+    Name(&'a str),
+    Level(&'a str),
+}
+
+impl PatternQuery<'_> {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PatternQuery::Name(name) => name,
+            PatternQuery::Level(level) => level,
+        }
+    }
+
+    /// The `grammar_patterns` column (aliased `gp`) this query filters on
+    pub fn column(&self) -> &'static str {
+        match self {
+            PatternQuery::Name(_) => "gp.pattern_name",
+            PatternQuery::Level(_) => "gp.jlpt_level",
+        }
+    }
+
+    /// The value to compare `column()` against; levels are stored lowercase ('n3')
+    pub fn value(&self) -> String {
+        self.as_str().to_lowercase()
+    }
+}
+
+/// A grammar pattern match within a transcript line
+#[derive(Debug)]
+pub struct PatternSpan {
+    pub transcript_id: i32,
+    pub pattern_name: String,
+    pub confidence: f64,
+    pub start_char: u32,
+    pub end_char: u32,
+}
+
+/// Gets the occurrences of the queried pattern(s) with at least `min_confidence` in the given
+/// transcripts, ordered by transcript and position
+pub fn get_pattern_spans(
+    conn: &Connection,
+    query: PatternQuery,
+    min_confidence: f64,
+    transcript_ids: &[i32],
+) -> Result<Vec<PatternSpan>, Error> {
+    let sql = format!(
+        "SELECT gpo.transcript_id, gp.pattern_name, gpo.confidence, gpo.start_char, gpo.end_char
+         FROM grammar_pattern_occurrences gpo
+         JOIN grammar_patterns gp ON gp.id = gpo.pattern_id
+         WHERE {} = ?1 AND gpo.confidence >= ?2
+           AND gpo.transcript_id IN (SELECT value FROM json_each(?3))
+         ORDER BY gpo.transcript_id, gpo.start_char",
+        query.column()
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let spans = stmt
+        .query_map(
+            params![
+                query.value(),
+                min_confidence,
+                serde_json::to_string(transcript_ids)?
+            ],
+            |row| {
+                Ok(PatternSpan {
+                    transcript_id: row.get(0)?,
+                    pattern_name: row.get(1)?,
+                    confidence: row.get(2)?,
+                    start_char: row.get(3)?,
+                    end_char: row.get(4)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(spans)
+}
+
+fn get_first_episode(
+    conn: &Connection,
+    pattern_id: i32,
+    show_id: i32,
+) -> Result<Option<FirstEpisode>, Error> {
+    let episode = conn
+        .query_row(
+            "SELECT e.id, e.name, e.episode_number
+             FROM grammar_pattern_occurrences gpo
+             JOIN transcripts t ON t.id = gpo.transcript_id
+             JOIN episodes e ON e.id = t.episode_id
+             WHERE gpo.pattern_id = ?1 AND e.show_id = ?2
+             ORDER BY e.episode_number IS NULL, COALESCE(e.season, 0), e.episode_number, e.id
+             LIMIT 1",
+            [pattern_id, show_id],
+            |row| {
+                Ok(FirstEpisode {
+                    episode_id: row.get(0)?,
+                    name: row.get(1)?,
+                    episode_number: row.get(2)?,
+                })
+            },
+        )
+        .optional()?;
+    Ok(episode)
+}
+
+fn per_thousand(occurrences: i64, line_count: i64) -> f64 {
+    if line_count == 0 {
+        0.0
+    } else {
+        occurrences as f64 * 1000.0 / line_count as f64
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,4 +455,57 @@ mod tests {
             ("past_tense".to_string(), 2, 7.5, 5, 15)
         );
     }
+
+    #[test]
+    fn test_pattern_stats() {
+        use crate::test_utils::{create_test_episode, create_test_show, create_test_transcript};
+
+        let (_file, handler) = crate::test_utils::create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Show A", "Anime");
+        let ep2 = create_test_episode(&handler, &show, "Episode 2", Some(2));
+        let ep1 = create_test_episode(&handler, &show, "Episode 1", Some(1));
+
+        let mut transcript_ids = Vec::new();
+        for (episode, line) in [(&ep1, 1), (&ep1, 2), (&ep2, 1), (&ep2, 2)] {
+            let t =
+                create_test_transcript(&handler, episode, line, 0, 1000, &format!("line {}", line));
+            transcript_ids.push(t.id.unwrap() as i64);
+        }
+
+        let te_shimau = get_or_create_pattern_id(conn, "te_shimau", "n4").unwrap();
+        let you_ni_naru = get_or_create_pattern_id(conn, "you_ni_naru", "n3").unwrap();
+        GrammarPatternOccurrence::bulk_insert_optimized(
+            &[
+                GrammarPatternOccurrence::new(te_shimau, transcript_ids[2], 10.0, 0, 4),
+                GrammarPatternOccurrence::new(te_shimau, transcript_ids[3], 10.0, 0, 4),
+                GrammarPatternOccurrence::new(you_ni_naru, transcript_ids[0], 11.0, 2, 8),
+            ],
+            conn,
+        )
+        .unwrap();
+
+        let stats = get_pattern_stats(conn, "te_shimau").unwrap().unwrap();
+        assert_eq!(stats.total_occurrences, 2);
+        assert_eq!(stats.episode_count, 1);
+        assert_eq!(stats.show_count, 1);
+        assert_eq!(stats.shows[0].per_thousand_lines, 500.0);
+        assert_eq!(
+            stats.shows[0]
+                .first_episode
+                .as_ref()
+                .unwrap()
+                .episode_number,
+            Some(2)
+        );
+        assert!(get_pattern_stats(conn, "unknown").unwrap().is_none());
+
+        let top = get_top_patterns_for_show(conn, show.id.unwrap(), None, 10).unwrap();
+        let names: Vec<_> = top.iter().map(|p| p.pattern_name.as_str()).collect();
+        assert_eq!(names, vec!["te_shimau", "you_ni_naru"]);
+
+        let n3 = get_top_patterns_for_show(conn, show.id.unwrap(), Some("n3"), 10).unwrap();
+        assert_eq!(n3.len(), 1);
+        assert_eq!(n3[0].pattern_name, "you_ni_naru");
+    }
 }
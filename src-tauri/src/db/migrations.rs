@@ -0,0 +1,643 @@
+//! Versioned schema migrations for transcripts.db.
+//!
+//! `PRAGMA user_version` holds the number of migrations applied. Each migration runs in its own
+//! transaction together with the version bump, so a failing step leaves the database at the
+//! previous version. Databases from before versioning are at version 0 but may already have
+//! some of the later changes, so every step tolerates its own change being there.
+
+use crate::error::Error;
+use rusqlite::{Connection, Transaction};
+use std::path::PathBuf;
+
+struct Migration {
+    description: &'static str,
+    apply: fn(&Transaction) -> Result<(), Error>,
+}
+
+/// In order; append new migrations, never edit or reorder released ones
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "initial schema",
+        apply: initial_schema,
+    },
+    Migration {
+        description: "word occurrence spans",
+        apply: word_occurrence_spans,
+    },
+    Migration {
+        description: "full-text index of transcripts",
+        apply: transcripts_fts,
+    },
+    Migration {
+        description: "episode seasons",
+        apply: episode_seasons,
+    },
+    Migration {
+        description: "show video path patterns",
+        apply: show_video_path_patterns,
+    },
+    Migration {
+        description: "exported Anki notes",
+        apply: exported_notes,
+    },
+    Migration {
+        description: "user vocabulary",
+        apply: user_words,
+    },
+    Migration {
+        description: "watched episodes",
+        apply: watched_episodes,
+    },
+    Migration {
+        description: "word tokens",
+        apply: word_tokens,
+    },
+    Migration {
+        description: "words identified by reading and part of speech",
+        apply: word_identity,
+    },
+    Migration {
+        description: "analyzed transcripts",
+        apply: analyzed_transcripts,
+    },
+    Migration {
+        description: "episodes identified by season",
+        apply: episode_identity,
+    },
+    Migration {
+        description: "user words identified like words",
+        apply: user_word_identity,
+    },
+];
+
+/// Schema version of a fully migrated database
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Creates the schema of a new database, or brings an existing one up to `SCHEMA_VERSION`.
+/// A database with tables is first copied to `<path>.v<version>.bak`.
+pub fn migrate(conn: &Connection) -> Result<(), Error> {
+    let version = user_version(conn)?;
+    if version > SCHEMA_VERSION {
+        return Err(Error::Other(format!(
+            "Database schema version {} is newer than this app supports ({}). Update the app to open it.",
+            version, SCHEMA_VERSION
+        )));
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    if has_tables(conn)? {
+        if let Some(path) = backup(conn, version)? {
+            eprintln!("Backed up database to {}", path.display());
+        }
+    }
+    apply_migrations(conn, version, SCHEMA_VERSION)
+}
+
+fn apply_migrations(conn: &Connection, from: u32, to: u32) -> Result<(), Error> {
+    // Rebuilding a table (see `word_identity`) drops it, which foreign keys referencing it would
+    // refuse. They can only be switched off outside a transaction.
+    let foreign_keys: bool = conn.pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
+    conn.pragma_update(None, "foreign_keys", false)?;
+    let result = (from + 1..=to)
+        .zip(&MIGRATIONS[from as usize..to as usize])
+        .try_for_each(|(version, migration)| {
+            eprintln!(
+                "Migrating database to version {}: {}",
+                version, migration.description
+            );
+            let tx = conn.unchecked_transaction()?;
+            (migration.apply)(&tx)?;
+            tx.pragma_update(None, "user_version", version)?;
+            tx.commit()?;
+            Ok(())
+        });
+    conn.pragma_update(None, "foreign_keys", foreign_keys)?;
+    result
+}
+
+fn user_version(conn: &Connection) -> Result<u32, Error> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+fn has_tables(conn: &Connection) -> Result<bool, Error> {
+    Ok(conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table')",
+        [],
+        |row| row.get(0),
+    )?)
+}
+
+/// Copies the database next to itself, replacing an older copy of the same version.
+/// `VACUUM INTO` also picks up changes still in the WAL. In-memory and temporary databases
+/// have no path and aren't backed up.
+fn backup(conn: &Connection, version: u32) -> Result<Option<PathBuf>, Error> {
+    let path = match conn.path() {
+        Some(path) if !path.is_empty() => PathBuf::from(format!("{}.v{}.bak", path, version)),
+        _ => return Ok(None),
+    };
+    if path.exists() {
+        std::fs::remove_file(&path)?;
+    }
+    conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+    Ok(Some(path))
+}
+
+fn add_column_if_missing(
+    tx: &Transaction,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), Error> {
+    let mut stmt = tx.prepare(&format!(
+        "SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1",
+        table
+    ))?;
+    if !stmt.exists([column])? {
+        tx.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn initial_schema(tx: &Transaction) -> Result<(), Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS shows (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            show_type TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS episodes (
+            id INTEGER PRIMARY KEY,
+            show_id INTEGER,
+            name TEXT NOT NULL,
+            episode_number INTEGER,
+            UNIQUE(show_id, episode_number),
+            FOREIGN KEY(show_id) REFERENCES shows(id)
+        );
+        CREATE TABLE IF NOT EXISTS transcripts (
+            id INTEGER PRIMARY KEY,
+            episode_id INTEGER,
+            line_id INTEGER,
+            time_start INTEGER,
+            time_end INTEGER,
+            text TEXT NOT NULL,
+            UNIQUE(episode_id, line_id, time_start, time_end, text),
+            FOREIGN KEY(episode_id) REFERENCES episodes(id)
+        );
+        CREATE TABLE IF NOT EXISTS words (
+            id INTEGER PRIMARY KEY,
+            word TEXT NOT NULL UNIQUE,
+            reading TEXT,
+            pos TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS word_occurrences (
+            word_id INTEGER,
+            transcript_id INTEGER,
+            FOREIGN KEY(word_id) REFERENCES words(id),
+            FOREIGN KEY(transcript_id) REFERENCES transcripts(id),
+            UNIQUE(word_id, transcript_id)
+        );
+        CREATE TABLE IF NOT EXISTS jlpt_levels (
+            word TEXT PRIMARY KEY,
+            level INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS episode_jlpt_stats (
+            episode_id INTEGER PRIMARY KEY,
+            n5_pct REAL,
+            n4_pct REAL,
+            n3_pct REAL,
+            n2_pct REAL,
+            n1_pct REAL,
+            FOREIGN KEY(episode_id) REFERENCES episodes(id)
+        );
+        -- Simplified grammar patterns - just unique pattern definitions
+        CREATE TABLE IF NOT EXISTS grammar_patterns (
+            id INTEGER PRIMARY KEY,
+            pattern_name TEXT NOT NULL UNIQUE,     -- 'te_form', 'past_tense', etc.
+            jlpt_level TEXT NOT NULL               -- 'n5', 'n4', 'n3', 'n2', 'n1'
+        );
+        -- Grammar pattern occurrences linked to specific transcripts
+        CREATE TABLE IF NOT EXISTS grammar_pattern_occurrences (
+            pattern_id INTEGER,
+            transcript_id INTEGER,
+            confidence REAL,
+            start_char INTEGER,
+            end_char INTEGER,
+            PRIMARY KEY (pattern_id, transcript_id),
+            FOREIGN KEY (pattern_id) REFERENCES grammar_patterns(id),
+            FOREIGN KEY (transcript_id) REFERENCES transcripts(id)
+        );
+        -- Optimized indexing strategy for direct queries without pre-computed tables
+
+        -- Episode and transcript indexes
+        CREATE INDEX IF NOT EXISTS idx_episodes_show_id ON episodes(show_id);
+        CREATE INDEX IF NOT EXISTS idx_transcripts_episode_id ON transcripts(episode_id);
+        CREATE INDEX IF NOT EXISTS idx_transcripts_episode_line ON transcripts(episode_id, line_id);
+
+        -- Critical word query indexes (replaces word_stats and word_episodes tables)
+        CREATE INDEX IF NOT EXISTS idx_words_word ON words(word);
+        CREATE INDEX IF NOT EXISTS idx_words_reading ON words(reading);
+        CREATE INDEX IF NOT EXISTS idx_wo_word_episode ON word_occurrences(word_id, transcript_id);
+        CREATE INDEX IF NOT EXISTS idx_wo_word_count ON word_occurrences(word_id);
+        CREATE INDEX IF NOT EXISTS idx_word_occurrences_transcript_id ON word_occurrences(transcript_id);
+
+        -- JLPT stats indexes for filtering and sorting
+        CREATE INDEX IF NOT EXISTS idx_jlpt_levels_word ON jlpt_levels(word);
+        CREATE INDEX IF NOT EXISTS idx_jlpt_levels_level ON jlpt_levels(level);
+        CREATE INDEX IF NOT EXISTS idx_episode_jlpt_n5 ON episode_jlpt_stats(n5_pct DESC);
+        CREATE INDEX IF NOT EXISTS idx_episode_jlpt_n4 ON episode_jlpt_stats(n4_pct DESC);
+        CREATE INDEX IF NOT EXISTS idx_episode_jlpt_n3 ON episode_jlpt_stats(n3_pct DESC);
+        CREATE INDEX IF NOT EXISTS idx_episode_jlpt_n2 ON episode_jlpt_stats(n2_pct DESC);
+        CREATE INDEX IF NOT EXISTS idx_episode_jlpt_n1 ON episode_jlpt_stats(n1_pct DESC);
+
+        -- Grammar pattern indexes with confidence support
+        CREATE INDEX IF NOT EXISTS idx_grammar_patterns_name ON grammar_patterns(pattern_name);
+        CREATE INDEX IF NOT EXISTS idx_gpo_pattern_transcript ON grammar_pattern_occurrences(pattern_id, transcript_id);
+        CREATE INDEX IF NOT EXISTS idx_gpo_confidence ON grammar_pattern_occurrences(confidence);
+        CREATE INDEX IF NOT EXISTS idx_grammar_pattern_occurrences_transcript_id ON grammar_pattern_occurrences(transcript_id);
+        ",
+    )?;
+    Ok(())
+}
+
+fn word_occurrence_spans(tx: &Transaction) -> Result<(), Error> {
+    // JSON [[start_char, end_char], ...]
+    add_column_if_missing(
+        tx,
+        "word_occurrences",
+        "spans",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )
+}
+
+fn transcripts_fts(tx: &Transaction) -> Result<(), Error> {
+    // Substring search over line text. Trigrams work for Japanese, which has no spaces
+    // between words; triggers keep the index in sync with transcripts.
+    tx.execute_batch(
+        "
+        CREATE VIRTUAL TABLE IF NOT EXISTS transcripts_fts USING fts5(
+            text,
+            content='transcripts',
+            content_rowid='id',
+            tokenize='trigram'
+        );
+        CREATE TRIGGER IF NOT EXISTS transcripts_fts_insert AFTER INSERT ON transcripts BEGIN
+            INSERT INTO transcripts_fts(rowid, text) VALUES (new.id, new.text);
+        END;
+        CREATE TRIGGER IF NOT EXISTS transcripts_fts_delete AFTER DELETE ON transcripts BEGIN
+            INSERT INTO transcripts_fts(transcripts_fts, rowid, text) VALUES ('delete', old.id, old.text);
+        END;
+        CREATE TRIGGER IF NOT EXISTS transcripts_fts_update AFTER UPDATE OF text ON transcripts BEGIN
+            INSERT INTO transcripts_fts(transcripts_fts, rowid, text) VALUES ('delete', old.id, old.text);
+            INSERT INTO transcripts_fts(rowid, text) VALUES (new.id, new.text);
+        END;
+        -- Index lines imported before the full-text table existed
+        INSERT INTO transcripts_fts(transcripts_fts) VALUES ('rebuild');
+        ",
+    )?;
+    Ok(())
+}
+
+fn episode_seasons(tx: &Transaction) -> Result<(), Error> {
+    add_column_if_missing(tx, "episodes", "season", "INTEGER")
+}
+
+fn show_video_path_patterns(tx: &Transaction) -> Result<(), Error> {
+    // See export::cut_list::video_path
+    add_column_if_missing(tx, "shows", "video_path_pattern", "TEXT")
+}
+
+fn exported_notes(tx: &Transaction) -> Result<(), Error> {
+    // Notes already exported to Anki, by note GUID, so later exports skip them
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS exported_notes (
+            guid TEXT PRIMARY KEY,
+            transcript_id INTEGER NOT NULL,
+            exported_at INTEGER NOT NULL,
+            FOREIGN KEY (transcript_id) REFERENCES transcripts(id)
+        );
+        ",
+    )?;
+    Ok(())
+}
+
+fn user_words(tx: &Transaction) -> Result<(), Error> {
+    // The user's vocabulary, keyed by dictionary form like words.word
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS user_words (
+            word TEXT PRIMARY KEY,
+            status TEXT NOT NULL      -- 'known', 'learning' or 'ignored'
+        );
+        ",
+    )?;
+    Ok(())
+}
+
+fn watched_episodes(tx: &Transaction) -> Result<(), Error> {
+    add_column_if_missing(tx, "episodes", "watched", "INTEGER NOT NULL DEFAULT 0")
+}
+
+fn word_tokens(tx: &Transaction) -> Result<(), Error> {
+    // Every occurrence of a word, where word_occurrences has one row per word and line.
+    // Filled in by the next analysis.
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS word_tokens (
+            word_id INTEGER NOT NULL,
+            transcript_id INTEGER NOT NULL,
+            token_index INTEGER NOT NULL,          -- position among the line's combined tokens
+            start_char INTEGER NOT NULL,
+            end_char INTEGER NOT NULL,
+            surface TEXT NOT NULL,                 -- as written, e.g. 食べた for 食べる
+            PRIMARY KEY(transcript_id, token_index),
+            FOREIGN KEY(word_id) REFERENCES words(id),
+            FOREIGN KEY(transcript_id) REFERENCES transcripts(id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_word_tokens_word ON word_tokens(word_id);
+        ",
+    )?;
+    Ok(())
+}
+
+/// `words.word` was UNIQUE, merging homographs like 方 (ホウ/カタ) into one row. SQLite can't
+/// drop the constraint, so the table is rebuilt with the same ids; the next analysis then
+/// splits the merged occurrences.
+fn word_identity(tx: &Transaction) -> Result<(), Error> {
+    let has_pos_group: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('words') WHERE name = 'pos_group')",
+        [],
+        |row| row.get(0),
+    )?;
+    if has_pos_group {
+        return Ok(());
+    }
+
+    tx.execute_batch(
+        "
+        CREATE TABLE words_new (
+            id INTEGER PRIMARY KEY,
+            word TEXT NOT NULL,                    -- dictionary form
+            reading TEXT,
+            pos TEXT NOT NULL,
+            pos_group TEXT NOT NULL DEFAULT '',    -- coarse part of speech, e.g. 名詞
+            UNIQUE(word, reading, pos_group)
+        );
+        INSERT INTO words_new (id, word, reading, pos, pos_group)
+            SELECT id, word, reading, pos,
+                   CASE WHEN json_valid(pos) THEN IFNULL(json_extract(pos, '$[0]'), '') ELSE '' END
+            FROM words;
+        DROP TABLE words;
+        ALTER TABLE words_new RENAME TO words;
+        CREATE INDEX IF NOT EXISTS idx_words_word ON words(word);
+        CREATE INDEX IF NOT EXISTS idx_words_reading ON words(reading);
+        ",
+    )?;
+    Ok(())
+}
+
+/// Lines the analysis has seen, so an incremental run only analyzes lines imported since.
+/// Lines with words or patterns were analyzed before this table existed.
+fn analyzed_transcripts(tx: &Transaction) -> Result<(), Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS analyzed_transcripts (
+            transcript_id INTEGER PRIMARY KEY,
+            FOREIGN KEY(transcript_id) REFERENCES transcripts(id) ON DELETE CASCADE
+        );
+        INSERT OR IGNORE INTO analyzed_transcripts (transcript_id)
+            SELECT transcript_id FROM word_occurrences
+            UNION SELECT transcript_id FROM grammar_pattern_occurrences;
+        ",
+    )?;
+    Ok(())
+}
+
+/// Episodes were UNIQUE by (show, number), so S02E05 was taken for S01E05. Rebuilt like
+/// `words` in `word_identity`, keeping ids; episodes merged before stay merged. Identity is a
+/// unique index over `IFNULL(season, 0)`, since a UNIQUE constraint treats every NULL season
+/// as distinct and files without a season would be inserted again and again.
+fn episode_identity(tx: &Transaction) -> Result<(), Error> {
+    let has_index: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master
+                       WHERE type = 'index' AND name = 'idx_episodes_identity')",
+        [],
+        |row| row.get(0),
+    )?;
+    if has_index {
+        return Ok(());
+    }
+
+    tx.execute_batch(
+        "
+        CREATE TABLE episodes_new (
+            id INTEGER PRIMARY KEY,
+            show_id INTEGER,
+            name TEXT NOT NULL,
+            episode_number INTEGER,
+            season INTEGER,
+            watched INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY(show_id) REFERENCES shows(id)
+        );
+        INSERT INTO episodes_new (id, show_id, name, episode_number, season, watched)
+            SELECT id, show_id, name, episode_number, season, watched FROM episodes;
+        DROP TABLE episodes;
+        ALTER TABLE episodes_new RENAME TO episodes;
+        CREATE INDEX IF NOT EXISTS idx_episodes_show_id ON episodes(show_id);
+        CREATE UNIQUE INDEX idx_episodes_identity
+            ON episodes(show_id, IFNULL(season, 0), episode_number);
+        ",
+    )?;
+    Ok(())
+}
+
+/// Statuses were keyed by dictionary form only, so marking 方 (ホウ) known also marked 方
+/// (カタ). They're now keyed like `words`; the existing ones, like imported word lists, keep
+/// applying to every sense with an empty reading and part of speech.
+fn user_word_identity(tx: &Transaction) -> Result<(), Error> {
+    let has_pos_group: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('user_words') WHERE name = 'pos_group')",
+        [],
+        |row| row.get(0),
+    )?;
+    if has_pos_group {
+        return Ok(());
+    }
+
+    tx.execute_batch(
+        "
+        CREATE TABLE user_words_new (
+            word TEXT NOT NULL,                    -- dictionary form
+            reading TEXT NOT NULL DEFAULT '',      -- '' with pos_group '' for every sense
+            pos_group TEXT NOT NULL DEFAULT '',
+            status TEXT NOT NULL,                  -- 'known', 'learning' or 'ignored'
+            PRIMARY KEY (word, reading, pos_group)
+        );
+        INSERT INTO user_words_new (word, status) SELECT word, status FROM user_words;
+        DROP TABLE user_words;
+        ALTER TABLE user_words_new RENAME TO user_words;
+        ",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DbHandler;
+    use std::path::Path;
+
+    /// Tables with their columns, then indexes and triggers
+    fn schema(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT m.name || '.' || p.name || ' ' || p.type || ' ' || p.\"notnull\"
+                        || ' ' || IFNULL(p.dflt_value, '')
+                 FROM sqlite_master m JOIN pragma_table_info(m.name) p
+                 WHERE m.type = 'table'
+                 UNION ALL
+                 SELECT type || ' ' || name FROM sqlite_master WHERE type IN ('index', 'trigger')
+                 ORDER BY 1",
+            )
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    /// Rows every schema version can hold
+    fn insert_fixture(conn: &Connection) {
+        conn.execute_batch(
+            r#"
+            INSERT INTO shows (id, name, show_type) VALUES (1, 'Test Show', 'Anime');
+            INSERT INTO episodes (id, show_id, name, episode_number) VALUES (1, 1, 'Episode 1', 1);
+            INSERT INTO transcripts (id, episode_id, line_id, time_start, time_end, text)
+                VALUES (1, 1, 1, 0, 1000, 'ご飯を食べる');
+            INSERT INTO words (id, word, reading, pos) VALUES (1, '食べる', 'タベル', '["動詞","自立"]');
+            INSERT INTO word_occurrences (word_id, transcript_id) VALUES (1, 1);
+            "#,
+        )
+        .unwrap();
+    }
+
+    fn open(path: &Path) -> DbHandler {
+        DbHandler::new(path.to_str().unwrap()).unwrap()
+    }
+
+    fn current_schema() -> Vec<String> {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        schema(&conn)
+    }
+
+    #[test]
+    fn test_new_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcripts.db");
+        let handler = open(&path);
+        handler.create_tables().unwrap();
+
+        assert_eq!(user_version(&handler.conn).unwrap(), SCHEMA_VERSION);
+        assert_eq!(schema(&handler.conn), current_schema());
+        // Nothing to back up
+        assert!(!dir.path().join("transcripts.db.v0.bak").exists());
+        // Already current
+        handler.create_tables().unwrap();
+    }
+
+    #[test]
+    fn test_migrates_fixture_from_each_version() {
+        let current = current_schema();
+        for version in 0..SCHEMA_VERSION {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("transcripts.db");
+            {
+                // Version 0 is a database from before versioning, with the initial schema
+                let handler = open(&path);
+                apply_migrations(&handler.conn, 0, version.max(1)).unwrap();
+                handler
+                    .conn
+                    .pragma_update(None, "user_version", version)
+                    .unwrap();
+                insert_fixture(&handler.conn);
+            }
+
+            let handler = open(&path);
+            handler.create_tables().unwrap();
+            let conn = &handler.conn;
+            assert_eq!(user_version(conn).unwrap(), SCHEMA_VERSION);
+            assert_eq!(schema(conn), current, "migrating from version {}", version);
+
+            let (word, reading, spans): (String, String, String) = conn
+                .query_row(
+                    "SELECT w.word, w.reading, wo.spans FROM word_occurrences wo
+                     JOIN words w ON w.id = wo.word_id WHERE wo.transcript_id = 1",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap();
+            assert_eq!((word.as_str(), reading.as_str()), ("食べる", "タベル"));
+            assert_eq!(spans, "[]");
+            let found: i32 = conn
+                .query_row(
+                    "SELECT rowid FROM transcripts_fts WHERE transcripts_fts MATCH '食べる'",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(found, 1);
+            let watched: bool = conn
+                .query_row("SELECT watched FROM episodes WHERE id = 1", [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert!(!watched);
+            if version == 0 {
+                let pos_group: String = conn
+                    .query_row("SELECT pos_group FROM words WHERE id = 1", [], |row| {
+                        row.get(0)
+                    })
+                    .unwrap();
+                assert_eq!(pos_group, "動詞");
+            }
+
+            let backup =
+                Connection::open(dir.path().join(format!("transcripts.db.v{}.bak", version)))
+                    .unwrap();
+            assert_eq!(user_version(&backup).unwrap(), version);
+            let lines: i64 = backup
+                .query_row("SELECT COUNT(*) FROM transcripts", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(lines, 1);
+        }
+    }
+
+    #[test]
+    fn test_unversioned_database_with_later_changes() {
+        // Builds from before versioning created every table they knew of, so a version 0
+        // database may already have any of the changes
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        insert_fixture(&conn);
+        conn.pragma_update(None, "user_version", 0).unwrap();
+
+        migrate(&conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), SCHEMA_VERSION);
+        assert_eq!(schema(&conn), current_schema());
+    }
+
+    #[test]
+    fn test_rejects_newer_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        assert!(migrate(&conn).is_err());
+    }
+}
@@ -1,129 +1,327 @@
-use crate::db::episode::Episode;
-use crate::db::show::Show;
-use crate::db::transcript::Transcript;
+use crate::db::grammar_pattern::{self, PatternQuery, PatternSpan};
 use crate::db::word::Word;
 use crate::error::Error;
+use page::{HitSource, Page};
+use results::build_results;
+use rusqlite::types::Value;
 use rusqlite::Connection;
-use serde_json::{json, Value as JsonValue};
 use std::collections::HashMap;
 
-/// Searches for a keyword in the transcripts of specified shows and returns the results with surrounding transcripts for context
+mod page;
+mod query;
+mod results;
+mod text;
+
+pub use page::SearchOptions;
+pub use results::{Highlight, SearchResult};
+pub use text::TextQuery;
+
+/// Transcript id -> spans to mark in that line
+type Highlights = HashMap<i32, Vec<Highlight>>;
+
+/// Searches for a keyword in the transcripts of specified shows and returns the results with surrounding transcripts for context.
+///
+/// The keyword may be a dictionary form, a kana reading (たべる, タベル) or an inflected form
+/// (食べた); `lemmatize` supplies dictionary forms for input that matches no word directly.
+/// When several words fit (かける → 掛ける/書ける/欠ける) no lines are returned and the
+/// "candidates" list lets the caller pick one for `search_word_id_with_context`.
 pub fn search_word_with_context(
     conn: &Connection,
     keyword: &str,
     shows: &[i32],
-) -> Result<JsonValue, Error> {
-    let transcripts = get_transcripts_for_word(conn, keyword, shows)?;
-    let results = build_results(conn, &transcripts)?;
-    let final_json = create_final_json(keyword, results);
-    Ok(final_json)
-}
-
-/// Retrieves all transcripts that contain the given word, filtered by an array of show IDs
-fn get_transcripts_for_word(
-    conn: &Connection,
-    word: &str,
-    show_ids: &[i32],
-) -> Result<Vec<Transcript>, Error> {
-    let word_entry = Word::get_by_word(conn, word)?;
-    word_entry.get_transcripts(conn, show_ids)
-}
+    options: &SearchOptions,
+    lemmatize: impl FnOnce(&str) -> Result<Vec<String>, Error>,
+) -> Result<SearchResult, Error> {
+    let mut candidates = Word::find_candidates(conn, keyword)?;
+    if candidates.is_empty() {
+        for lemma in lemmatize(keyword)? {
+            for candidate in Word::find_candidates(conn, &lemma)? {
+                if !candidates.iter().any(|c| c.id == candidate.id) {
+                    candidates.push(candidate);
+                }
+            }
+        }
+    }
 
-/// Builds a structured JSON result from the transcripts, grouped by show and episode
-fn build_results(conn: &Connection, transcripts: &[Transcript]) -> Result<Vec<JsonValue>, Error> {
-    let mut show_map: HashMap<i32, JsonValue> = HashMap::new();
+    if let [candidate] = candidates.as_slice() {
+        return search_word_id_with_context(conn, keyword, candidate.id, shows, options);
+    }
 
-    for transcript in transcripts {
-        let episode = Episode::get_by_id(conn, transcript.episode_id)?;
-        let show = Show::get_by_id(conn, episode.show_id)?;
+    let mut result = SearchResult::new(keyword, Vec::new(), Page::default());
+    result.candidates = Some(candidates);
+    Ok(result)
+}
 
-        let show_entry = show_map.entry(show.id.unwrap()).or_insert_with(|| {
-            json!({
-                "show": show.name,
-                "instances": []
-            })
-        });
+/// Searches for a specific word (e.g. one picked from the "candidates" of an ambiguous search)
+/// and returns the results with surrounding transcripts for context
+pub fn search_word_id_with_context(
+    conn: &Connection,
+    keyword: &str,
+    word_id: i32,
+    shows: &[i32],
+    options: &SearchOptions,
+) -> Result<SearchResult, Error> {
+    let word_entry = Word::get_by_id(conn, word_id)?;
+    let candidate = Word::get_candidate(conn, word_id)?;
+    let hits = HitSource {
+        sql: "SELECT transcript_id FROM word_occurrences WHERE word_id = ?".to_string(),
+        params: vec![Value::Integer(word_id as i64)],
+    };
+    let page = page::fetch_page(conn, &hits, shows, options)?;
 
-        add_instance_to_show(show_entry, conn, &episode, transcript.line_id)?;
+    // Spans point at the form as written, so 見る highlights 見ていた
+    let mut highlights: Highlights = HashMap::new();
+    for (transcript_id, spans) in word_entry.get_spans(conn, &page.ids)? {
+        push_word_spans(&mut highlights, transcript_id, spans);
     }
 
-    Ok(show_map.into_values().collect())
+    let results = build_results(conn, &page.ids, &highlights, options)?;
+    let mut result = SearchResult::new(keyword, results, page);
+    result.candidates = Some(vec![candidate]);
+    Ok(result)
 }
 
-/// Adds a new instance of the word occurrence to the JSON show entry, including context information
-fn add_instance_to_show(
-    show_entry: &mut JsonValue,
+/// Searches for lines containing a grammar pattern (or any pattern at a JLPT level) and returns
+/// them with context, in the same structure as the word search. Matched lines include the
+/// pattern spans in "highlights".
+pub fn search_grammar_pattern_with_context(
     conn: &Connection,
-    episode: &Episode,
-    line_id: i32,
-) -> Result<(), Error> {
-    let context = get_context(conn, episode, line_id)?;
-    let instances = show_entry["instances"].as_array_mut().unwrap();
-
-    // Check if an entry for this episode already exists
-    let episode_json_value = match episode.episode_number {
-        Some(num) => json!(num),
-        None => json!(null),
+    query: PatternQuery,
+    shows: &[i32],
+    min_confidence: f64,
+    options: &SearchOptions,
+) -> Result<SearchResult, Error> {
+    let hits = HitSource {
+        sql: format!(
+            "SELECT gpo.transcript_id FROM grammar_pattern_occurrences gpo
+             JOIN grammar_patterns gp ON gp.id = gpo.pattern_id
+             WHERE {} = ? AND gpo.confidence >= ?",
+            query.column()
+        ),
+        params: vec![Value::Text(query.value()), Value::Real(min_confidence)],
     };
-    if let Some(instance_entry) = instances
-        .iter_mut()
-        .find(|i| i["episode"] == episode_json_value)
-    {
-        // If the episode entry exists, extend its "lines" array with the new context
-        let lines = instance_entry["lines"].as_array_mut().unwrap();
-
-        // Add new lines from context, avoiding duplicates
-        for line in context {
-            if !lines
-                .iter()
-                .any(|existing_line| existing_line["id"] == line["id"])
-            {
-                lines.push(line);
-            }
-        }
-    } else {
-        // If no entry for this episode exists, create a new one
-        instances.push(json!({
-            "episode": episode_json_value,
-            "lines": context
-        }));
-    }
+    let page = page::fetch_page(conn, &hits, shows, options)?;
 
-    // Sort lines by id to maintain order
-    if let Some(instance_entry) = instances
-        .iter_mut()
-        .find(|i| i["episode"] == episode_json_value)
-    {
-        let lines = instance_entry["lines"].as_array_mut().unwrap();
-        lines.sort_by(|a, b| a["id"].as_i64().unwrap().cmp(&b["id"].as_i64().unwrap()));
+    let mut highlights: Highlights = HashMap::new();
+    for span in grammar_pattern::get_pattern_spans(conn, query, min_confidence, &page.ids)? {
+        push_pattern_span(&mut highlights, span);
     }
 
-    Ok(())
+    let results = build_results(conn, &page.ids, &highlights, options)?;
+    Ok(SearchResult::new(query.as_str(), results, page))
+}
+
+/// Searches with a compound query (see `query` for the syntax), e.g.
+/// `食べる pattern:te_shimau level:>=n3`, returning results in the same structure as the
+/// word search. Matched lines highlight the query's word and pattern terms.
+pub fn search_query_with_context(
+    conn: &Connection,
+    query: &str,
+    shows: &[i32],
+    options: &SearchOptions,
+) -> Result<SearchResult, Error> {
+    let parsed = query::parse(query)?;
+    let page = page::fetch_page(conn, &query::hit_source(&parsed), shows, options)?;
+    let highlights = query::find_highlights(conn, &parsed, &page.ids)?;
+    let results = build_results(conn, &page.ids, &highlights, options)?;
+    Ok(SearchResult::new(query, results, page))
 }
 
-/// Retrieves the context (surrounding lines) for a given line in an episode
-fn get_context(
+/// Searches line text for phrases or a regular expression, returning results in the same
+/// structure as the word search with the matched text highlighted
+pub fn search_text_with_context(
     conn: &Connection,
-    episode: &Episode,
-    line_id: i32,
-) -> Result<Vec<JsonValue>, Error> {
-    let episode_id = episode.id.unwrap();
-    let transcripts = Transcript::get_context(conn, episode_id, line_id, 2)?;
-    Ok(transcripts.iter().map(transcript_to_json).collect())
+    query: TextQuery,
+    shows: &[i32],
+    options: &SearchOptions,
+) -> Result<SearchResult, Error> {
+    let hits = text::hit_source(conn, query, shows)?;
+    let page = page::fetch_page(conn, &hits, shows, options)?;
+    let highlights = text::find_highlights(conn, query, &page.ids)?;
+    let results = build_results(conn, &page.ids, &highlights, options)?;
+    Ok(SearchResult::new(query.as_str(), results, page))
 }
 
-/// Converts a Transcript object to a JSON representation
-fn transcript_to_json(t: &Transcript) -> JsonValue {
-    json!({
-        "id": t.id,
-        "text": t.text
-    })
+fn push_word_spans(highlights: &mut Highlights, transcript_id: i32, spans: Vec<(u32, u32)>) {
+    highlights.entry(transcript_id).or_default().extend(
+        spans
+            .into_iter()
+            .map(|(start, end)| Highlight::new(start, end)),
+    );
 }
 
-/// Creates the final JSON structure with the keyword and search results
-fn create_final_json(keyword: &str, results: Vec<JsonValue>) -> JsonValue {
-    json!({
-        "keyword": keyword,
-        "results": results
-    })
+fn push_pattern_span(highlights: &mut Highlights, span: PatternSpan) {
+    highlights
+        .entry(span.transcript_id)
+        .or_default()
+        .push(Highlight {
+            pattern: Some(span.pattern_name),
+            confidence: Some(span.confidence),
+            ..Highlight::new(span.start_char, span.end_char)
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::grammar_pattern::{get_or_create_pattern_id, GrammarPatternOccurrence};
+    use crate::db::word::WordOccurrence;
+    use crate::test_utils::{
+        create_test_db, create_test_episode, create_test_show, create_test_transcript,
+    };
+
+    #[test]
+    fn test_search_grammar_pattern_with_context() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let episode = create_test_episode(&handler, &show, "Episode 1", Some(1));
+        let before = create_test_transcript(&handler, &episode, 1, 1000, 2000, "おはよう");
+        let hit = create_test_transcript(&handler, &episode, 2, 2000, 4000, "全部食べてしまった");
+
+        let pattern_id = get_or_create_pattern_id(conn, "te_shimau", "n4").unwrap();
+        let low_id = get_or_create_pattern_id(conn, "you_ni_naru", "n3").unwrap();
+        GrammarPatternOccurrence::bulk_insert_optimized(
+            &[
+                GrammarPatternOccurrence::new(pattern_id, hit.id.unwrap() as i64, 10.5, 2, 9),
+                GrammarPatternOccurrence::new(low_id, before.id.unwrap() as i64, 1.0, 0, 4),
+            ],
+            conn,
+        )
+        .unwrap();
+
+        let results = search_grammar_pattern_with_context(
+            conn,
+            PatternQuery::Level("N4"),
+            &[show.id.unwrap()],
+            5.0,
+            &SearchOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(results.keyword, "N4");
+        assert_eq!(results.total, 1);
+        assert_eq!(results.next_cursor, None);
+        let instance = &results.results[0].instances[0];
+        assert_eq!(instance.episode_name, "Episode 1");
+        // Context lines aren't part of the clip
+        assert_eq!(instance.clips.len(), 1);
+        assert_eq!(
+            (instance.clips[0].start_ms, instance.clips[0].end_ms),
+            (1500, 4500)
+        );
+        let lines = &instance.lines;
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].highlights.is_empty());
+        assert_eq!((lines[1].time_start, lines[1].time_end), (2000, 4000));
+        assert_eq!(lines[1].highlights[0].pattern.as_deref(), Some("te_shimau"));
+        assert_eq!(
+            (lines[1].highlights[0].start, lines[1].highlights[0].end),
+            (2, 9)
+        );
+
+        // Below the confidence threshold
+        let results = search_grammar_pattern_with_context(
+            conn,
+            PatternQuery::Name("you_ni_naru"),
+            &[show.id.unwrap()],
+            5.0,
+            &SearchOptions::default(),
+        )
+        .unwrap();
+        assert!(results.results.is_empty());
+    }
+
+    #[test]
+    fn test_search_word_with_context_highlights_spans() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let episode = create_test_episode(&handler, &show, "Episode 1", Some(1));
+        let transcript =
+            create_test_transcript(&handler, &episode, 1, 1000, 2000, "見ていた、見た");
+
+        let mut word = Word::new(
+            "見る".to_string(),
+            Some("みる".to_string()),
+            "[]".to_string(),
+        );
+        word.insert(conn).unwrap();
+        WordOccurrence::new(
+            word.id.unwrap(),
+            transcript.id.unwrap(),
+            vec![(0, 4), (5, 7)],
+        )
+        .insert(conn)
+        .unwrap();
+
+        let options = SearchOptions::default();
+        let results =
+            search_word_with_context(conn, "見る", &[show.id.unwrap()], &options, |_| {
+                Ok(Vec::new())
+            })
+            .unwrap();
+
+        let lines = &results.results[0].instances[0].lines;
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            lines[0].highlights,
+            vec![Highlight::new(0, 4), Highlight::new(5, 7)]
+        );
+    }
+
+    #[test]
+    fn test_search_word_with_context_resolves_input() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let episode = create_test_episode(&handler, &show, "Episode 1", Some(1));
+        let transcript = create_test_transcript(&handler, &episode, 1, 1000, 2000, "もう食べた");
+        let shows = [show.id.unwrap()];
+        let options = SearchOptions::default();
+
+        let mut taberu = Word::new(
+            "食べる".to_string(),
+            Some("タベル".to_string()),
+            "[]".to_string(),
+        );
+        taberu.insert(conn).unwrap();
+        WordOccurrence::new(taberu.id.unwrap(), transcript.id.unwrap(), vec![(2, 5)])
+            .insert(conn)
+            .unwrap();
+        for homophone in ["掛ける", "書ける"] {
+            Word::new(
+                homophone.to_string(),
+                Some("カケル".to_string()),
+                "[]".to_string(),
+            )
+            .insert(conn)
+            .unwrap();
+        }
+
+        // Inflected input is resolved through its dictionary form
+        let results = search_word_with_context(conn, "食べた", &shows, &options, |input| {
+            assert_eq!(input, "食べた");
+            Ok(vec!["食べる".to_string()])
+        })
+        .unwrap();
+        assert_eq!(results.candidates.unwrap()[0].word, "食べる");
+        assert_eq!(
+            results.results[0].instances[0].lines[0].highlights,
+            vec![Highlight::new(2, 5)]
+        );
+
+        // Kana input matches by reading without needing the tokenizer
+        let results = search_word_with_context(conn, "たべる", &shows, &options, |_| {
+            panic!("not needed")
+        })
+        .unwrap();
+        assert_eq!(results.results.len(), 1);
+
+        // Homophones need the user to pick one
+        let results =
+            search_word_with_context(conn, "かける", &shows, &options, |_| Ok(Vec::new())).unwrap();
+        assert_eq!(results.candidates.unwrap().len(), 2);
+        assert!(results.results.is_empty());
+    }
 }
@@ -0,0 +1,285 @@
+//! Sorting and cursor-based pagination shared by all searches. Each search describes its hits
+//! as a SQL subquery of transcript ids; this module counts them and fetches one page.
+
+use crate::db::clip::ClipOptions;
+use crate::error::Error;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde::Deserialize;
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 500;
+const DEFAULT_CONTEXT_LINES: u32 = 2;
+
+/// Difficulty of a line: 0 if none of its words has a JLPT level, else 1 (n5) to 5 (n1)
+/// for its hardest word
+const LINE_DIFFICULTY_SQL: &str = "COALESCE((SELECT 6 - MIN(jl.level)
+     FROM word_occurrences wo
+     JOIN words w ON w.id = wo.word_id
+     JOIN jlpt_levels jl ON jl.word = w.word
+     WHERE wo.transcript_id = t.id), 0)";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    /// Show, then season and episode number, then line order
+    #[default]
+    Episode,
+    /// Line length in characters
+    LineLength,
+    /// JLPT level of the line's hardest word, easiest first
+    Difficulty,
+}
+
+impl SortBy {
+    /// Sort key expressions over `t` (transcripts) and `e` (episodes). `t.id` is always last so
+    /// every row has a unique key for the cursor.
+    fn key_columns(self) -> Vec<&'static str> {
+        let mut columns = match self {
+            SortBy::Episode => vec![
+                "e.show_id",
+                "COALESCE(e.season, 0)",
+                "COALESCE(e.episode_number, 0)",
+                "t.line_id",
+            ],
+            SortBy::LineLength => vec!["length(t.text)"],
+            SortBy::Difficulty => vec![LINE_DIFFICULTY_SQL],
+        };
+        columns.push("t.id");
+        columns
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    /// Maximum matching lines per page (capped at 500)
+    pub limit: usize,
+    /// `next_cursor` from the previous page, or `None` for the first page
+    pub cursor: Option<String>,
+    /// Lines of context before and after each match
+    pub context_lines: u32,
+    /// Padding and merging of the clip ranges in each episode's hits
+    pub clips: ClipOptions,
+    pub sort_by: SortBy,
+    pub descending: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_LIMIT,
+            cursor: None,
+            context_lines: DEFAULT_CONTEXT_LINES,
+            clips: ClipOptions::default(),
+            sort_by: SortBy::default(),
+            descending: false,
+        }
+    }
+}
+
+/// Matching transcript ids as a SQL subquery using `?` placeholders,
+/// e.g. `SELECT transcript_id FROM word_occurrences WHERE word_id = ?`
+pub struct HitSource {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+impl HitSource {
+    /// A fixed list of transcript ids, for matches found outside SQL
+    pub fn from_ids(ids: &[i32]) -> Result<Self, Error> {
+        Ok(Self {
+            sql: "SELECT value FROM json_each(?)".to_string(),
+            params: vec![Value::Text(serde_json::to_string(ids)?)],
+        })
+    }
+}
+
+/// One page of matching transcript ids in sort order
+#[derive(Debug, Default)]
+pub struct Page {
+    pub ids: Vec<i32>,
+    /// Matching lines across all pages
+    pub total: i64,
+    /// Pass back as `SearchOptions::cursor` for the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Counts the hits in the given shows and fetches the page after `options.cursor`
+pub fn fetch_page(
+    conn: &Connection,
+    hits: &HitSource,
+    show_ids: &[i32],
+    options: &SearchOptions,
+) -> Result<Page, Error> {
+    if show_ids.is_empty() {
+        return Ok(Page::default());
+    }
+
+    let from = format!(
+        "FROM transcripts t
+         JOIN episodes e ON e.id = t.episode_id
+         WHERE t.id IN ({}) AND e.show_id IN (SELECT value FROM json_each(?))",
+        hits.sql
+    );
+    let mut params = hits.params.clone();
+    params.push(Value::Text(serde_json::to_string(show_ids)?));
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) {}", from),
+        rusqlite::params_from_iter(params.iter()),
+        |row| row.get(0),
+    )?;
+
+    let keys = options.sort_by.key_columns();
+    let (direction, comparison) = if options.descending {
+        ("DESC", "<")
+    } else {
+        ("ASC", ">")
+    };
+
+    let mut sql = format!("SELECT {} {}", keys.join(", "), from);
+    if let Some(cursor) = &options.cursor {
+        let values = parse_cursor(cursor, keys.len())?;
+        sql.push_str(&format!(
+            " AND ({}) {} ({})",
+            keys.join(", "),
+            comparison,
+            vec!["?"; keys.len()].join(", ")
+        ));
+        params.extend(values.into_iter().map(Value::Integer));
+    }
+    let order: Vec<String> = keys
+        .iter()
+        .map(|key| format!("{} {}", key, direction))
+        .collect();
+    sql.push_str(&format!(" ORDER BY {} LIMIT ?", order.join(", ")));
+
+    // Fetch one extra row to know whether there is a next page
+    let limit = options.limit.clamp(1, MAX_LIMIT);
+    params.push(Value::Integer(limit as i64 + 1));
+
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            (0..keys.len())
+                .map(|i| row.get::<_, i64>(i))
+                .collect::<Result<Vec<_>, _>>()
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let has_more = rows.len() > limit;
+    rows.truncate(limit);
+    let next_cursor = has_more
+        .then(|| rows.last().map(|keys| format_cursor(keys)))
+        .flatten();
+
+    Ok(Page {
+        ids: rows
+            .iter()
+            .map(|keys| keys[keys.len() - 1] as i32)
+            .collect(),
+        total,
+        next_cursor,
+    })
+}
+
+fn format_cursor(keys: &[i64]) -> String {
+    keys.iter()
+        .map(|key| key.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn parse_cursor(cursor: &str, key_count: usize) -> Result<Vec<i64>, Error> {
+    let values = cursor
+        .split(',')
+        .map(|value| value.parse())
+        .collect::<Result<Vec<i64>, _>>()
+        .ok()
+        .filter(|values| values.len() == key_count);
+    values.ok_or_else(|| Error::Other(format!("Invalid search cursor '{}'", cursor)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        create_test_db, create_test_episode, create_test_show, create_test_transcript,
+    };
+
+    #[test]
+    fn test_fetch_page_sorts_and_paginates() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let episode_2 = create_test_episode(&handler, &show, "Episode 2", Some(2));
+        let episode_1 = create_test_episode(&handler, &show, "Episode 1", Some(1));
+        let mut ids = Vec::new();
+        for (episode, line_id, text) in [
+            (&episode_2, 1, "あ"),
+            (&episode_1, 2, "あああ"),
+            (&episode_1, 1, "ああ"),
+        ] {
+            let transcript = create_test_transcript(&handler, episode, line_id, 1000, 2000, text);
+            ids.push(transcript.id.unwrap());
+        }
+        let hits = HitSource::from_ids(&ids).unwrap();
+        let shows = [show.id.unwrap()];
+
+        // Episode order, two per page
+        let mut options = SearchOptions {
+            limit: 2,
+            ..SearchOptions::default()
+        };
+        let page = fetch_page(conn, &hits, &shows, &options).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.ids, vec![ids[2], ids[1]]);
+
+        options.cursor = page.next_cursor;
+        let page = fetch_page(conn, &hits, &shows, &options).unwrap();
+        assert_eq!(page.ids, vec![ids[0]]);
+        assert_eq!(page.next_cursor, None);
+
+        // Longest lines first
+        let options = SearchOptions {
+            sort_by: SortBy::LineLength,
+            descending: true,
+            ..SearchOptions::default()
+        };
+        let page = fetch_page(conn, &hits, &shows, &options).unwrap();
+        assert_eq!(page.ids, vec![ids[1], ids[2], ids[0]]);
+
+        let options = SearchOptions {
+            cursor: Some("1,2".to_string()),
+            ..SearchOptions::default()
+        };
+        assert!(fetch_page(conn, &hits, &shows, &options).is_err());
+    }
+
+    #[test]
+    fn test_episode_sort_orders_seasons() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let season_2 = create_test_episode(&handler, &show, "S02E01", Some(1));
+        let season_1 = create_test_episode(&handler, &show, "S01E02", Some(2));
+        conn.execute(
+            "UPDATE episodes SET season = 2 WHERE id = ?1",
+            [season_2.id.unwrap()],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE episodes SET season = 1 WHERE id = ?1",
+            [season_1.id.unwrap()],
+        )
+        .unwrap();
+        let later = create_test_transcript(&handler, &season_2, 1, 1000, 2000, "あ");
+        let earlier = create_test_transcript(&handler, &season_1, 1, 1000, 2000, "あ");
+        let ids = [later.id.unwrap(), earlier.id.unwrap()];
+
+        let hits = HitSource::from_ids(&ids).unwrap();
+        let page = fetch_page(conn, &hits, &[show.id.unwrap()], &SearchOptions::default()).unwrap();
+        assert_eq!(page.ids, vec![ids[1], ids[0]]);
+    }
+}
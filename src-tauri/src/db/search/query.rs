@@ -0,0 +1,612 @@
+//! A small query language for combining word, reading, grammar pattern and metadata filters.
+//!
+//! ```text
+//! 食べる pattern:te_shimau show:"Some Show" level:>=n3
+//! (word:食べる OR word:飲む) -pattern:te_iru length:<20 episode:1-12
+//! ```
+//!
+//! Terms next to each other are ANDed; `OR` binds looser than `AND`; `NOT` or a leading `-`
+//! negates a term or group. A bare term is a word (dictionary form). Supported keys:
+//!
+//! - `word:` / `reading:` - a word by dictionary form or by reading
+//! - `pattern:` - a grammar pattern name
+//! - `level:` - lines containing any grammar pattern at a JLPT level; `>=n3` means n3 or harder
+//! - `show:` - a show id or (quoted) name
+//! - `episode:` - episode number, e.g. `3`, `1-12`, `>=10`
+//! - `length:` - line length in characters, e.g. `<20`, `5-15`
+
+use crate::db::grammar_pattern::{get_pattern_spans, PatternQuery};
+use crate::db::search::page::HitSource;
+use crate::db::search::{push_pattern_span, push_word_spans, Highlights};
+use crate::error::Error;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+
+/// JLPT levels from easiest to hardest, as stored in `grammar_patterns.jlpt_level`
+const JLPT_LEVELS: [&str; 5] = ["n5", "n4", "n3", "n2", "n1"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Term(Term),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Word(String),
+    Reading(String),
+    Pattern(String),
+    /// JLPT levels, e.g. `["n3", "n2", "n1"]` for `level:>=n3`
+    Level(Vec<&'static str>),
+    Show(ShowRef),
+    Episode(Bounds),
+    Length(Bounds),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShowRef {
+    Id(i32),
+    Name(String),
+}
+
+/// Inclusive numeric bounds; `None` leaves that side open
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Not,
+    Atom { text: String, quoted: bool },
+}
+
+/// Parses a query string into a `Query`
+pub fn parse(input: &str) -> Result<Query, Error> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err(query_error("the query is empty"));
+    }
+
+    let mut parser = Parser { tokens, pos: 0 };
+    let query = parser.parse_or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(query_error("unexpected ')'"));
+    }
+    Ok(query)
+}
+
+fn query_error(message: &str) -> Error {
+    Error::Other(format!("Invalid search query: {}", message))
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' {
+            chars.next();
+            tokens.push(Token::LParen);
+        } else if c == ')' {
+            chars.next();
+            tokens.push(Token::RParen);
+        } else if c == '-' {
+            // A leading '-' negates whatever follows it
+            chars.next();
+            tokens.push(Token::Not);
+        } else {
+            let mut text = String::new();
+            let mut quoted = false;
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '(' || c == ')' {
+                    break;
+                }
+                chars.next();
+                if c == '"' {
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some(c) => text.push(c),
+                            None => return Err(query_error("unterminated quote")),
+                        }
+                    }
+                } else {
+                    text.push(c);
+                }
+            }
+
+            if !quoted && text == "NOT" {
+                tokens.push(Token::Not);
+            } else {
+                tokens.push(Token::Atom { text, quoted });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Atom { text, quoted: false }) if text == keyword)
+    }
+
+    fn parse_or(&mut self) -> Result<Query, Error> {
+        let mut branches = vec![self.parse_and()?];
+        while self.is_keyword("OR") {
+            self.pos += 1;
+            branches.push(self.parse_and()?);
+        }
+        Ok(flatten(branches, Query::Or))
+    }
+
+    fn parse_and(&mut self) -> Result<Query, Error> {
+        let mut parts = vec![self.parse_unary()?];
+        loop {
+            if self.is_keyword("AND") {
+                self.pos += 1;
+            } else if self.is_keyword("OR") || matches!(self.peek(), None | Some(Token::RParen)) {
+                break;
+            }
+            parts.push(self.parse_unary()?);
+        }
+        Ok(flatten(parts, Query::And))
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, Error> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| query_error("expected a term at the end of the query"))?;
+        self.pos += 1;
+
+        match token {
+            Token::Not => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            Token::LParen => {
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(query_error("missing ')'"));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Token::RParen => Err(query_error("unexpected ')'")),
+            Token::Atom { text, quoted } => parse_term(&text, quoted).map(Query::Term),
+        }
+    }
+}
+
+fn flatten(mut parts: Vec<Query>, combine: fn(Vec<Query>) -> Query) -> Query {
+    if parts.len() == 1 {
+        parts.pop().unwrap()
+    } else {
+        combine(parts)
+    }
+}
+
+fn parse_term(text: &str, quoted: bool) -> Result<Term, Error> {
+    let Some((key, value)) = text.split_once(':') else {
+        return Ok(Term::Word(text.to_string()));
+    };
+    if value.is_empty() {
+        return Err(query_error(&format!("'{}:' needs a value", key)));
+    }
+
+    match key {
+        "word" => Ok(Term::Word(value.to_string())),
+        "reading" => Ok(Term::Reading(value.to_string())),
+        "pattern" => Ok(Term::Pattern(value.to_string())),
+        "level" => parse_level(value).map(Term::Level),
+        "show" => Ok(match value.parse() {
+            Ok(id) if !quoted => Term::Show(ShowRef::Id(id)),
+            _ => Term::Show(ShowRef::Name(value.to_string())),
+        }),
+        "episode" | "ep" => parse_bounds(value, |s| s.parse()).map(Term::Episode),
+        "length" | "len" => parse_bounds(value, |s| s.parse()).map(Term::Length),
+        _ => Err(query_error(&format!("unknown key '{}'", key))),
+    }
+}
+
+/// Parses `5`, `>=5`, `<5`, `5-10` etc. with `parse_value` for each bound
+fn parse_bounds(
+    value: &str,
+    parse_value: fn(&str) -> Result<i64, std::num::ParseIntError>,
+) -> Result<Bounds, Error> {
+    let invalid = || query_error(&format!("invalid range '{}'", value));
+    let parse = |s: &str| parse_value(s.trim()).map_err(|_| invalid());
+
+    let bounds = if let Some(rest) = value.strip_prefix(">=") {
+        Bounds {
+            min: Some(parse(rest)?),
+            max: None,
+        }
+    } else if let Some(rest) = value.strip_prefix("<=") {
+        Bounds {
+            min: None,
+            max: Some(parse(rest)?),
+        }
+    } else if let Some(rest) = value.strip_prefix('>') {
+        Bounds {
+            min: Some(parse(rest)? + 1),
+            max: None,
+        }
+    } else if let Some(rest) = value.strip_prefix('<') {
+        Bounds {
+            min: None,
+            max: Some(parse(rest)? - 1),
+        }
+    } else if let Some((min, max)) = value.split_once('-') {
+        Bounds {
+            min: Some(parse(min)?),
+            max: Some(parse(max)?),
+        }
+    } else {
+        let exact = parse(value)?;
+        Bounds {
+            min: Some(exact),
+            max: Some(exact),
+        }
+    };
+    Ok(bounds)
+}
+
+/// Parses a level filter into the matching stored levels. Comparisons are by difficulty,
+/// so `>=n3` is n3, n2 and n1.
+fn parse_level(value: &str) -> Result<Vec<&'static str>, Error> {
+    // Difficulty 1 (n5) to 5 (n1)
+    let difficulty = |s: &str| -> Result<i64, std::num::ParseIntError> {
+        let s = s.trim().to_lowercase();
+        let number: i64 = s.strip_prefix('n').unwrap_or(&s).parse()?;
+        Ok(6 - number)
+    };
+    let mut bounds = parse_bounds(value, difficulty)?;
+    // Allow ranges in either direction, e.g. n3-n1 and n1-n3
+    if let (Some(min), Some(max)) = (bounds.min, bounds.max) {
+        bounds = Bounds {
+            min: Some(min.min(max)),
+            max: Some(min.max(max)),
+        };
+    }
+
+    let levels: Vec<_> = (1..=JLPT_LEVELS.len() as i64)
+        .filter(|d| {
+            bounds.min.is_none_or(|min| *d >= min) && bounds.max.is_none_or(|max| *d <= max)
+        })
+        .map(|d| JLPT_LEVELS[d as usize - 1])
+        .collect();
+    if levels.is_empty() {
+        return Err(query_error(&format!("no JLPT level matches '{}'", value)));
+    }
+    Ok(levels)
+}
+
+impl Query {
+    /// Appends this query as a SQL boolean expression over `t` (transcripts),
+    /// `e` (episodes) and `s` (shows)
+    fn push_sql(&self, sql: &mut String, params: &mut Vec<Value>) {
+        match self {
+            Query::Term(term) => term.push_sql(sql, params),
+            Query::And(parts) | Query::Or(parts) => {
+                let joiner = if matches!(self, Query::And(_)) {
+                    " AND "
+                } else {
+                    " OR "
+                };
+                sql.push('(');
+                for (i, part) in parts.iter().enumerate() {
+                    if i > 0 {
+                        sql.push_str(joiner);
+                    }
+                    part.push_sql(sql, params);
+                }
+                sql.push(')');
+            }
+            Query::Not(inner) => {
+                sql.push_str("NOT ");
+                inner.push_sql(sql, params);
+            }
+        }
+    }
+
+    /// Word and pattern terms that aren't negated; these are the spans worth highlighting
+    fn positive_terms(&self) -> Vec<&Term> {
+        match self {
+            Query::Term(term) => vec![term],
+            Query::And(parts) | Query::Or(parts) => {
+                parts.iter().flat_map(Query::positive_terms).collect()
+            }
+            Query::Not(_) => Vec::new(),
+        }
+    }
+}
+
+impl Term {
+    fn push_sql(&self, sql: &mut String, params: &mut Vec<Value>) {
+        match self {
+            Term::Word(word) => {
+                sql.push_str(WORD_SUBQUERY);
+                sql.push_str("w.word = ?)");
+                params.push(Value::Text(word.clone()));
+            }
+            Term::Reading(reading) => {
+                sql.push_str(WORD_SUBQUERY);
+                sql.push_str("w.reading = ?)");
+                params.push(Value::Text(reading.clone()));
+            }
+            Term::Pattern(name) => {
+                sql.push_str(PATTERN_SUBQUERY);
+                sql.push_str("gp.pattern_name = ?)");
+                params.push(Value::Text(name.clone()));
+            }
+            Term::Level(levels) => {
+                sql.push_str(PATTERN_SUBQUERY);
+                sql.push_str("gp.jlpt_level IN (");
+                sql.push_str(&vec!["?"; levels.len()].join(","));
+                sql.push_str("))");
+                params.extend(levels.iter().map(|level| Value::Text(level.to_string())));
+            }
+            Term::Show(ShowRef::Id(id)) => {
+                sql.push_str("e.show_id = ?");
+                params.push(Value::Integer(*id as i64));
+            }
+            Term::Show(ShowRef::Name(name)) => {
+                sql.push_str("s.name = ? COLLATE NOCASE");
+                params.push(Value::Text(name.clone()));
+            }
+            Term::Episode(bounds) => bounds.push_sql("e.episode_number", sql, params),
+            Term::Length(bounds) => bounds.push_sql("length(t.text)", sql, params),
+        }
+    }
+}
+
+const WORD_SUBQUERY: &str = "t.id IN (SELECT wo.transcript_id FROM word_occurrences wo \
+     JOIN words w ON w.id = wo.word_id WHERE ";
+const PATTERN_SUBQUERY: &str =
+    "t.id IN (SELECT gpo.transcript_id FROM grammar_pattern_occurrences gpo \
+     JOIN grammar_patterns gp ON gp.id = gpo.pattern_id WHERE ";
+
+impl Bounds {
+    fn push_sql(&self, column: &str, sql: &mut String, params: &mut Vec<Value>) {
+        let mut conditions = Vec::new();
+        if let Some(min) = self.min {
+            conditions.push(format!("{} >= ?", column));
+            params.push(Value::Integer(min));
+        }
+        if let Some(max) = self.max {
+            conditions.push(format!("{} <= ?", column));
+            params.push(Value::Integer(max));
+        }
+        sql.push('(');
+        sql.push_str(&conditions.join(" AND "));
+        sql.push(')');
+    }
+}
+
+/// The transcripts matching `query`, as a subquery for `page::fetch_page`
+pub fn hit_source(query: &Query) -> HitSource {
+    let mut params = Vec::new();
+    let mut sql = "SELECT t.id FROM transcripts t
+         JOIN episodes e ON e.id = t.episode_id
+         JOIN shows s ON s.id = e.show_id
+         WHERE "
+        .to_string();
+    query.push_sql(&mut sql, &mut params);
+    HitSource { sql, params }
+}
+
+/// Collects highlight spans for the query's non-negated word and pattern terms in the
+/// given transcripts, in the same shape as word and grammar search highlights
+pub fn find_highlights(
+    conn: &Connection,
+    query: &Query,
+    transcript_ids: &[i32],
+) -> Result<Highlights, Error> {
+    let mut highlights: Highlights = HashMap::new();
+
+    for term in query.positive_terms() {
+        let pattern_queries = match term {
+            Term::Word(word) => {
+                add_word_highlights(conn, "w.word", word, transcript_ids, &mut highlights)?;
+                continue;
+            }
+            Term::Reading(reading) => {
+                add_word_highlights(conn, "w.reading", reading, transcript_ids, &mut highlights)?;
+                continue;
+            }
+            Term::Pattern(name) => vec![PatternQuery::Name(name)],
+            Term::Level(levels) => levels
+                .iter()
+                .map(|level| PatternQuery::Level(level))
+                .collect(),
+            Term::Show(_) | Term::Episode(_) | Term::Length(_) => continue,
+        };
+
+        for pattern_query in pattern_queries {
+            for span in get_pattern_spans(conn, pattern_query, 0.0, transcript_ids)? {
+                push_pattern_span(&mut highlights, span);
+            }
+        }
+    }
+
+    Ok(highlights)
+}
+
+fn add_word_highlights(
+    conn: &Connection,
+    column: &str,
+    value: &str,
+    transcript_ids: &[i32],
+    highlights: &mut Highlights,
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT wo.transcript_id, wo.spans FROM word_occurrences wo
+         JOIN words w ON w.id = wo.word_id
+         WHERE {} = ?1 AND wo.transcript_id IN (SELECT value FROM json_each(?2))",
+        column
+    ))?;
+    let rows = stmt.query_map(
+        params![value, serde_json::to_string(transcript_ids)?],
+        |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)),
+    )?;
+    for row in rows {
+        let (transcript_id, spans) = row?;
+        push_word_spans(highlights, transcript_id, serde_json::from_str(&spans)?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::grammar_pattern::{get_or_create_pattern_id, GrammarPatternOccurrence};
+    use crate::db::search::page::{fetch_page, SearchOptions};
+    use crate::db::word::{Word, WordOccurrence};
+    use crate::test_utils::{
+        create_test_db, create_test_episode, create_test_show, create_test_transcript,
+    };
+
+    /// Ids of the matching lines in line order
+    fn find(conn: &Connection, query: &Query, shows: &[i32]) -> Vec<i32> {
+        fetch_page(conn, &hit_source(query), shows, &SearchOptions::default())
+            .unwrap()
+            .ids
+    }
+
+    fn word(text: &str) -> Query {
+        Query::Term(Term::Word(text.to_string()))
+    }
+
+    #[test]
+    fn test_parse_precedence_and_negation() {
+        let query = parse("食べる pattern:te_shimau OR (飲む -level:n5)").unwrap();
+        assert_eq!(
+            query,
+            Query::Or(vec![
+                Query::And(vec![
+                    word("食べる"),
+                    Query::Term(Term::Pattern("te_shimau".to_string())),
+                ]),
+                Query::And(vec![
+                    word("飲む"),
+                    Query::Not(Box::new(Query::Term(Term::Level(vec!["n5"])))),
+                ]),
+            ])
+        );
+
+        assert_eq!(
+            parse("NOT word:見る AND 猫").unwrap(),
+            Query::And(vec![Query::Not(Box::new(word("見る"))), word("猫")])
+        );
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        assert_eq!(
+            parse("level:>=n3").unwrap(),
+            Query::Term(Term::Level(vec!["n3", "n2", "n1"]))
+        );
+        assert_eq!(
+            parse("level:n4-n5").unwrap(),
+            Query::Term(Term::Level(vec!["n5", "n4"]))
+        );
+        assert_eq!(
+            parse("length:<20").unwrap(),
+            Query::Term(Term::Length(Bounds {
+                min: None,
+                max: Some(19)
+            }))
+        );
+        assert_eq!(
+            parse("episode:1-12").unwrap(),
+            Query::Term(Term::Episode(Bounds {
+                min: Some(1),
+                max: Some(12)
+            }))
+        );
+        assert_eq!(
+            parse("show:12").unwrap(),
+            Query::Term(Term::Show(ShowRef::Id(12)))
+        );
+        assert_eq!(
+            parse(r#"show:"Cowboy Bebop""#).unwrap(),
+            Query::Term(Term::Show(ShowRef::Name("Cowboy Bebop".to_string())))
+        );
+
+        for invalid in [
+            "",
+            "(食べる",
+            "食べる)",
+            "colour:red",
+            "length:abc",
+            "level:n9",
+        ] {
+            assert!(parse(invalid).is_err(), "{:?} should not parse", invalid);
+        }
+    }
+
+    #[test]
+    fn test_hit_source_and_highlights() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let episode = create_test_episode(&handler, &show, "Episode 1", Some(1));
+        let eaten = create_test_transcript(&handler, &episode, 1, 1000, 2000, "全部食べてしまった");
+        let eating = create_test_transcript(&handler, &episode, 2, 2000, 3000, "食べている");
+
+        let mut taberu = Word::new("食べる".to_string(), None, "[]".to_string());
+        taberu.insert(conn).unwrap();
+        for (transcript, span) in [(&eaten, (2, 7)), (&eating, (0, 5))] {
+            WordOccurrence::new(taberu.id.unwrap(), transcript.id.unwrap(), vec![span])
+                .insert(conn)
+                .unwrap();
+        }
+        let pattern_id = get_or_create_pattern_id(conn, "te_shimau", "n4").unwrap();
+        GrammarPatternOccurrence::bulk_insert_optimized(
+            &[GrammarPatternOccurrence::new(
+                pattern_id,
+                eaten.id.unwrap() as i64,
+                10.0,
+                2,
+                9,
+            )],
+            conn,
+        )
+        .unwrap();
+
+        let shows = [show.id.unwrap()];
+        let query = parse("食べる pattern:te_shimau").unwrap();
+        let found = find(conn, &query, &shows);
+        assert_eq!(found, vec![eaten.id.unwrap()]);
+
+        let highlights = find_highlights(conn, &query, &found).unwrap();
+        let spans = &highlights[&eaten.id.unwrap()];
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[1].pattern.as_deref(), Some("te_shimau"));
+
+        let query = parse("食べる -level:<=n4 length:<=5").unwrap();
+        assert_eq!(find(conn, &query, &shows), vec![eating.id.unwrap()]);
+
+        let query = parse(r#"食べる show:"Other Show""#).unwrap();
+        assert!(find(conn, &query, &shows).is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+//! The result shape shared by all searches: matching lines with their context, grouped by show
+//! and episode.
+
+use crate::db::clip::{compute_clips, ClipRange};
+use crate::db::search::page::Page;
+use crate::db::search::{Highlights, SearchOptions};
+use crate::db::word::WordCandidate;
+use crate::error::Error;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub keyword: String,
+    /// Shows in the order their first match appears in the requested sort
+    pub results: Vec<ShowHits>,
+    /// Matching lines across all pages
+    pub total: i64,
+    /// Pass back as `SearchOptions::cursor` for the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+    /// Words the keyword may refer to (word search only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidates: Option<Vec<WordCandidate>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShowHits {
+    pub show_id: i32,
+    pub show: String,
+    pub instances: Vec<EpisodeHits>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EpisodeHits {
+    pub episode_id: i32,
+    /// Episode number, or `None` for movies
+    pub episode: Option<i32>,
+    pub episode_name: String,
+    pub season: Option<i32>,
+    /// Matching lines and their context, in line order
+    pub lines: Vec<ContextLine>,
+    /// Clip ranges covering the matching lines (not the context), in time order
+    pub clips: Vec<ClipRange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContextLine {
+    pub id: i32,
+    pub line_id: i32,
+    /// Milliseconds from the start of the episode
+    pub time_start: i64,
+    pub time_end: i64,
+    pub text: String,
+    /// Spans to mark in the line; empty for context lines
+    pub highlights: Vec<Highlight>,
+}
+
+/// A span of a line in characters. Grammar pattern matches also carry the pattern.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Highlight {
+    pub start: u32,
+    pub end: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+}
+
+impl Highlight {
+    pub fn new(start: u32, end: u32) -> Self {
+        Self {
+            start,
+            end,
+            pattern: None,
+            confidence: None,
+        }
+    }
+}
+
+impl SearchResult {
+    pub fn new(keyword: &str, results: Vec<ShowHits>, page: Page) -> Self {
+        Self {
+            keyword: keyword.to_string(),
+            results,
+            total: page.total,
+            next_cursor: page.next_cursor,
+            candidates: None,
+        }
+    }
+}
+
+/// Groups one page of matching transcript ids by show and episode in the order they first
+/// appear on the page. Each match brings `options.context_lines` lines before and after it;
+/// all of them are fetched in a single query.
+pub fn build_results(
+    conn: &Connection,
+    transcript_ids: &[i32],
+    highlights: &Highlights,
+    options: &SearchOptions,
+) -> Result<Vec<ShowHits>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT s.id, s.name, e.id, e.episode_number, e.name, e.season,
+                c.id, c.line_id, c.time_start, c.time_end, c.text
+         FROM json_each(?1) j
+         JOIN transcripts h ON h.id = j.value
+         JOIN episodes e ON e.id = h.episode_id
+         JOIN shows s ON s.id = e.show_id
+         JOIN transcripts c ON c.episode_id = h.episode_id
+             AND c.line_id BETWEEN h.line_id - ?2 AND h.line_id + ?2
+         ORDER BY j.key, c.line_id",
+    )?;
+    let mut rows = stmt.query(params![
+        serde_json::to_string(transcript_ids)?,
+        options.context_lines
+    ])?;
+
+    let mut shows: Vec<ShowHits> = Vec::new();
+    let mut show_index: HashMap<i32, usize> = HashMap::new();
+    // Episode id -> (show index, instance index)
+    let mut episode_index: HashMap<i32, (usize, usize)> = HashMap::new();
+    let mut seen_lines: HashSet<i32> = HashSet::new();
+
+    while let Some(row) = rows.next()? {
+        let show_id: i32 = row.get(0)?;
+        let episode_id: i32 = row.get(2)?;
+        let id: i32 = row.get(6)?;
+
+        let show = match show_index.get(&show_id) {
+            Some(&show) => show,
+            None => {
+                shows.push(ShowHits {
+                    show_id,
+                    show: row.get(1)?,
+                    instances: Vec::new(),
+                });
+                show_index.insert(show_id, shows.len() - 1);
+                shows.len() - 1
+            }
+        };
+        let (show, instance) = match episode_index.get(&episode_id) {
+            Some(&position) => position,
+            None => {
+                let instances = &mut shows[show].instances;
+                instances.push(EpisodeHits {
+                    episode_id,
+                    episode: row.get(3)?,
+                    episode_name: row.get(4)?,
+                    season: row.get(5)?,
+                    lines: Vec::new(),
+                    clips: Vec::new(),
+                });
+                let position = (show, instances.len() - 1);
+                episode_index.insert(episode_id, position);
+                position
+            }
+        };
+
+        // Context windows of nearby matches overlap
+        if seen_lines.insert(id) {
+            shows[show].instances[instance].lines.push(ContextLine {
+                id,
+                line_id: row.get(7)?,
+                time_start: row.get(8)?,
+                time_end: row.get(9)?,
+                text: row.get(10)?,
+                highlights: highlights.get(&id).cloned().unwrap_or_default(),
+            });
+        }
+    }
+
+    let hits: HashSet<i32> = transcript_ids.iter().copied().collect();
+    for show in &mut shows {
+        for instance in &mut show.instances {
+            // A page sorted by something other than position can visit lines out of order
+            instance.lines.sort_by_key(|line| line.line_id);
+            let hit_lines: Vec<(i32, i64, i64)> = instance
+                .lines
+                .iter()
+                .filter(|line| hits.contains(&line.id))
+                .map(|line| (line.id, line.time_start, line.time_end))
+                .collect();
+            instance.clips = compute_clips(&hit_lines, &options.clips);
+        }
+    }
+
+    Ok(shows)
+}
@@ -0,0 +1,250 @@
+//! Substring and regular expression search over line text, for phrases, names and sound
+//! effects that aren't single dictionary words. Substring search uses the `transcripts_fts`
+//! trigram index.
+
+use crate::db::search::page::HitSource;
+use crate::db::search::{Highlight, Highlights};
+use crate::error::Error;
+use regex::Regex;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::collections::HashMap;
+
+/// The trigram tokenizer can only match phrases of at least this many characters
+const MIN_TRIGRAM_CHARS: usize = 3;
+
+#[derive(Debug, Clone, Copy)]
+pub enum TextQuery<'a> {
+    /// Whitespace-separated phrases that must all appear in the line
+    Phrase(&'a str),
+    /// A regular expression matched against the line
+    Regex(&'a str),
+}
+
+impl TextQuery<'_> {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TextQuery::Phrase(text) | TextQuery::Regex(text) => text,
+        }
+    }
+}
+
+/// The lines matching `query`, as a subquery for `page::fetch_page`. Phrases are matched in
+/// SQL; regular expressions are checked against every line of the given shows.
+pub fn hit_source(
+    conn: &Connection,
+    query: TextQuery,
+    show_ids: &[i32],
+) -> Result<HitSource, Error> {
+    match Matcher::new(query)? {
+        Matcher::Phrases(phrases) => {
+            let mut sql = "SELECT t.id FROM transcripts t WHERE 1".to_string();
+            let mut params = Vec::new();
+            push_phrase_conditions(&phrases, &mut sql, &mut params);
+            Ok(HitSource { sql, params })
+        }
+        Matcher::Regex(regex) => {
+            let mut stmt = conn.prepare(
+                "SELECT t.id, t.text FROM transcripts t
+                 JOIN episodes e ON e.id = t.episode_id
+                 WHERE e.show_id IN (SELECT value FROM json_each(?1))",
+            )?;
+            let rows = stmt.query_map([serde_json::to_string(show_ids)?], |row| {
+                Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
+            })?;
+            let mut ids = Vec::new();
+            for row in rows {
+                let (id, text) = row?;
+                if regex.is_match(&text) {
+                    ids.push(id);
+                }
+            }
+            HitSource::from_ids(&ids)
+        }
+    }
+}
+
+/// The matched spans of `query` in each of the given lines
+pub fn find_highlights(
+    conn: &Connection,
+    query: TextQuery,
+    transcript_ids: &[i32],
+) -> Result<Highlights, Error> {
+    let matcher = Matcher::new(query)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, text FROM transcripts WHERE id IN (SELECT value FROM json_each(?1))",
+    )?;
+    let rows = stmt.query_map([serde_json::to_string(transcript_ids)?], |row| {
+        Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
+    })?;
+
+    let mut highlights = HashMap::new();
+    for row in rows {
+        let (id, text) = row?;
+        highlights.insert(
+            id,
+            matcher
+                .spans(&text)
+                .into_iter()
+                .map(|(start, end)| Highlight::new(start, end))
+                .collect(),
+        );
+    }
+    Ok(highlights)
+}
+
+/// Phrases long enough for trigrams go through the full-text index; shorter ones fall back to
+/// LIKE on the line text
+fn push_phrase_conditions(phrases: &[&str], sql: &mut String, params: &mut Vec<Value>) {
+    let (indexed, short): (Vec<&str>, Vec<&str>) = phrases
+        .iter()
+        .partition(|phrase| phrase.chars().count() >= MIN_TRIGRAM_CHARS);
+
+    if !indexed.is_empty() {
+        let match_expr = indexed
+            .iter()
+            .map(|phrase| format!("\"{}\"", phrase.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        sql.push_str(
+            " AND t.id IN (SELECT rowid FROM transcripts_fts WHERE transcripts_fts MATCH ?)",
+        );
+        params.push(Value::Text(match_expr));
+    }
+
+    for phrase in short {
+        let escaped = phrase
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        sql.push_str(" AND t.text LIKE ? ESCAPE '\\'");
+        params.push(Value::Text(format!("%{}%", escaped)));
+    }
+}
+
+enum Matcher<'a> {
+    Phrases(Vec<&'a str>),
+    Regex(Regex),
+}
+
+impl<'a> Matcher<'a> {
+    fn new(query: TextQuery<'a>) -> Result<Self, Error> {
+        match query {
+            TextQuery::Phrase(text) => {
+                let phrases: Vec<&str> = text.split_whitespace().collect();
+                if phrases.is_empty() {
+                    return Err(Error::Other("The search text is empty".to_string()));
+                }
+                Ok(Matcher::Phrases(phrases))
+            }
+            TextQuery::Regex(pattern) => Regex::new(pattern)
+                .map(Matcher::Regex)
+                .map_err(|err| Error::Other(format!("Invalid regular expression: {}", err))),
+        }
+    }
+
+    /// (start_char, end_char) of every match in `text`, in order
+    fn spans(&self, text: &str) -> Vec<(u32, u32)> {
+        let mut byte_spans: Vec<(usize, usize)> = match self {
+            Matcher::Phrases(phrases) => phrases
+                .iter()
+                .flat_map(|phrase| {
+                    text.match_indices(phrase)
+                        .map(|(start, matched)| (start, start + matched.len()))
+                })
+                .collect(),
+            Matcher::Regex(regex) => regex
+                .find_iter(text)
+                .filter(|m| !m.is_empty())
+                .map(|m| (m.start(), m.end()))
+                .collect(),
+        };
+        byte_spans.sort_unstable();
+
+        byte_spans
+            .into_iter()
+            .map(|(start, end)| {
+                (
+                    text[..start].chars().count() as u32,
+                    text[..end].chars().count() as u32,
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::search::page::{fetch_page, SearchOptions};
+    use crate::test_utils::{
+        create_test_db, create_test_episode, create_test_show, create_test_transcript,
+    };
+
+    /// Ids of the matching lines in line order, and their highlights
+    fn find(conn: &Connection, query: TextQuery, shows: &[i32]) -> (Vec<i32>, Highlights) {
+        let hits = hit_source(conn, query, shows).unwrap();
+        let page = fetch_page(conn, &hits, shows, &SearchOptions::default()).unwrap();
+        let highlights = find_highlights(conn, query, &page.ids).unwrap();
+        (page.ids, highlights)
+    }
+
+    #[test]
+    fn test_find_text_matches() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let episode = create_test_episode(&handler, &show, "Episode 1", Some(1));
+        let lines = ["ドキドキする、ドキドキ", "よろしくお願いします", "お願い"];
+        let mut ids = Vec::new();
+        for (i, text) in lines.iter().enumerate() {
+            let transcript =
+                create_test_transcript(&handler, &episode, i as i32 + 1, 1000, 2000, text);
+            ids.push(transcript.id.unwrap());
+        }
+        let shows = [show.id.unwrap()];
+
+        // Indexed phrase, with every occurrence highlighted
+        let (found, highlights) = find(conn, TextQuery::Phrase("ドキドキ"), &shows);
+        assert_eq!(found, vec![ids[0]]);
+        assert_eq!(
+            highlights[&ids[0]],
+            vec![Highlight::new(0, 4), Highlight::new(7, 11)]
+        );
+
+        // Phrases shorter than a trigram still match, and all phrases are required
+        let (found, _) = find(conn, TextQuery::Phrase("お願い します"), &shows);
+        assert_eq!(found, vec![ids[1]]);
+        let (found, _) = find(conn, TextQuery::Phrase("願"), &shows);
+        assert_eq!(found, vec![ids[1], ids[2]]);
+
+        let (found, highlights) = find(conn, TextQuery::Regex("^お願い$"), &shows);
+        assert_eq!(found, vec![ids[2]]);
+        assert_eq!(highlights[&ids[2]], vec![Highlight::new(0, 3)]);
+
+        assert!(hit_source(conn, TextQuery::Regex("("), &shows).is_err());
+        assert!(hit_source(conn, TextQuery::Phrase(" "), &shows).is_err());
+    }
+
+    #[test]
+    fn test_fts_index_follows_transcript_changes() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let episode = create_test_episode(&handler, &show, "Episode 1", Some(1));
+        let mut transcript =
+            create_test_transcript(&handler, &episode, 1, 1000, 2000, "いただきます");
+        let shows = [show.id.unwrap()];
+
+        transcript.text = "ごちそうさま".to_string();
+        transcript.update(conn).unwrap();
+        let (found, _) = find(conn, TextQuery::Phrase("いただき"), &shows);
+        assert!(found.is_empty());
+        let (found, _) = find(conn, TextQuery::Phrase("ちそう"), &shows);
+        assert_eq!(found.len(), 1);
+
+        transcript.delete(conn).unwrap();
+        let (found, _) = find(conn, TextQuery::Phrase("ちそう"), &shows);
+        assert!(found.is_empty());
+    }
+}
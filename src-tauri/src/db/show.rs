@@ -42,6 +42,21 @@ impl Show {
         Ok(())
     }
 
+    /// Sets the pattern that locates an episode's video file, for exporting clips
+    /// (see `export::cut_list::video_path`); `None` clears it
+    pub fn set_video_path_pattern(
+        conn: &Connection,
+        show_id: i32,
+        pattern: Option<&str>,
+    ) -> Result<(), Error> {
+        conn.execute(
+            "UPDATE shows SET video_path_pattern = ?1 WHERE id = ?2",
+            params![pattern, show_id],
+        )?;
+        Ok(())
+    }
+
+    #[cfg(test)]
     pub fn get_by_id(conn: &Connection, id: i32) -> Result<Show, Error> {
         let mut stmt = conn.prepare("SELECT id, name, show_type FROM shows WHERE id = ?1")?;
         let show = stmt.query_row(params![id], |row| {
@@ -6,20 +6,20 @@ pub struct Transcript {
     pub id: Option<i32>,
     pub episode_id: i32,
     pub line_id: i32,
+    /// Milliseconds from the start of the episode
     #[allow(dead_code)] // Used in database operations
-    pub time_start: String,
+    pub time_start: i64,
     #[allow(dead_code)] // Used in database operations
-    pub time_end: String,
+    pub time_end: i64,
     pub text: String,
 }
 
 impl Transcript {
-    #[cfg(test)]
     pub fn new(
         episode_id: i32,
         line_id: i32,
-        time_start: String,
-        time_end: String,
+        time_start: i64,
+        time_end: i64,
         text: String,
     ) -> Self {
         Transcript {
@@ -32,7 +32,6 @@ impl Transcript {
         }
     }
 
-    #[cfg(test)]
     pub fn insert(&mut self, conn: &Connection) -> Result<(), Error> {
         conn.execute(
             "INSERT OR IGNORE INTO transcripts (episode_id, line_id, time_start, time_end, text) VALUES (?1, ?2, ?3, ?4, ?5)",
@@ -43,7 +42,6 @@ impl Transcript {
         Ok(())
     }
 
-    #[cfg(test)]
     pub fn update(&self, conn: &Connection) -> Result<(), Error> {
         conn.execute(
             "UPDATE transcripts SET episode_id = ?1, line_id = ?2, time_start = ?3, time_end = ?4, text = ?5 WHERE id = ?6",
@@ -52,13 +50,11 @@ impl Transcript {
         Ok(())
     }
 
-    #[cfg(test)]
     pub fn delete(&self, conn: &Connection) -> Result<(), Error> {
         conn.execute("DELETE FROM transcripts WHERE id = ?1", params![self.id])?;
         Ok(())
     }
 
-    #[cfg(test)]
     pub fn get_by_id(conn: &Connection, id: i32) -> Result<Transcript, Error> {
         let mut stmt = conn.prepare("SELECT id, episode_id, line_id, time_start, time_end, text FROM transcripts WHERE id = ?1")?;
         let transcript = stmt.query_row(params![id], |row| {
@@ -74,7 +70,6 @@ impl Transcript {
         Ok(transcript)
     }
 
-    #[cfg(test)]
     pub fn get_all_for_episode(
         conn: &Connection,
         episode_id: i32,
@@ -96,7 +91,6 @@ impl Transcript {
             .map_err(Error::from)
     }
 
-    #[cfg(test)]
     pub fn search_by_text(conn: &Connection, search_term: &str) -> Result<Vec<Transcript>, Error> {
         let mut stmt = conn.prepare("SELECT id, episode_id, line_id, time_start, time_end, text FROM transcripts WHERE text LIKE ?1")?;
         let transcripts_iter = stmt.query_map(params![format!("%{}%", search_term)], |row| {
@@ -164,8 +158,8 @@ mod tests {
         let mut transcript = Transcript::new(
             episode.id.unwrap(),
             1,
-            "00:00:01,000".to_string(),
-            "00:00:05,000".to_string(),
+            1000,
+            5000,
             "Hello, world!".to_string(),
         );
         transcript.insert(&handler.conn).unwrap();
@@ -187,8 +181,8 @@ mod tests {
         let mut transcript = Transcript::new(
             episode.id.unwrap(),
             1,
-            "00:00:01,000".to_string(),
-            "00:00:05,000".to_string(),
+            1000,
+            5000,
             "Hello, world!".to_string(),
         );
         transcript.insert(&handler.conn).unwrap();
@@ -210,8 +204,8 @@ mod tests {
         let mut transcript = Transcript::new(
             episode.id.unwrap(),
             1,
-            "00:00:01,000".to_string(),
-            "00:00:05,000".to_string(),
+            1000,
+            5000,
             "Hello, world!".to_string(),
         );
         transcript.insert(&handler.conn).unwrap();
@@ -229,27 +223,9 @@ mod tests {
         let episode = create_test_episode(&handler, &show, "Test Episode", Some(1));
 
         let transcripts = vec![
-            Transcript::new(
-                episode.id.unwrap(),
-                1,
-                "00:00:01,000".to_string(),
-                "00:00:05,000".to_string(),
-                "Line 1".to_string(),
-            ),
-            Transcript::new(
-                episode.id.unwrap(),
-                2,
-                "00:00:06,000".to_string(),
-                "00:00:10,000".to_string(),
-                "Line 2".to_string(),
-            ),
-            Transcript::new(
-                episode.id.unwrap(),
-                3,
-                "00:00:11,000".to_string(),
-                "00:00:15,000".to_string(),
-                "Line 3".to_string(),
-            ),
+            Transcript::new(episode.id.unwrap(), 1, 1000, 5000, "Line 1".to_string()),
+            Transcript::new(episode.id.unwrap(), 2, 6000, 10000, "Line 2".to_string()),
+            Transcript::new(episode.id.unwrap(), 3, 11000, 15000, "Line 3".to_string()),
         ];
 
         for mut transcript in transcripts {
@@ -274,22 +250,22 @@ mod tests {
             Transcript::new(
                 episode.id.unwrap(),
                 1,
-                "00:00:01,000".to_string(),
-                "00:00:05,000".to_string(),
+                1000,
+                5000,
                 "Hello, world!".to_string(),
             ),
             Transcript::new(
                 episode.id.unwrap(),
                 2,
-                "00:00:06,000".to_string(),
-                "00:00:10,000".to_string(),
+                6000,
+                10000,
                 "This is a test".to_string(),
             ),
             Transcript::new(
                 episode.id.unwrap(),
                 3,
-                "00:00:11,000".to_string(),
-                "00:00:15,000".to_string(),
+                11000,
+                15000,
                 "Goodbye, world!".to_string(),
             ),
         ];
@@ -311,41 +287,11 @@ mod tests {
         let episode = create_test_episode(&handler, &show, "Test Episode", Some(1));
 
         let transcripts = vec![
-            Transcript::new(
-                episode.id.unwrap(),
-                1,
-                "00:00:01,000".to_string(),
-                "00:00:05,000".to_string(),
-                "Line 1".to_string(),
-            ),
-            Transcript::new(
-                episode.id.unwrap(),
-                2,
-                "00:00:06,000".to_string(),
-                "00:00:10,000".to_string(),
-                "Line 2".to_string(),
-            ),
-            Transcript::new(
-                episode.id.unwrap(),
-                3,
-                "00:00:11,000".to_string(),
-                "00:00:15,000".to_string(),
-                "Line 3".to_string(),
-            ),
-            Transcript::new(
-                episode.id.unwrap(),
-                4,
-                "00:00:16,000".to_string(),
-                "00:00:20,000".to_string(),
-                "Line 4".to_string(),
-            ),
-            Transcript::new(
-                episode.id.unwrap(),
-                5,
-                "00:00:21,000".to_string(),
-                "00:00:25,000".to_string(),
-                "Line 5".to_string(),
-            ),
+            Transcript::new(episode.id.unwrap(), 1, 1000, 5000, "Line 1".to_string()),
+            Transcript::new(episode.id.unwrap(), 2, 6000, 10000, "Line 2".to_string()),
+            Transcript::new(episode.id.unwrap(), 3, 11000, 15000, "Line 3".to_string()),
+            Transcript::new(episode.id.unwrap(), 4, 16000, 20000, "Line 4".to_string()),
+            Transcript::new(episode.id.unwrap(), 5, 21000, 25000, "Line 5".to_string()),
         ];
 
         for mut transcript in transcripts {
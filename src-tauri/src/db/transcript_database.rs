@@ -1,10 +1,23 @@
 use crate::analysis::japanese_analyzer;
+use crate::db::clip::ClipOptions;
+use crate::db::coverage::{self, CoverageOptions, EpisodeCoverage, ShowCoverage};
 use crate::db::episode::Episode;
-use crate::db::search;
+use crate::db::grammar_pattern::{self, PatternQuery, PatternStats, RankedPattern};
+use crate::db::migrations;
+use crate::db::search::{self, SearchOptions, SearchResult, TextQuery};
 use crate::db::show::Show;
+use crate::db::user_word::{self, IPlusOneLine, IPlusOneOptions, WordImportOptions, WordStatus};
+use crate::db::word::{SurfaceForm, Word};
 use crate::error::Error;
-use rusqlite::Connection;
-use serde_json::Value as JsonValue;
+use crate::export::anki::{self, AnkiExportOptions, ExportSummary};
+use crate::export::cards::CardTarget;
+use crate::export::cut_list::{self, Cut};
+use crate::export::frequency::{self, FrequencyEntry, FrequencyListOptions};
+use crate::export::study_list::{self, StudyListOptions, StudyWord};
+use crate::jobs::JobContext;
+use crate::subtitle_importer::ShowEntry;
+use rusqlite::{Connection, OptionalExtension};
+use std::path::Path;
 
 /// DbHandler struct that wraps a SQLite connection
 pub struct DbHandler {
@@ -21,6 +34,7 @@ impl DbHandler {
             "
             PRAGMA journal_mode = WAL;
             PRAGMA synchronous = NORMAL; 
+            PRAGMA busy_timeout = 5000;
             PRAGMA cache_size = 10000;
             PRAGMA temp_store = MEMORY;
             PRAGMA mmap_size = 268435456;
@@ -30,108 +44,15 @@ impl DbHandler {
         Ok(Self { conn })
     }
 
-    /// Creates the necessary tables in the database if they don't already exist
+    /// File the database was opened from, e.g. to open another connection for a job.
+    /// None for in-memory databases.
+    pub fn path(&self) -> Option<&str> {
+        self.conn.path().filter(|path| !path.is_empty())
+    }
+
+    /// Creates the tables of a new database, or migrates an existing one to the current schema
     pub fn create_tables(&self) -> Result<(), Error> {
-        let sql = "
-            CREATE TABLE IF NOT EXISTS shows (
-                id INTEGER PRIMARY KEY, 
-                name TEXT NOT NULL UNIQUE, 
-                show_type TEXT NOT NULL
-            );
-            CREATE TABLE IF NOT EXISTS episodes (
-                id INTEGER PRIMARY KEY,
-                show_id INTEGER,
-                name TEXT NOT NULL,
-                episode_number INTEGER,
-                UNIQUE(show_id, episode_number),
-                FOREIGN KEY(show_id) REFERENCES shows(id)
-            );
-            CREATE TABLE IF NOT EXISTS transcripts (
-                id INTEGER PRIMARY KEY,
-                episode_id INTEGER,
-                line_id INTEGER,
-                time_start INTEGER,
-                time_end INTEGER,
-                text TEXT NOT NULL,
-                UNIQUE(episode_id, line_id, time_start, time_end, text),
-                FOREIGN KEY(episode_id) REFERENCES episodes(id)
-            );
-            CREATE TABLE IF NOT EXISTS words (
-                id INTEGER PRIMARY KEY, 
-                word TEXT NOT NULL UNIQUE, 
-                reading TEXT,
-                pos TEXT NOT NULL
-            );
-            CREATE TABLE IF NOT EXISTS word_occurrences (
-                word_id INTEGER, 
-                transcript_id INTEGER,
-                FOREIGN KEY(word_id) REFERENCES words(id),
-                FOREIGN KEY(transcript_id) REFERENCES transcripts(id),
-                UNIQUE(word_id, transcript_id)
-            );
-            CREATE TABLE IF NOT EXISTS jlpt_levels (
-                word TEXT PRIMARY KEY,
-                level INTEGER NOT NULL
-            );
-            CREATE TABLE IF NOT EXISTS episode_jlpt_stats (
-                episode_id INTEGER PRIMARY KEY,
-                n5_pct REAL,
-                n4_pct REAL,
-                n3_pct REAL,
-                n2_pct REAL,
-                n1_pct REAL,
-                FOREIGN KEY(episode_id) REFERENCES episodes(id)
-            );
-            -- Simplified grammar patterns - just unique pattern definitions
-            CREATE TABLE IF NOT EXISTS grammar_patterns (
-                id INTEGER PRIMARY KEY,
-                pattern_name TEXT NOT NULL UNIQUE,     -- 'te_form', 'past_tense', etc.
-                jlpt_level TEXT NOT NULL               -- 'n5', 'n4', 'n3', 'n2', 'n1'
-            );
-            -- Grammar pattern occurrences linked to specific transcripts
-            CREATE TABLE IF NOT EXISTS grammar_pattern_occurrences (
-                pattern_id INTEGER,
-                transcript_id INTEGER,
-                confidence REAL,
-                start_char INTEGER,
-                end_char INTEGER,
-                PRIMARY KEY (pattern_id, transcript_id),
-                FOREIGN KEY (pattern_id) REFERENCES grammar_patterns(id),
-                FOREIGN KEY (transcript_id) REFERENCES transcripts(id)
-            );
-            -- Optimized indexing strategy for direct queries without pre-computed tables
-
-            -- Episode and transcript indexes
-            CREATE INDEX IF NOT EXISTS idx_episodes_show_id ON episodes(show_id);
-            CREATE INDEX IF NOT EXISTS idx_transcripts_episode_id ON transcripts(episode_id);
-            CREATE INDEX IF NOT EXISTS idx_transcripts_episode_line ON transcripts(episode_id, line_id);
-
-            -- Critical word query indexes (replaces word_stats and word_episodes tables)
-            CREATE INDEX IF NOT EXISTS idx_words_word ON words(word);
-            CREATE INDEX IF NOT EXISTS idx_words_reading ON words(reading);
-            CREATE INDEX IF NOT EXISTS idx_wo_word_episode ON word_occurrences(word_id, transcript_id);
-            CREATE INDEX IF NOT EXISTS idx_wo_word_count ON word_occurrences(word_id);
-            CREATE INDEX IF NOT EXISTS idx_word_occurrences_transcript_id ON word_occurrences(transcript_id);
-
-            -- JLPT stats indexes for filtering and sorting
-            CREATE INDEX IF NOT EXISTS idx_jlpt_levels_word ON jlpt_levels(word);
-            CREATE INDEX IF NOT EXISTS idx_jlpt_levels_level ON jlpt_levels(level);
-            CREATE INDEX IF NOT EXISTS idx_episode_jlpt_n5 ON episode_jlpt_stats(n5_pct DESC);
-            CREATE INDEX IF NOT EXISTS idx_episode_jlpt_n4 ON episode_jlpt_stats(n4_pct DESC);
-            CREATE INDEX IF NOT EXISTS idx_episode_jlpt_n3 ON episode_jlpt_stats(n3_pct DESC);
-            CREATE INDEX IF NOT EXISTS idx_episode_jlpt_n2 ON episode_jlpt_stats(n2_pct DESC);
-            CREATE INDEX IF NOT EXISTS idx_episode_jlpt_n1 ON episode_jlpt_stats(n1_pct DESC);
-
-            -- Grammar pattern indexes with confidence support
-            CREATE INDEX IF NOT EXISTS idx_grammar_patterns_name ON grammar_patterns(pattern_name);
-            CREATE INDEX IF NOT EXISTS idx_gpo_pattern_transcript ON grammar_pattern_occurrences(pattern_id, transcript_id);
-            CREATE INDEX IF NOT EXISTS idx_gpo_confidence ON grammar_pattern_occurrences(confidence);
-            CREATE INDEX IF NOT EXISTS idx_grammar_pattern_occurrences_transcript_id ON grammar_pattern_occurrences(transcript_id);
-            
-            -- Reverse index provides fast Japanese word search
-        ";
-        self.conn.execute_batch(sql)?;
-        Ok(())
+        migrations::migrate(&self.conn)
     }
 
     pub fn get_show_id_name_pairs(&mut self) -> Result<Vec<(i32, String)>, Error> {
@@ -143,88 +64,338 @@ impl DbHandler {
         Ok(show_id_name_pairs)
     }
 
-    /// Inserts a new show into the database
-    pub fn insert_shows(&mut self, shows: &[(String, String)]) -> Result<(), Error> {
+    /// Inserts parsed shows with their episodes and transcripts in one transaction, reporting
+    /// progress per batch of lines. Shows and episodes already in the database are reused.
+    /// Cancelling rolls the whole import back. Returns the number of lines inserted.
+    pub fn import_show_entries(
+        &mut self,
+        show_entries: &[ShowEntry],
+        job: &JobContext,
+    ) -> Result<usize, Error> {
         let tx = self.conn.transaction()?;
-        for (name, show_type) in shows {
-            let mut show = Show::new(name.clone(), show_type.clone());
+
+        // (episode_id, line_id, time_start, time_end, text)
+        let mut transcripts: Vec<(i32, i32, i64, i64, &str)> = Vec::new();
+        for show_entry in show_entries {
+            let mut show = Show::new(show_entry.name.clone(), "Anime".to_string());
             show.insert(&tx)?;
+            // An existing show isn't inserted again, so the last insert id isn't its id
+            let show_id: i32 = tx.query_row(
+                "SELECT id FROM shows WHERE name = ?1",
+                [&show.name],
+                |row| row.get(0),
+            )?;
+
+            for srt_entry in &show_entry.episodes {
+                // Episodes without a number, like movies, are told apart by name
+                let existing_id: Option<i32> = tx
+                    .query_row(
+                        "SELECT id FROM episodes
+                         WHERE show_id = ?1 AND episode_number IS ?2
+                           AND IFNULL(season, 0) = IFNULL(?4, 0)
+                           AND (?2 IS NOT NULL OR name = ?3)",
+                        rusqlite::params![
+                            show_id,
+                            srt_entry.episode_number,
+                            srt_entry.episode_name,
+                            srt_entry.season
+                        ],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let episode_id = match existing_id {
+                    Some(id) => id,
+                    None => {
+                        let mut episode = Episode::new(
+                            show_id,
+                            srt_entry.episode_name.clone(),
+                            srt_entry.episode_number,
+                        );
+                        episode.season = srt_entry.season;
+                        episode.insert(&tx)?;
+                        episode.id.unwrap()
+                    }
+                };
+
+                for subtitle in &srt_entry.content.0 {
+                    transcripts.push((
+                        episode_id,
+                        subtitle.number as i32,
+                        subtitle.start_time.to_milliseconds(),
+                        subtitle.end_time.to_milliseconds(),
+                        &subtitle.text,
+                    ));
+                }
+            }
         }
-        tx.commit()?;
-        Ok(())
-    }
 
-    /// Inserts multiple episodes into the database
-    pub fn insert_episodes(
-        &mut self,
-        episodes: &[(i32, String, Option<i32>)],
-    ) -> Result<Vec<i32>, Error> {
-        let tx = self.conn.transaction()?;
-        let mut inserted_ids = Vec::with_capacity(episodes.len());
+        // Batches only bound the work between progress reports and cancellation checks
+        const BATCH_SIZE: usize = 50000;
+        // Rows per statement, to stay under SQLite's query length limits
+        const CHUNK_SIZE: usize = 5000;
+        let batch_count = transcripts.len().div_ceil(BATCH_SIZE);
+        // Lines already in the database are ignored, so only new rows are counted
+        let mut inserted = 0;
+
+        for (batch_index, batch) in transcripts.chunks(BATCH_SIZE).enumerate() {
+            job.check_cancelled()?;
+            job.progress("Inserting transcripts", batch_index, batch_count);
+
+            for chunk in batch.chunks(CHUNK_SIZE) {
+                let placeholders = chunk
+                    .iter()
+                    .map(|_| "(?, ?, ?, ?, ?)")
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                let query = format!(
+                    "INSERT OR IGNORE INTO transcripts (episode_id, line_id, time_start, time_end, text) VALUES {}",
+                    placeholders
+                );
+
+                let mut params: Vec<rusqlite::types::Value> = Vec::with_capacity(chunk.len() * 5);
+                for &(episode_id, line_id, time_start, time_end, text) in chunk {
+                    params.push(episode_id.into());
+                    params.push(line_id.into());
+                    params.push(time_start.into());
+                    params.push(time_end.into());
+                    params.push(text.to_string().into());
+                }
 
-        for &(show_id, ref name, episode_number) in episodes {
-            let mut episode = Episode::new(show_id, name.clone(), episode_number);
-            episode.insert(&tx)?;
-            inserted_ids.push(episode.id.unwrap());
+                inserted += tx.execute(&query, rusqlite::params_from_iter(params))?;
+            }
         }
 
+        // Last chance to cancel; nothing is visible to other connections until the commit
+        job.check_cancelled()?;
         tx.commit()?;
-        Ok(inserted_ids)
+        job.progress("Inserting transcripts", batch_count, batch_count);
+        Ok(inserted)
     }
 
-    /// Inserts multiple transcripts into the database using optimized batch operations
-    pub fn insert_transcripts(
+    /// Creates a reverse index using kagome for Japanese morphological analysis. `incremental`
+    /// only analyzes lines imported since the last run.
+    pub fn create_reverse_index(
         &mut self,
-        transcripts: &[(i32, i32, i64, i64, String)],
+        incremental: bool,
+        job: &JobContext,
     ) -> Result<(), Error> {
-        let tx = self.conn.transaction()?;
+        japanese_analyzer::create_reverse_index(&mut self.conn, incremental, job)
+    }
 
-        // Process in chunks to avoid SQLite query length limits
-        const CHUNK_SIZE: usize = 5000;
+    /// Performs a search for transcripts containing a specific keyword with context, filtered by shows.
+    /// `lemmatize` gives dictionary forms for inflected input.
+    pub fn search_word_with_context(
+        &self,
+        keyword: &str,
+        shows: &[i32],
+        options: &SearchOptions,
+        lemmatize: impl FnOnce(&str) -> Result<Vec<String>, Error>,
+    ) -> Result<SearchResult, Error> {
+        search::search_word_with_context(&self.conn, keyword, shows, options, lemmatize)
+    }
 
-        for chunk in transcripts.chunks(CHUNK_SIZE) {
-            // Build multi-row INSERT for this chunk
-            let placeholders = chunk
-                .iter()
-                .map(|_| "(?, ?, ?, ?, ?)")
-                .collect::<Vec<_>>()
-                .join(", ");
-
-            let query = format!(
-                "INSERT OR IGNORE INTO transcripts (episode_id, line_id, time_start, time_end, text) VALUES {}",
-                placeholders
-            );
-
-            // Flatten parameters for this chunk
-            let mut params: Vec<rusqlite::types::Value> = Vec::with_capacity(chunk.len() * 5);
-            for &(episode_id, line_id, time_start, time_end, ref text) in chunk {
-                params.push(episode_id.into());
-                params.push(line_id.into());
-                params.push(time_start.into());
-                params.push(time_end.into());
-                params.push(text.clone().into());
-            }
+    /// Performs a search for a specific word, e.g. one chosen from an ambiguous search's candidates
+    pub fn search_word_id_with_context(
+        &self,
+        keyword: &str,
+        word_id: i32,
+        shows: &[i32],
+        options: &SearchOptions,
+    ) -> Result<SearchResult, Error> {
+        search::search_word_id_with_context(&self.conn, keyword, word_id, shows, options)
+    }
 
-            // Execute this chunk
-            tx.execute(&query, rusqlite::params_from_iter(params))?;
-        }
+    /// Gets how a word is written across the subtitles, most frequent form first
+    pub fn get_word_surface_forms(&self, word_id: i32) -> Result<Vec<SurfaceForm>, Error> {
+        Word::get_surface_forms(&self.conn, word_id)
+    }
 
-        tx.commit()?;
-        Ok(())
+    /// Performs a search for transcripts containing a grammar pattern with context, filtered by shows
+    pub fn search_grammar_pattern_with_context(
+        &self,
+        query: PatternQuery,
+        shows: &[i32],
+        min_confidence: f64,
+        options: &SearchOptions,
+    ) -> Result<SearchResult, Error> {
+        search::search_grammar_pattern_with_context(
+            &self.conn,
+            query,
+            shows,
+            min_confidence,
+            options,
+        )
     }
 
-    /// Creates a reverse index using kagome for Japanese morphological analysis
-    pub fn create_reverse_index(&mut self) -> Result<(), Error> {
-        japanese_analyzer::create_reverse_index(&mut self.conn)
+    /// Performs a compound query search (words, patterns, levels, show/episode and length filters)
+    pub fn search_query_with_context(
+        &self,
+        query: &str,
+        shows: &[i32],
+        options: &SearchOptions,
+    ) -> Result<SearchResult, Error> {
+        search::search_query_with_context(&self.conn, query, shows, options)
     }
 
-    /// Performs a search for transcripts containing a specific keyword with context, filtered by shows
-    pub fn search_word_with_context(
+    /// Performs a substring (phrase) or regular expression search over line text, filtered by shows
+    pub fn search_text_with_context(
+        &self,
+        query: TextQuery,
+        shows: &[i32],
+        options: &SearchOptions,
+    ) -> Result<SearchResult, Error> {
+        search::search_text_with_context(&self.conn, query, shows, options)
+    }
+
+    /// Gets occurrence statistics for a grammar pattern, broken down by show
+    pub fn get_pattern_stats(&self, pattern_name: &str) -> Result<Option<PatternStats>, Error> {
+        grammar_pattern::get_pattern_stats(&self.conn, pattern_name)
+    }
+
+    /// Gets the most common grammar patterns in a show, optionally filtered by JLPT level
+    pub fn get_top_patterns_for_show(
+        &self,
+        show_id: i32,
+        jlpt_level: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<RankedPattern>, Error> {
+        grammar_pattern::get_top_patterns_for_show(&self.conn, show_id, jlpt_level, limit)
+    }
+
+    /// Sets the pattern that locates a show's video files, or clears it with `None`
+    pub fn set_show_video_path_pattern(
+        &self,
+        show_id: i32,
+        pattern: Option<&str>,
+    ) -> Result<(), Error> {
+        Show::set_video_path_pattern(&self.conn, show_id, pattern)
+    }
+
+    /// Computes the video clips to cut for the given lines
+    pub fn get_cuts(
+        &self,
+        transcript_ids: &[i32],
+        options: &ClipOptions,
+    ) -> Result<Vec<Cut>, Error> {
+        cut_list::get_cuts(&self.conn, transcript_ids, options)
+    }
+
+    /// Writes Anki cards for the given lines to `path`, skipping lines exported before
+    pub fn export_anki_cards(
+        &self,
+        transcript_ids: &[i32],
+        target: &CardTarget,
+        options: &AnkiExportOptions,
+        path: &Path,
+    ) -> Result<ExportSummary, Error> {
+        anki::export_cards(&self.conn, transcript_ids, target, options, path)
+    }
+
+    /// Marks a word (dictionary form), or only its sense `word_id`, as known, learning or
+    /// ignored, or clears it with `None`
+    pub fn set_word_status(
+        &self,
+        word: &str,
+        word_id: Option<i32>,
+        status: Option<WordStatus>,
+    ) -> Result<(), Error> {
+        user_word::set_word_status(&self.conn, word, word_id, status)
+    }
+
+    /// Imports a word list or Anki plain text export into the user's vocabulary
+    pub fn import_word_list(
+        &mut self,
+        path: &str,
+        options: &WordImportOptions,
+    ) -> Result<usize, Error> {
+        let text = std::fs::read_to_string(path)?;
+        user_word::import_word_list(&mut self.conn, &text, options)
+    }
+
+    /// Finds lines with exactly one unknown word in the given shows
+    pub fn find_i_plus_one(
+        &self,
+        shows: &[i32],
+        options: &IPlusOneOptions,
+    ) -> Result<Vec<IPlusOneLine>, Error> {
+        user_word::find_i_plus_one(&self.conn, shows, options)
+    }
+
+    /// Marks an episode as watched or unwatched
+    pub fn set_episode_watched(&self, episode_id: i32, watched: bool) -> Result<(), Error> {
+        Episode::set_watched(&self.conn, episode_id, watched)
+    }
+
+    /// Coverage of one episode by known words, with its most frequent unknown words
+    pub fn get_episode_coverage(
+        &self,
+        episode_id: i32,
+        options: &CoverageOptions,
+    ) -> Result<EpisodeCoverage, Error> {
+        coverage::get_episode_coverage(&self.conn, episode_id, options)
+    }
+
+    /// Episodes of the given shows, highest coverage by known words first
+    pub fn rank_episodes_by_coverage(
+        &self,
+        shows: &[i32],
+        options: &CoverageOptions,
+    ) -> Result<Vec<EpisodeCoverage>, Error> {
+        coverage::rank_episodes_by_coverage(&self.conn, shows, options)
+    }
+
+    /// Coverage of each of the given shows by known words
+    pub fn get_show_coverage(
         &self,
-        keyword: &str,
         shows: &[i32],
-    ) -> Result<JsonValue, Error> {
-        search::search_word_with_context(&self.conn, keyword, shows)
+        options: &CoverageOptions,
+    ) -> Result<Vec<ShowCoverage>, Error> {
+        coverage::get_show_coverage(&self.conn, shows, options)
+    }
+
+    /// The words of an episode worth studying before watching it, best first
+    pub fn get_study_list(
+        &self,
+        episode_id: i32,
+        options: &StudyListOptions,
+    ) -> Result<Vec<StudyWord>, Error> {
+        study_list::get_study_list(&self.conn, episode_id, options)
+    }
+
+    /// Word frequencies across the shows selected by `options`, most frequent first
+    pub fn get_frequency_list(
+        &self,
+        options: &FrequencyListOptions,
+    ) -> Result<Vec<FrequencyEntry>, Error> {
+        frequency::get_frequency_list(&self.conn, options)
+    }
+
+    /// Row counts of the main tables, 0 for tables that can't be read
+    pub fn table_counts(&self) -> Vec<(&'static str, i64)> {
+        const TABLES: [&str; 9] = [
+            "shows",
+            "episodes",
+            "transcripts",
+            "analyzed_transcripts",
+            "words",
+            "word_occurrences",
+            "word_tokens",
+            "jlpt_levels",
+            "episode_jlpt_stats",
+        ];
+        TABLES
+            .iter()
+            .map(|&table| {
+                let count = self
+                    .conn
+                    .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                        row.get(0)
+                    })
+                    .unwrap_or(0);
+                (table, count)
+            })
+            .collect()
     }
 
     /// Imports JLPT word levels from a CSV file
@@ -390,22 +561,7 @@ impl Default for DbHandler {
 impl std::fmt::Display for DbHandler {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Database Status:")?;
-        let tables = [
-            "shows",
-            "episodes",
-            "transcripts",
-            "words",
-            "word_occurrences",
-            "jlpt_levels",
-            "episode_jlpt_stats",
-        ];
-        for table in &tables {
-            let count: i32 = self
-                .conn
-                .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
-                    row.get(0)
-                })
-                .unwrap_or(0);
+        for (table, count) in self.table_counts() {
             writeln!(f, "  {}: {} rows", table, count)?;
         }
         Ok(())
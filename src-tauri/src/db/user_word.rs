@@ -0,0 +1,478 @@
+//! The user's own vocabulary: words marked known, learning or ignored, and the "i+1" lines
+//! built on it, where exactly one content word is still unknown.
+//!
+//! Statuses are keyed like `words`, by dictionary form, reading and coarse part of speech, so
+//! they survive re-importing subtitles and homographs like 方 (ホウ/カタ) are told apart. A
+//! status with an empty reading and part of speech, e.g. from an imported word list, applies to
+//! every sense of the word that has no status of its own, and words can be marked before they
+//! appear in any line.
+
+use crate::error::Error;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WordStatus {
+    Known,
+    /// Being studied; still counts as unknown for i+1 unless `learning_is_known` is set
+    Learning,
+    /// Names, sound effects and other words that shouldn't count as unknown
+    Ignored,
+}
+
+impl WordStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            WordStatus::Known => "known",
+            WordStatus::Learning => "learning",
+            WordStatus::Ignored => "ignored",
+        }
+    }
+}
+
+/// How to read a word list. Plain lists have one word per line; Anki "Notes in Plain Text"
+/// exports have one note per line and `#` headers, and the word is one of the note's fields.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WordImportOptions {
+    pub status: WordStatus,
+    /// 1-based column holding the word, counting a GUID or note type column if the export has
+    /// one. Lines without a separator are taken whole.
+    pub column: usize,
+}
+
+impl Default for WordImportOptions {
+    fn default() -> Self {
+        Self {
+            status: WordStatus::Known,
+            column: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct IPlusOneOptions {
+    /// Count words being learned as known, so the unknown word is always a new one
+    pub learning_is_known: bool,
+    /// Least common unknown words first instead of most common
+    pub rarest_first: bool,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+/// A line with exactly one unknown word
+#[derive(Debug, Serialize)]
+pub struct IPlusOneLine {
+    pub transcript_id: i32,
+    pub text: String,
+    pub show_id: i32,
+    pub show: String,
+    pub episode_id: i32,
+    pub episode: Option<i32>,
+    pub episode_name: String,
+    /// Milliseconds from the start of the episode
+    pub time_start: i64,
+    pub time_end: i64,
+    pub word_id: i32,
+    pub word: String,
+    pub reading: Option<String>,
+    /// Where the unknown word appears in the line, in characters
+    pub spans: Vec<(u32, u32)>,
+    /// Lines the unknown word occurs in across all shows
+    pub frequency: i64,
+}
+
+const DEFAULT_I_PLUS_ONE_LIMIT: usize = 100;
+
+/// Joins `u` (user_words) onto `w` (words): the status of the word's sense if it has one,
+/// otherwise the one of its dictionary form
+pub const USER_WORD_JOIN: &str = "LEFT JOIN user_words u ON u.rowid = (
+    SELECT uw.rowid FROM user_words uw
+    WHERE uw.word = w.word
+      AND ((uw.reading = IFNULL(w.reading, '') AND uw.pos_group = w.pos_group)
+           OR (uw.reading = '' AND uw.pos_group = ''))
+    ORDER BY uw.reading = '' AND uw.pos_group = ''
+    LIMIT 1)";
+
+/// Marks a word with a status, or forgets it with `None`. With `word_id` only that sense of
+/// `word` is marked, otherwise every sense without a status of its own.
+pub fn set_word_status(
+    conn: &Connection,
+    word: &str,
+    word_id: Option<i32>,
+    status: Option<WordStatus>,
+) -> Result<(), Error> {
+    let (reading, pos_group) = match word_id {
+        Some(word_id) => conn
+            .query_row(
+                "SELECT IFNULL(reading, ''), pos_group FROM words WHERE id = ?1 AND word = ?2",
+                params![word_id, word],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| Error::Other(format!("Word {} is not '{}'", word_id, word)))?,
+        None => (String::new(), String::new()),
+    };
+
+    match status {
+        Some(status) => conn.execute(
+            "INSERT INTO user_words (word, reading, pos_group, status) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(word, reading, pos_group) DO UPDATE SET status = excluded.status",
+            params![word, reading, pos_group, status.as_str()],
+        )?,
+        None => conn.execute(
+            "DELETE FROM user_words WHERE word = ?1 AND reading = ?2 AND pos_group = ?3",
+            params![word, reading, pos_group],
+        )?,
+    };
+    Ok(())
+}
+
+/// Imports a plain word list or an Anki plain text export, giving every word in it
+/// `options.status`. Returns the number of words imported.
+pub fn import_word_list(
+    conn: &mut Connection,
+    text: &str,
+    options: &WordImportOptions,
+) -> Result<usize, Error> {
+    let column = options.column.max(1) - 1;
+    let mut separator = None;
+    let mut words = Vec::new();
+    for line in text.lines() {
+        if let Some(header) = line.strip_prefix('#') {
+            if let Some(name) = header.strip_prefix("separator:") {
+                separator = parse_separator(name);
+            }
+            continue;
+        }
+
+        let separator = separator.unwrap_or(if line.contains('\t') { '\t' } else { ',' });
+        let fields = split_fields(line, separator);
+        let field = match fields.get(column) {
+            Some(field) => field.as_str(),
+            None if fields.len() == 1 => line,
+            None => continue,
+        };
+        let word = clean_field(field);
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+
+    let tx = conn.transaction()?;
+    for word in &words {
+        set_word_status(&tx, word, None, Some(options.status))?;
+    }
+    tx.commit()?;
+    Ok(words.len())
+}
+
+/// Finds lines in the given shows where exactly one content word is neither known nor
+/// ignored, ranked by how many lines that word occurs in (most common first by default).
+///
+/// Content words are those stored in `word_occurrences`, i.e. what `extract_vocabulary`
+/// keeps: particles, auxiliaries and symbols never count as unknown.
+pub fn find_i_plus_one(
+    conn: &Connection,
+    shows: &[i32],
+    options: &IPlusOneOptions,
+) -> Result<Vec<IPlusOneLine>, Error> {
+    let direction = if options.rarest_first { "ASC" } else { "DESC" };
+    let mut stmt = conn.prepare(&format!(
+        "WITH unknown AS (
+             SELECT wo.transcript_id, wo.word_id, wo.spans
+             FROM word_occurrences wo
+             JOIN words w ON w.id = wo.word_id
+             {}
+             WHERE NOT {}
+         ),
+         targets AS (
+             SELECT transcript_id, MIN(word_id) AS word_id, MIN(spans) AS spans
+             FROM unknown
+             GROUP BY transcript_id
+             HAVING COUNT(*) = 1
+         ),
+         frequencies AS (
+             SELECT word_id, COUNT(*) AS frequency
+             FROM word_occurrences
+             WHERE word_id IN (SELECT word_id FROM targets)
+             GROUP BY word_id
+         )
+         SELECT t.id, t.text, s.id, s.name, e.id, e.episode_number, e.name,
+                t.time_start, t.time_end, w.id, w.word, w.reading, tg.spans, f.frequency
+         FROM targets tg
+         JOIN transcripts t ON t.id = tg.transcript_id
+         JOIN episodes e ON e.id = t.episode_id
+         JOIN shows s ON s.id = e.show_id
+         JOIN words w ON w.id = tg.word_id
+         JOIN frequencies f ON f.word_id = tg.word_id
+         WHERE e.show_id IN (SELECT value FROM json_each(?1))
+         ORDER BY f.frequency {}, w.id, t.id
+         LIMIT ?2 OFFSET ?3",
+        USER_WORD_JOIN,
+        is_known_sql(options.learning_is_known),
+        direction
+    ))?;
+
+    let limit = options.limit.unwrap_or(DEFAULT_I_PLUS_ONE_LIMIT);
+    let rows = stmt.query_map(
+        params![
+            serde_json::to_string(shows)?,
+            limit as i64,
+            options.offset as i64
+        ],
+        |row| {
+            Ok((
+                IPlusOneLine {
+                    transcript_id: row.get(0)?,
+                    text: row.get(1)?,
+                    show_id: row.get(2)?,
+                    show: row.get(3)?,
+                    episode_id: row.get(4)?,
+                    episode: row.get(5)?,
+                    episode_name: row.get(6)?,
+                    time_start: row.get(7)?,
+                    time_end: row.get(8)?,
+                    word_id: row.get(9)?,
+                    word: row.get(10)?,
+                    reading: row.get(11)?,
+                    spans: Vec::new(),
+                    frequency: row.get(13)?,
+                },
+                row.get::<_, String>(12)?,
+            ))
+        },
+    )?;
+
+    let mut lines = Vec::new();
+    for row in rows {
+        let (mut line, spans) = row?;
+        line.spans = serde_json::from_str(&spans)?;
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+/// SQL condition, over `u` (user_words) joined by `USER_WORD_JOIN`, that is true when the user
+/// counts the word as known. Ignored words count as known; unmarked words are unknown.
+pub fn is_known_sql(learning_is_known: bool) -> &'static str {
+    if learning_is_known {
+        "(u.status IS NOT NULL)"
+    } else {
+        "COALESCE(u.status IN ('known', 'ignored'), 0)"
+    }
+}
+
+/// Anki writes the separator as a character or a name (`#separator:Tab`)
+fn parse_separator(name: &str) -> Option<char> {
+    match name.trim().to_lowercase().as_str() {
+        "tab" => Some('\t'),
+        "comma" => Some(','),
+        "semicolon" => Some(';'),
+        "pipe" => Some('|'),
+        "space" => Some(' '),
+        "colon" => Some(':'),
+        other => other.chars().next(),
+    }
+}
+
+/// Splits a line on `separator`, honouring double-quoted fields ("a, b" and "")
+fn split_fields(line: &str, separator: char) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted || field.is_empty() => quoted = !quoted,
+            c if c == separator && !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Reduces an Anki field to the bare word: drops HTML and furigana (` 食[た]べる` → 食べる)
+fn clean_field(field: &str) -> String {
+    let mut word = String::new();
+    let mut in_tag = false;
+    let mut in_reading = false;
+    for c in field.replace("&nbsp;", " ").chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            '[' if !in_tag => in_reading = true,
+            ']' if !in_tag => in_reading = false,
+            c if in_tag || in_reading || c.is_whitespace() => {}
+            c => word.push(c),
+        }
+    }
+    word.replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::word::{Word, WordOccurrence};
+    use crate::test_utils::{create_test_db, create_test_hierarchy, create_test_transcript};
+
+    #[test]
+    fn test_import_word_list() {
+        let (_file, mut handler) = create_test_db();
+        let anki_export = "#separator:tab\n#html:true\n#guid column:1\n\
+            abc123\t<b>食べる</b>\tto eat\n\
+            def456\t 勉強[べんきょう]\tstudy\n\
+            ghi789\t\"<span class=\"\"word\"\">本</span>\"\tbook\n";
+        let options = WordImportOptions {
+            status: WordStatus::Known,
+            column: 2,
+        };
+        assert_eq!(
+            import_word_list(&mut handler.conn, anki_export, &options).unwrap(),
+            3
+        );
+
+        let plain_list = "猫\n\n食べる\n";
+        let options = WordImportOptions {
+            status: WordStatus::Learning,
+            column: 1,
+        };
+        assert_eq!(
+            import_word_list(&mut handler.conn, plain_list, &options).unwrap(),
+            2
+        );
+
+        let mut stmt = handler
+            .conn
+            .prepare("SELECT word, status FROM user_words ORDER BY word")
+            .unwrap();
+        let words: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            words,
+            vec![
+                ("勉強".to_string(), "known".to_string()),
+                ("本".to_string(), "known".to_string()),
+                ("猫".to_string(), "learning".to_string()),
+                ("食べる".to_string(), "learning".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_status_by_sense() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let mut senses = Vec::new();
+        for (reading, pos) in [
+            ("ホウ", r#"["名詞","非自立"]"#),
+            ("カタ", r#"["名詞","接尾"]"#),
+        ] {
+            let mut word = Word::new("方".to_string(), Some(reading.to_string()), pos.to_string());
+            word.insert(conn).unwrap();
+            senses.push(word.id.unwrap());
+        }
+        let status_of = |word_id: i32| -> Option<String> {
+            conn.query_row(
+                &format!(
+                    "SELECT u.status FROM words w {} WHERE w.id = ?1",
+                    USER_WORD_JOIN
+                ),
+                params![word_id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        // Marking one sense leaves the other unknown
+        set_word_status(conn, "方", Some(senses[0]), Some(WordStatus::Known)).unwrap();
+        assert_eq!(status_of(senses[0]).as_deref(), Some("known"));
+        assert_eq!(status_of(senses[1]), None);
+
+        // A status for the dictionary form applies to senses without their own
+        set_word_status(conn, "方", None, Some(WordStatus::Learning)).unwrap();
+        assert_eq!(status_of(senses[0]).as_deref(), Some("known"));
+        assert_eq!(status_of(senses[1]).as_deref(), Some("learning"));
+
+        set_word_status(conn, "方", Some(senses[0]), None).unwrap();
+        assert_eq!(status_of(senses[0]).as_deref(), Some("learning"));
+        assert!(set_word_status(conn, "猫", Some(senses[0]), Some(WordStatus::Known)).is_err());
+    }
+
+    #[test]
+    fn test_find_i_plus_one() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let (show, episode, _) = create_test_hierarchy(&handler);
+
+        let mut word_ids = Vec::new();
+        for text in ["猫", "食べる", "魚", "犬"] {
+            let mut word = Word::new(text.to_string(), None, "[]".to_string());
+            word.insert(conn).unwrap();
+            word_ids.push(word.id.unwrap());
+        }
+        let [cat, eat, fish, dog] = word_ids[..] else {
+            unreachable!()
+        };
+        // Lines and the words in them: 猫+魚 (one unknown), 猫+食べる+魚 (two unknown),
+        // 犬 (one unknown, less common), 猫 (none unknown)
+        for (line_id, text, words) in [
+            (2, "猫が魚を", vec![(cat, (0, 1)), (fish, (2, 3))]),
+            (
+                3,
+                "猫が魚を食べる",
+                vec![(cat, (0, 1)), (fish, (2, 3)), (eat, (4, 7))],
+            ),
+            (4, "犬だ", vec![(dog, (0, 1))]),
+            (5, "猫だ", vec![(cat, (0, 1))]),
+        ] {
+            let transcript = create_test_transcript(&handler, &episode, line_id, 0, 1000, text);
+            for (word_id, span) in words {
+                WordOccurrence::new(word_id, transcript.id.unwrap(), vec![span])
+                    .insert(conn)
+                    .unwrap();
+            }
+        }
+        set_word_status(conn, "猫", None, Some(WordStatus::Known)).unwrap();
+        set_word_status(conn, "食べる", None, Some(WordStatus::Learning)).unwrap();
+
+        let shows = [show.id.unwrap()];
+        let lines = find_i_plus_one(conn, &shows, &IPlusOneOptions::default()).unwrap();
+        let found: Vec<(&str, &str, i64)> = lines
+            .iter()
+            .map(|line| (line.text.as_str(), line.word.as_str(), line.frequency))
+            .collect();
+        assert_eq!(found, vec![("猫が魚を", "魚", 2), ("犬だ", "犬", 1)]);
+        assert_eq!(lines[0].spans, vec![(2, 3)]);
+
+        let options = IPlusOneOptions {
+            learning_is_known: true,
+            rarest_first: true,
+            ..IPlusOneOptions::default()
+        };
+        let lines = find_i_plus_one(conn, &shows, &options).unwrap();
+        let found: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(found, vec!["犬だ", "猫が魚を", "猫が魚を食べる"]);
+
+        // Ignored words don't count, and other shows are filtered out
+        set_word_status(conn, "魚", None, Some(WordStatus::Ignored)).unwrap();
+        set_word_status(conn, "犬", None, None).unwrap();
+        let lines = find_i_plus_one(conn, &shows, &IPlusOneOptions::default()).unwrap();
+        let found: Vec<&str> = lines.iter().map(|line| line.word.as_str()).collect();
+        assert_eq!(found, vec!["食べる", "犬"]);
+        assert!(
+            find_i_plus_one(conn, &[show.id.unwrap() + 1], &IPlusOneOptions::default())
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,6 +1,9 @@
-use crate::db::transcript::Transcript;
 use crate::error::Error;
+use grammar_lib::{is_kana, to_hiragana, to_katakana};
 use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::LazyLock;
 
 #[derive(Debug)]
 pub struct Word {
@@ -13,11 +16,77 @@ pub struct Word {
     pub pos: String,
 }
 
+/// A word that search input may refer to, with its number of occurrences so the user
+/// can pick between homophones like 掛ける/書ける/欠ける
+#[derive(Debug, Clone, Serialize)]
+pub struct WordCandidate {
+    pub id: i32,
+    pub word: String,
+    pub reading: Option<String>,
+    pub pos: String,
+    /// Tokens of the word over all lines, like coverage and frequencies count them
+    pub occurrences: i64,
+}
+
+/// A way a word is written in the subtitles, e.g. 食べた or 食べない for 食べる
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SurfaceForm {
+    pub surface: String,
+    /// Tokens written this way
+    pub count: i64,
+}
+
+/// SQL condition over `w` (words) for words that aren't vocabulary worth counting or
+/// studying: proper nouns, fillers and interjections, and numbers
+pub const EXCLUDED_POS_SQL: &str = "(
+    (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '固有名詞')
+    OR JSON_EXTRACT(w.pos, '$[0]') IN ('フィラー', 'その他')
+    OR (JSON_EXTRACT(w.pos, '$[0]') = '感動詞' AND JSON_EXTRACT(w.pos, '$[1]') = '間投')
+    OR (JSON_EXTRACT(w.pos, '$[0]') = '名詞' AND JSON_EXTRACT(w.pos, '$[1]') = '数')
+)";
+
+/// Tokens of a word in one line (`wo`, a word_occurrences row). Rows stored before spans were
+/// recorded count once.
+pub const TOKENS_SQL: &str = "MAX(json_array_length(wo.spans), 1)";
+
+/// The coarse part of speech (名詞, 動詞, ...) of a `pos` JSON array. With the dictionary
+/// form and reading it identifies a word, so 方 (ホウ) and 方 (カタ) are separate words.
+#[cfg(test)]
+fn pos_group(pos: &str) -> String {
+    serde_json::from_str::<Vec<String>>(pos)
+        .ok()
+        .and_then(|parts| parts.into_iter().next())
+        .unwrap_or_default()
+}
+
+static CANDIDATE_SELECT: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "SELECT w.id, w.word, w.reading, w.pos,
+            (SELECT IFNULL(SUM({}), 0) FROM word_occurrences wo WHERE wo.word_id = w.id)
+                AS occurrences
+         FROM words w",
+        TOKENS_SQL
+    )
+});
+
+impl WordCandidate {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(WordCandidate {
+            id: row.get(0)?,
+            word: row.get(1)?,
+            reading: row.get(2)?,
+            pos: row.get(3)?,
+            occurrences: row.get(4)?,
+        })
+    }
+}
+
 #[cfg(test)]
 #[derive(Debug)]
 pub struct WordOccurrence {
     pub word_id: i32,
     pub transcript_id: i32,
+    pub spans: Vec<(u32, u32)>,
 }
 
 impl Word {
@@ -34,8 +103,8 @@ impl Word {
     #[cfg(test)]
     pub fn insert(&mut self, conn: &Connection) -> Result<(), Error> {
         conn.execute(
-            "INSERT INTO words (word, reading, pos) VALUES (?1, ?2, ?3)",
-            params![self.word, self.reading, self.pos],
+            "INSERT INTO words (word, reading, pos, pos_group) VALUES (?1, ?2, ?3, ?4)",
+            params![self.word, self.reading, self.pos, pos_group(&self.pos)],
         )?;
         // Convert the last inserted row id to i32 and assign it to the word's id field
         crate::db::model::set_id_from_last_insert(&mut self.id, conn);
@@ -45,8 +114,14 @@ impl Word {
     #[cfg(test)]
     pub fn update(&self, conn: &Connection) -> Result<(), Error> {
         conn.execute(
-            "UPDATE words SET word = ?1, reading = ?2, pos = ?3 WHERE id = ?4",
-            params![self.word, self.reading, self.pos, self.id],
+            "UPDATE words SET word = ?1, reading = ?2, pos = ?3, pos_group = ?4 WHERE id = ?5",
+            params![
+                self.word,
+                self.reading,
+                self.pos,
+                pos_group(&self.pos),
+                self.id
+            ],
         )?;
         Ok(())
     }
@@ -57,7 +132,6 @@ impl Word {
         Ok(())
     }
 
-    #[cfg(test)]
     pub fn get_by_id(conn: &Connection, id: i32) -> Result<Word, Error> {
         let mut stmt = conn.prepare("SELECT id, word, reading, pos FROM words WHERE id = ?1")?;
         stmt.query_row(params![id], |row| {
@@ -71,16 +145,36 @@ impl Word {
         .map_err(Error::from)
     }
 
-    pub fn get_by_word(conn: &Connection, word_text: &str) -> Result<Word, Error> {
-        let mut stmt = conn.prepare("SELECT id, word, reading, pos FROM words WHERE word = ?1")?;
-        stmt.query_row(params![word_text], |row| {
-            Ok(Word {
-                id: Some(row.get(0)?),
-                word: row.get(1)?,
-                reading: row.get(2)?,
-                pos: row.get(3)?,
-            })
-        })
+    /// Finds the words that search input may refer to, most frequent first. The input
+    /// matches a word's dictionary form in either kana script, and kana-only input also
+    /// matches by reading (たべる, タベル → 食べる).
+    pub fn find_candidates(conn: &Connection, input: &str) -> Result<Vec<WordCandidate>, Error> {
+        let katakana = to_katakana(input);
+        let hiragana = to_hiragana(input);
+        let match_reading = is_kana(input);
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE w.word IN (?1, ?2, ?3) OR (?4 AND w.reading IN (?2, ?3))
+             ORDER BY occurrences DESC, w.id",
+            *CANDIDATE_SELECT
+        ))?;
+        let candidates = stmt
+            .query_map(
+                params![input, katakana, hiragana, match_reading],
+                WordCandidate::from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(candidates)
+    }
+
+    /// Gets a word as a search candidate, e.g. once the user has picked it
+    pub fn get_candidate(conn: &Connection, id: i32) -> Result<WordCandidate, Error> {
+        conn.query_row(
+            &format!("{} WHERE w.id = ?1", *CANDIDATE_SELECT),
+            params![id],
+            WordCandidate::from_row,
+        )
         .map_err(Error::from)
     }
 
@@ -102,133 +196,90 @@ impl Word {
             .map_err(Error::from)
     }
 
-    pub fn get_transcripts(
+    /// Returns the (start_char, end_char) spans of this word in each of the given lines it
+    /// occurs in, keyed by transcript id
+    pub fn get_spans(
         &self,
         conn: &Connection,
-        show_ids: &[i32],
-    ) -> Result<Vec<Transcript>, Error> {
-        if show_ids.is_empty() {
-            return Ok(Vec::new());
-        }
-
-        // For small number of show_ids, use direct IN clause for better performance
-        if show_ids.len() <= 10 {
-            let placeholders = show_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-            let query = format!(
-                "SELECT DISTINCT t.id, t.episode_id, t.line_id, t.time_start, t.time_end, t.text
-                FROM transcripts t
-                JOIN word_occurrences wo ON t.id = wo.transcript_id
-                JOIN episodes e ON t.episode_id = e.id
-                WHERE wo.word_id = ?1 AND e.show_id IN ({})",
-                placeholders
-            );
+        transcript_ids: &[i32],
+    ) -> Result<HashMap<i32, Vec<(u32, u32)>>, Error> {
+        let mut stmt = conn.prepare(
+            "SELECT transcript_id, spans FROM word_occurrences
+             WHERE word_id = ?1 AND transcript_id IN (SELECT value FROM json_each(?2))",
+        )?;
+        let rows = stmt.query_map(
+            params![self.id, serde_json::to_string(transcript_ids)?],
+            |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)),
+        )?;
 
-            let mut stmt = conn.prepare(&query)?;
-            let mut params: Vec<&dyn rusqlite::ToSql> = vec![&self.id];
-            params.extend(show_ids.iter().map(|id| id as &dyn rusqlite::ToSql));
-
-            let transcripts_iter = stmt.query_map(rusqlite::params_from_iter(params), |row| {
-                Ok(Transcript {
-                    id: Some(row.get(0)?),
-                    episode_id: row.get(1)?,
-                    line_id: row.get(2)?,
-                    time_start: row.get(3)?,
-                    time_end: row.get(4)?,
-                    text: row.get(5)?,
-                })
-            })?;
-
-            transcripts_iter
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(Error::from)
-        } else {
-            // For large number of show_ids, use a temporary table approach
-            self.get_transcripts_large_set(conn, show_ids)
+        let mut spans_by_transcript = HashMap::new();
+        for row in rows {
+            let (transcript_id, spans) = row?;
+            spans_by_transcript.insert(transcript_id, serde_json::from_str(&spans)?);
         }
+        Ok(spans_by_transcript)
     }
 
-    fn get_transcripts_large_set(
-        &self,
-        conn: &Connection,
-        show_ids: &[i32],
-    ) -> Result<Vec<Transcript>, Error> {
-        let tx = conn.unchecked_transaction()?;
-
-        // Create temporary table for show_ids
-        tx.execute(
-            "CREATE TEMP TABLE temp_show_ids (show_id INTEGER PRIMARY KEY)",
-            [],
-        )?;
-
-        // Batch insert show_ids
-        let mut stmt = tx.prepare("INSERT INTO temp_show_ids (show_id) VALUES (?)")?;
-        for &show_id in show_ids {
-            stmt.execute([show_id])?;
-        }
-        drop(stmt);
-
-        // Execute optimized query
-        let mut stmt = tx.prepare(
-            "SELECT DISTINCT t.id, t.episode_id, t.line_id, t.time_start, t.time_end, t.text
-            FROM transcripts t
-            JOIN word_occurrences wo ON t.id = wo.transcript_id
-            JOIN episodes e ON t.episode_id = e.id
-            JOIN temp_show_ids tsi ON e.show_id = tsi.show_id
-            WHERE wo.word_id = ?1",
+    /// Returns how a word is written across the subtitles, most frequent form first
+    pub fn get_surface_forms(conn: &Connection, word_id: i32) -> Result<Vec<SurfaceForm>, Error> {
+        let mut stmt = conn.prepare(
+            "SELECT surface, COUNT(*) AS count FROM word_tokens
+             WHERE word_id = ?1
+             GROUP BY surface
+             ORDER BY count DESC, surface",
         )?;
-
-        let transcripts_iter = stmt.query_map([&self.id], |row| {
-            Ok(Transcript {
-                id: Some(row.get(0)?),
-                episode_id: row.get(1)?,
-                line_id: row.get(2)?,
-                time_start: row.get(3)?,
-                time_end: row.get(4)?,
-                text: row.get(5)?,
-            })
-        })?;
-
-        let results = transcripts_iter.collect::<Result<Vec<_>, _>>()?;
-        drop(stmt); // Explicitly drop the statement before committing
-
-        // Clean up temp table
-        tx.execute("DROP TABLE temp_show_ids", [])?;
-        tx.commit()?;
-
-        Ok(results)
+        let forms = stmt
+            .query_map(params![word_id], |row| {
+                Ok(SurfaceForm {
+                    surface: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(forms)
     }
 }
 
 #[cfg(test)]
 impl WordOccurrence {
-    pub fn new(word_id: i32, transcript_id: i32) -> Self {
+    pub fn new(word_id: i32, transcript_id: i32, spans: Vec<(u32, u32)>) -> Self {
         WordOccurrence {
             word_id,
             transcript_id,
+            spans,
         }
     }
 
     pub fn insert(&self, conn: &Connection) -> Result<(), Error> {
         conn.execute(
-            "INSERT OR IGNORE INTO word_occurrences (word_id, transcript_id) VALUES (?1, ?2)",
-            params![self.word_id, self.transcript_id],
+            "INSERT OR IGNORE INTO word_occurrences (word_id, transcript_id, spans) VALUES (?1, ?2, ?3)",
+            params![
+                self.word_id,
+                self.transcript_id,
+                serde_json::to_string(&self.spans)?
+            ],
         )?;
         Ok(())
     }
 
     pub fn get_by_word_id(conn: &Connection, word_id: i32) -> Result<Vec<WordOccurrence>, Error> {
-        let mut stmt =
-            conn.prepare("SELECT word_id, transcript_id FROM word_occurrences WHERE word_id = ?1")?;
+        let mut stmt = conn.prepare(
+            "SELECT word_id, transcript_id, spans FROM word_occurrences WHERE word_id = ?1",
+        )?;
         let occurrences_iter = stmt.query_map(params![word_id], |row| {
-            Ok(WordOccurrence {
-                word_id: row.get(0)?,
-                transcript_id: row.get(1)?,
-            })
+            Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?))
         })?;
 
-        occurrences_iter
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(Error::from)
+        let mut occurrences = Vec::new();
+        for row in occurrences_iter {
+            let (word_id, transcript_id, spans) = row?;
+            occurrences.push(WordOccurrence {
+                word_id,
+                transcript_id,
+                spans: serde_json::from_str(&spans)?,
+            });
+        }
+        Ok(occurrences)
     }
 }
 
@@ -309,23 +360,175 @@ mod tests {
     #[test]
     fn test_word_occurrences() {
         let (_file, handler) = create_test_db();
-        let (show, _episode, transcript) = create_test_hierarchy(&handler);
+        let (_show, _episode, transcript) = create_test_hierarchy(&handler);
 
         let mut word = Word::new("hello".to_string(), None, "noun".to_string());
         word.insert(&handler.conn).unwrap();
 
-        let occurrence = WordOccurrence::new(word.id.unwrap(), transcript.id.unwrap());
+        let occurrence = WordOccurrence::new(
+            word.id.unwrap(),
+            transcript.id.unwrap(),
+            vec![(0, 2), (5, 7)],
+        );
         occurrence.insert(&handler.conn).unwrap();
 
         let occurrences = WordOccurrence::get_by_word_id(&handler.conn, word.id.unwrap()).unwrap();
         assert_eq!(occurrences.len(), 1);
         assert_eq!(occurrences[0].transcript_id, transcript.id.unwrap());
+        assert_eq!(occurrences[0].spans, vec![(0, 2), (5, 7)]);
+
+        let spans = word
+            .get_spans(&handler.conn, &[transcript.id.unwrap()])
+            .unwrap();
+        assert_eq!(spans[&transcript.id.unwrap()], vec![(0, 2), (5, 7)]);
+        assert!(word.get_spans(&handler.conn, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_surface_forms() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let (_show, _episode, transcript) = create_test_hierarchy(&handler);
+
+        let mut word = Word::new("食べる".to_string(), None, "[]".to_string());
+        word.insert(conn).unwrap();
+        for (token_index, surface) in [(0, "食べた"), (2, "食べる"), (4, "食べた")] {
+            conn.execute(
+                "INSERT INTO word_tokens
+                 (word_id, transcript_id, token_index, start_char, end_char, surface)
+                 VALUES (?1, ?2, ?3, 0, 3, ?4)",
+                params![word.id, transcript.id, token_index, surface],
+            )
+            .unwrap();
+        }
+
+        let forms = Word::get_surface_forms(conn, word.id.unwrap()).unwrap();
+        assert_eq!(
+            forms,
+            vec![
+                SurfaceForm {
+                    surface: "食べた".to_string(),
+                    count: 2
+                },
+                SurfaceForm {
+                    surface: "食べる".to_string(),
+                    count: 1
+                },
+            ]
+        );
+    }
 
-        // Use the show ID in the get_transcripts call
-        let transcripts = word
-            .get_transcripts(&handler.conn, &[show.id.unwrap()])
+    #[test]
+    fn test_homographs_are_separate_words() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let (_show, _episode, transcript) = create_test_hierarchy(&handler);
+
+        let mut words = Vec::new();
+        for (reading, pos) in [
+            ("ホウ", r#"["名詞","非自立"]"#),
+            ("カタ", r#"["名詞","接尾"]"#),
+        ] {
+            let mut word = Word::new("方".to_string(), Some(reading.to_string()), pos.to_string());
+            word.insert(conn).unwrap();
+            words.push(word);
+        }
+        // Same dictionary form, reading and coarse POS is the same word
+        let mut duplicate = Word::new(
+            "方".to_string(),
+            Some("ホウ".to_string()),
+            r#"["名詞","一般"]"#.to_string(),
+        );
+        assert!(duplicate.insert(conn).is_err());
+
+        WordOccurrence::new(
+            words[1].id.unwrap(),
+            transcript.id.unwrap(),
+            vec![(0, 1), (3, 4)],
+        )
+        .insert(conn)
+        .unwrap();
+        let candidates = Word::find_candidates(conn, "方").unwrap();
+        let senses: Vec<_> = candidates
+            .iter()
+            .map(|c| (c.reading.as_deref().unwrap(), c.occurrences))
+            .collect();
+        // Occurrences count tokens, so カタ twice in one line is 2
+        assert_eq!(senses, vec![("カタ", 2), ("ホウ", 0)]);
+    }
+
+    #[test]
+    fn test_migrates_words_keyed_by_dictionary_form() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let conn = Connection::open(file.path()).unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE words (
+                   id INTEGER PRIMARY KEY,
+                   word TEXT NOT NULL UNIQUE,
+                   reading TEXT,
+                   pos TEXT NOT NULL
+               );
+               INSERT INTO words VALUES (7, '方', 'ホウ', '["名詞","非自立"]');"#,
+        )
+        .unwrap();
+        drop(conn);
+
+        let handler = crate::db::DbHandler::new(file.path().to_str().unwrap()).unwrap();
+        handler.create_tables().unwrap();
+        let word = Word::get_by_id(&handler.conn, 7).unwrap();
+        assert_eq!(word.reading.as_deref(), Some("ホウ"));
+        let group: String = handler
+            .conn
+            .query_row("SELECT pos_group FROM words WHERE id = 7", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(group, "名詞");
+
+        let mut other = Word::new(
+            "方".to_string(),
+            Some("カタ".to_string()),
+            r#"["名詞","接尾"]"#.to_string(),
+        );
+        other.insert(&handler.conn).unwrap();
+    }
+
+    #[test]
+    fn test_find_candidates_by_kana_and_reading() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let (_show, _episode, transcript) = create_test_hierarchy(&handler);
+
+        let mut words = Vec::new();
+        for (text, reading) in [
+            ("掛ける", "カケル"),
+            ("書ける", "カケル"),
+            ("食べる", "タベル"),
+        ] {
+            let mut word = Word::new(
+                text.to_string(),
+                Some(reading.to_string()),
+                "[]".to_string(),
+            );
+            word.insert(conn).unwrap();
+            words.push(word);
+        }
+        WordOccurrence::new(words[1].id.unwrap(), transcript.id.unwrap(), vec![(0, 3)])
+            .insert(conn)
             .unwrap();
-        assert_eq!(transcripts.len(), 1);
-        assert_eq!(transcripts[0].id, transcript.id);
+
+        // Homophones come back together, most frequent first
+        let candidates = Word::find_candidates(conn, "かける").unwrap();
+        let found: Vec<_> = candidates.iter().map(|c| c.word.as_str()).collect();
+        assert_eq!(found, vec!["書ける", "掛ける"]);
+        assert_eq!(candidates[0].occurrences, 1);
+
+        assert_eq!(
+            Word::find_candidates(conn, "タベル").unwrap()[0].word,
+            "食べる"
+        );
+        assert_eq!(Word::find_candidates(conn, "食べる").unwrap().len(), 1);
+        // Kanji input doesn't match by reading
+        assert!(Word::find_candidates(conn, "駆ける").unwrap().is_empty());
     }
 }
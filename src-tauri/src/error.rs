@@ -6,6 +6,8 @@ pub enum Error {
     Io(std::io::Error),
     Database(rusqlite::Error),
     Json(serde_json::Error),
+    /// A job stopped at the user's request (see `jobs`)
+    Cancelled,
     Other(String),
 }
 
@@ -15,6 +17,7 @@ impl fmt::Display for Error {
             Error::Io(err) => write!(f, "IO error: {}", err),
             Error::Database(err) => write!(f, "Database error: {}", err),
             Error::Json(err) => write!(f, "JSON error: {}", err),
+            Error::Cancelled => write!(f, "Cancelled"),
             Error::Other(msg) => write!(f, "Error: {}", msg),
         }
     }
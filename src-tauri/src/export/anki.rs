@@ -0,0 +1,530 @@
+//! Sentence mining export: cards as TSV for Anki's text import, as plain CSV, or as an `.apkg`
+//! package with its own note type.
+//!
+//! Every note gets a GUID derived from its line and target, so importing a line twice updates
+//! the existing note instead of duplicating it. Exported GUIDs are also recorded in
+//! `exported_notes` and skipped on later exports unless `include_exported` is set.
+
+use super::cards::{get_cards, Card, CardTarget};
+use super::{csv_field, format_time, zip_error};
+use crate::error::Error;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write as _;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardField {
+    Sentence,
+    Highlighted,
+    Target,
+    BaseForm,
+    Reading,
+    Furigana,
+    GrammarPattern,
+    Show,
+    Episode,
+    TimeStart,
+    TimeEnd,
+}
+
+impl CardField {
+    /// The Anki field name, as used in templates (`{{Sentence}}`)
+    pub fn name(self) -> &'static str {
+        match self {
+            CardField::Sentence => "Sentence",
+            CardField::Highlighted => "Highlighted",
+            CardField::Target => "Target",
+            CardField::BaseForm => "BaseForm",
+            CardField::Reading => "Reading",
+            CardField::Furigana => "Furigana",
+            CardField::GrammarPattern => "GrammarPattern",
+            CardField::Show => "Show",
+            CardField::Episode => "Episode",
+            CardField::TimeStart => "TimeStart",
+            CardField::TimeEnd => "TimeEnd",
+        }
+    }
+
+    fn value(self, card: &Card) -> String {
+        match self {
+            CardField::Sentence => card.sentence.clone(),
+            CardField::Highlighted => card.highlighted.clone(),
+            CardField::Target => card.target.clone(),
+            CardField::BaseForm => card.base_form.clone(),
+            CardField::Reading => card.reading.clone(),
+            CardField::Furigana => card.furigana.clone(),
+            CardField::GrammarPattern => card.grammar_pattern.clone(),
+            CardField::Show => card.show.clone(),
+            CardField::Episode => match card.episode {
+                Some(number) => number.to_string(),
+                None => card.episode_name.clone(),
+            },
+            CardField::TimeStart => format_time(card.time_start),
+            CardField::TimeEnd => format_time(card.time_end),
+        }
+    }
+}
+
+/// The note type of exported cards. The first field is the sort field Anki shows in the browser.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NoteType {
+    pub name: String,
+    pub fields: Vec<CardField>,
+    pub front_template: String,
+    pub back_template: String,
+    pub css: String,
+}
+
+impl Default for NoteType {
+    fn default() -> Self {
+        Self {
+            name: "Japanese Subtitle Sentence".to_string(),
+            fields: vec![
+                CardField::Highlighted,
+                CardField::Sentence,
+                CardField::Target,
+                CardField::BaseForm,
+                CardField::Reading,
+                CardField::Furigana,
+                CardField::GrammarPattern,
+                CardField::Show,
+                CardField::Episode,
+                CardField::TimeStart,
+                CardField::TimeEnd,
+            ],
+            front_template: "<div class=sentence>{{Highlighted}}</div>".to_string(),
+            back_template: "{{FrontSide}}\n<hr id=answer>\n\
+                <div class=word>{{furigana:Furigana}}</div>\n\
+                <div>{{GrammarPattern}}</div>\n\
+                <div class=source>{{Show}} {{Episode}} {{TimeStart}}</div>"
+                .to_string(),
+            css: ".card { font-size: 24px; text-align: center; }\n\
+                .sentence b { color: #e0575b; }\n\
+                .source { font-size: 14px; color: grey; }"
+                .to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnkiFormat {
+    #[default]
+    Apkg,
+    /// Tab-separated with Anki's import headers (note type, deck, GUID and tags columns)
+    Tsv,
+    /// Plain CSV with a header row, for spreadsheets and other tools
+    Csv,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AnkiExportOptions {
+    pub format: AnkiFormat,
+    pub deck_name: String,
+    pub note_type: NoteType,
+    pub tags: Vec<String>,
+    /// Export lines that were already exported for the same target
+    pub include_exported: bool,
+}
+
+impl Default for AnkiExportOptions {
+    fn default() -> Self {
+        Self {
+            format: AnkiFormat::default(),
+            deck_name: "Subtitle Sentences".to_string(),
+            note_type: NoteType::default(),
+            tags: vec!["subtitles".to_string()],
+            include_exported: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportSummary {
+    pub exported: usize,
+    /// Lines skipped because they were exported before
+    pub skipped: usize,
+}
+
+/// Writes cards for the given lines to `path` and records them as exported
+pub fn export_cards(
+    conn: &Connection,
+    transcript_ids: &[i32],
+    target: &CardTarget,
+    options: &AnkiExportOptions,
+    path: &Path,
+) -> Result<ExportSummary, Error> {
+    if options.note_type.fields.is_empty() {
+        return Err(Error::Other(
+            "The note type needs at least one field".to_string(),
+        ));
+    }
+
+    let mut notes = Vec::new();
+    let mut skipped = 0;
+    for card in get_cards(conn, transcript_ids, target)? {
+        let guid = note_guid(card.transcript_id, target);
+        if !options.include_exported && was_exported(conn, &guid)? {
+            skipped += 1;
+            continue;
+        }
+        notes.push((guid, card));
+    }
+
+    match options.format {
+        AnkiFormat::Apkg => write_apkg(&notes, options, path)?,
+        AnkiFormat::Tsv => std::fs::write(path, format_tsv(&notes, options))?,
+        AnkiFormat::Csv => std::fs::write(path, format_csv(&notes, options))?,
+    }
+
+    let now = now_millis();
+    for (guid, card) in &notes {
+        conn.execute(
+            "INSERT OR REPLACE INTO exported_notes (guid, transcript_id, exported_at)
+             VALUES (?1, ?2, ?3)",
+            params![guid, card.transcript_id, now],
+        )?;
+    }
+
+    Ok(ExportSummary {
+        exported: notes.len(),
+        skipped,
+    })
+}
+
+/// A stable GUID for a line and target, so re-imports update the same note
+fn note_guid(transcript_id: i32, target: &CardTarget) -> String {
+    let key = format!(
+        "japanese-subtitle-parser:{}:{}:{}",
+        transcript_id,
+        target.word_id.map(|id| id.to_string()).unwrap_or_default(),
+        target.pattern_name.as_deref().unwrap_or_default()
+    );
+    sha1_smol::Sha1::from(key).digest().to_string()[..16].to_string()
+}
+
+fn was_exported(conn: &Connection, guid: &str) -> Result<bool, Error> {
+    Ok(conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM exported_notes WHERE guid = ?1)",
+        params![guid],
+        |row| row.get(0),
+    )?)
+}
+
+fn format_tsv(notes: &[(String, Card)], options: &AnkiExportOptions) -> String {
+    let fields = &options.note_type.fields;
+    let mut tsv = String::new();
+    let _ = writeln!(tsv, "#separator:tab\n#html:true");
+    let _ = writeln!(tsv, "#notetype:{}", options.note_type.name);
+    let _ = writeln!(tsv, "#deck:{}", options.deck_name);
+    let _ = writeln!(tsv, "#guid column:1");
+    let _ = writeln!(tsv, "#tags column:{}", fields.len() + 2);
+    let names: Vec<&str> = fields.iter().map(|field| field.name()).collect();
+    let _ = writeln!(tsv, "#columns:GUID\t{}\tTags", names.join("\t"));
+
+    let tags = options.tags.join(" ");
+    for (guid, card) in notes {
+        let values: Vec<String> = fields
+            .iter()
+            .map(|field| field.value(card).replace('\t', " ").replace('\n', "<br>"))
+            .collect();
+        let _ = writeln!(tsv, "{}\t{}\t{}", guid, values.join("\t"), tags);
+    }
+    tsv
+}
+
+fn format_csv(notes: &[(String, Card)], options: &AnkiExportOptions) -> String {
+    let fields = &options.note_type.fields;
+    let names: Vec<&str> = fields.iter().map(|field| field.name()).collect();
+    let mut csv = format!("{}\n", names.join(","));
+    for (_, card) in notes {
+        let values: Vec<String> = fields
+            .iter()
+            .map(|field| csv_field(&field.value(card)))
+            .collect();
+        let _ = writeln!(csv, "{}", values.join(","));
+    }
+    csv
+}
+
+/// Anki's legacy (schema 11) collection, which every Anki version can import
+const COLLECTION_SCHEMA: &str = "
+    CREATE TABLE col (
+        id integer primary key, crt integer not null, mod integer not null,
+        scm integer not null, ver integer not null, dty integer not null, usn integer not null,
+        ls integer not null, conf text not null, models text not null, decks text not null,
+        dconf text not null, tags text not null
+    );
+    CREATE TABLE notes (
+        id integer primary key, guid text not null, mid integer not null, mod integer not null,
+        usn integer not null, tags text not null, flds text not null, sfld integer not null,
+        csum integer not null, flags integer not null, data text not null
+    );
+    CREATE TABLE cards (
+        id integer primary key, nid integer not null, did integer not null, ord integer not null,
+        mod integer not null, usn integer not null, type integer not null,
+        queue integer not null, due integer not null, ivl integer not null,
+        factor integer not null, reps integer not null, lapses integer not null,
+        left integer not null, odue integer not null, odid integer not null,
+        flags integer not null, data text not null
+    );
+    CREATE TABLE revlog (
+        id integer primary key, cid integer not null, usn integer not null,
+        ivl integer not null, lastIvl integer not null, factor integer not null,
+        time integer not null, type integer not null
+    );
+    CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+";
+
+fn write_apkg(
+    notes: &[(String, Card)],
+    options: &AnkiExportOptions,
+    path: &Path,
+) -> Result<(), Error> {
+    let collection_file = tempfile::NamedTempFile::new()?;
+    {
+        let collection = Connection::open(collection_file.path())?;
+        write_collection(&collection, notes, options)?;
+    }
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+    // Stored uncompressed; Anki doesn't mind and it keeps the zip dependency small
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("collection.anki2", file_options)
+        .map_err(zip_error)?;
+    zip.write_all(&std::fs::read(collection_file.path())?)?;
+    // No media files
+    zip.start_file("media", file_options).map_err(zip_error)?;
+    zip.write_all(b"{}")?;
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+fn write_collection(
+    collection: &Connection,
+    notes: &[(String, Card)],
+    options: &AnkiExportOptions,
+) -> Result<(), Error> {
+    collection.execute_batch(COLLECTION_SCHEMA)?;
+
+    let now = now_millis();
+    let note_type = &options.note_type;
+    // Stable ids keep later exports on the same note type and deck
+    let model_id = stable_id(&note_type.name);
+    let deck_id = stable_id(&options.deck_name);
+
+    let fields: Vec<_> = note_type
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(ord, field)| {
+            json!({
+                "name": field.name(), "ord": ord, "sticky": false, "rtl": false,
+                "font": "Arial", "size": 20, "media": []
+            })
+        })
+        .collect();
+    let models = json!({
+        model_id.to_string(): {
+            "id": model_id, "name": note_type.name, "type": 0, "mod": now / 1000, "usn": -1,
+            "sortf": 0, "did": deck_id, "tags": [], "vers": [], "flds": fields,
+            "tmpls": [{
+                "name": "Card 1", "ord": 0, "qfmt": note_type.front_template,
+                "afmt": note_type.back_template, "did": null, "bqfmt": "", "bafmt": ""
+            }],
+            "css": note_type.css,
+            "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+            "latexPost": "\\end{document}",
+            "req": [[0, "any", [0]]]
+        }
+    });
+    let deck = |id: i64, name: &str| {
+        json!({
+            "id": id, "name": name, "mod": now / 1000, "usn": -1, "desc": "", "dyn": 0,
+            "conf": 1, "collapsed": false, "extendNew": 10, "extendRev": 50,
+            "newToday": [0, 0], "revToday": [0, 0], "lrnToday": [0, 0], "timeToday": [0, 0]
+        })
+    };
+    let decks = json!({
+        "1": deck(1, "Default"),
+        deck_id.to_string(): deck(deck_id, &options.deck_name)
+    });
+    let deck_config = json!({
+        "1": {
+            "id": 1, "name": "Default", "mod": 0, "usn": 0, "maxTaken": 60, "autoplay": true,
+            "timer": 0, "replayq": true, "dyn": false,
+            "new": {
+                "delays": [1, 10], "ints": [1, 4, 7], "initialFactor": 2500, "order": 1,
+                "perDay": 20, "bury": true, "separate": true
+            },
+            "rev": {
+                "perDay": 200, "ease4": 1.3, "fuzz": 0.05, "ivlFct": 1, "maxIvl": 36500,
+                "bury": true, "minSpace": 1
+            },
+            "lapse": {
+                "delays": [10], "mult": 0, "minInt": 1, "leechFails": 8, "leechAction": 0
+            }
+        }
+    });
+    let config = json!({
+        "curDeck": deck_id, "curModel": model_id, "nextPos": notes.len() + 1,
+        "activeDecks": [deck_id], "sortType": "noteFld", "sortBackwards": false
+    });
+
+    collection.execute(
+        "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        params![
+            now / 1000,
+            now,
+            config.to_string(),
+            models.to_string(),
+            decks.to_string(),
+            deck_config.to_string()
+        ],
+    )?;
+
+    let tags = if options.tags.is_empty() {
+        String::new()
+    } else {
+        format!(" {} ", options.tags.join(" "))
+    };
+    for (i, (guid, card)) in notes.iter().enumerate() {
+        let values: Vec<String> = note_type.fields.iter().map(|f| f.value(card)).collect();
+        let sort_field = strip_html(&values[0]);
+        let checksum = i64::from_str_radix(
+            &sha1_smol::Sha1::from(&sort_field).digest().to_string()[..8],
+            16,
+        )
+        .unwrap_or_default();
+        let id = now + i as i64;
+
+        collection.execute(
+            "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+            params![
+                id,
+                guid,
+                model_id,
+                now / 1000,
+                tags,
+                values.join("\x1f"),
+                sort_field,
+                checksum
+            ],
+        )?;
+        // New card in position order
+        collection.execute(
+            "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+            params![id, deck_id, now / 1000, i as i64 + 1],
+        )?;
+    }
+    Ok(())
+}
+
+/// A positive id derived from a name (48 bits of its SHA-1)
+fn stable_id(name: &str) -> i64 {
+    let digest = sha1_smol::Sha1::from(name).digest().bytes();
+    digest[..6]
+        .iter()
+        .fold(0i64, |id, &byte| (id << 8) | byte as i64)
+}
+
+fn strip_html(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_test_db, create_test_hierarchy};
+    use std::io::Read;
+
+    #[test]
+    fn test_export_cards_formats_and_deduplicates() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let (_, _, transcript) = create_test_hierarchy(&handler);
+        let ids = [transcript.id.unwrap()];
+        let target = CardTarget::default();
+        let dir = tempfile::tempdir().unwrap();
+
+        let tsv_path = dir.path().join("cards.tsv");
+        let options = AnkiExportOptions {
+            format: AnkiFormat::Tsv,
+            ..AnkiExportOptions::default()
+        };
+        let summary = export_cards(conn, &ids, &target, &options, &tsv_path).unwrap();
+        assert_eq!((summary.exported, summary.skipped), (1, 0));
+        let tsv = std::fs::read_to_string(&tsv_path).unwrap();
+        assert!(tsv.contains("#notetype:Japanese Subtitle Sentence"));
+        let guid = note_guid(ids[0], &target);
+        assert!(tsv.contains(&format!("{}\tHello, world!\tHello, world!\t", guid)));
+
+        // Already exported for this target
+        let summary = export_cards(conn, &ids, &target, &options, &tsv_path).unwrap();
+        assert_eq!((summary.exported, summary.skipped), (0, 1));
+
+        let apkg_path = dir.path().join("cards.apkg");
+        let options = AnkiExportOptions {
+            include_exported: true,
+            ..AnkiExportOptions::default()
+        };
+        let summary = export_cards(conn, &ids, &target, &options, &apkg_path).unwrap();
+        assert_eq!(summary.exported, 1);
+
+        let mut archive = zip::ZipArchive::new(File::open(&apkg_path).unwrap()).unwrap();
+        let mut collection_bytes = Vec::new();
+        archive
+            .by_name("collection.anki2")
+            .unwrap()
+            .read_to_end(&mut collection_bytes)
+            .unwrap();
+        let collection_path = dir.path().join("collection.anki2");
+        std::fs::write(&collection_path, collection_bytes).unwrap();
+        let collection = Connection::open(collection_path).unwrap();
+        let (note_guid_value, fields): (String, String) = collection
+            .query_row("SELECT guid, flds FROM notes", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(note_guid_value, guid);
+        assert!(fields.starts_with("Hello, world!\x1fHello, world!\x1f"));
+        let card_count: i64 = collection
+            .query_row("SELECT COUNT(*) FROM cards", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(card_count, 1);
+    }
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(strip_html("全部<b>食べ</b>た &lt;3"), "全部食べた <3");
+    }
+}
@@ -0,0 +1,273 @@
+//! Flashcard data for selected lines: the sentence with its target word and grammar pattern
+//! marked, the word's reading and dictionary form, and where the line came from
+
+use crate::db::grammar_pattern::{get_pattern_spans, PatternQuery};
+use crate::db::word::Word;
+use crate::error::Error;
+use grammar_lib::to_hiragana;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// What each card is about, usually the word or pattern that was searched for
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CardTarget {
+    pub word_id: Option<i32>,
+    pub pattern_name: Option<String>,
+}
+
+/// One card's fields. Fields that don't apply (no target word or pattern) are empty.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Card {
+    pub transcript_id: i32,
+    pub sentence: String,
+    /// The sentence as HTML with the target word and pattern in `<b>`
+    pub highlighted: String,
+    /// The target word as written in the sentence, e.g. 食べた
+    pub target: String,
+    pub base_form: String,
+    /// Reading of the base form in hiragana
+    pub reading: String,
+    /// The base form in Anki's furigana syntax, e.g. 食[た]べる
+    pub furigana: String,
+    pub grammar_pattern: String,
+    pub show: String,
+    pub episode: Option<i32>,
+    pub episode_name: String,
+    /// Milliseconds from the start of the episode
+    pub time_start: i64,
+    pub time_end: i64,
+}
+
+/// Builds a card for each of the given lines, in the given order
+pub fn get_cards(
+    conn: &Connection,
+    transcript_ids: &[i32],
+    target: &CardTarget,
+) -> Result<Vec<Card>, Error> {
+    let word = target
+        .word_id
+        .map(|word_id| Word::get_by_id(conn, word_id))
+        .transpose()?;
+    let word_spans = match &word {
+        Some(word) => word.get_spans(conn, transcript_ids)?,
+        None => HashMap::new(),
+    };
+    let mut pattern_spans: HashMap<i32, Vec<(u32, u32)>> = HashMap::new();
+    if let Some(name) = &target.pattern_name {
+        for span in get_pattern_spans(conn, PatternQuery::Name(name), 0.0, transcript_ids)? {
+            pattern_spans
+                .entry(span.transcript_id)
+                .or_default()
+                .push((span.start_char, span.end_char));
+        }
+    }
+
+    let base_form = word.as_ref().map(|w| w.word.clone()).unwrap_or_default();
+    let reading = word
+        .as_ref()
+        .and_then(|w| w.reading.as_deref())
+        .map(to_hiragana)
+        .unwrap_or_default();
+    let furigana = furigana(&base_form, &reading);
+
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.text, t.time_start, t.time_end, s.name, e.episode_number, e.name
+         FROM json_each(?1) j
+         JOIN transcripts t ON t.id = j.value
+         JOIN episodes e ON e.id = t.episode_id
+         JOIN shows s ON s.id = e.show_id
+         ORDER BY j.key",
+    )?;
+    let rows = stmt.query_map(params![serde_json::to_string(transcript_ids)?], |row| {
+        Ok(Card {
+            transcript_id: row.get(0)?,
+            sentence: row.get(1)?,
+            highlighted: String::new(),
+            target: String::new(),
+            base_form: base_form.clone(),
+            reading: reading.clone(),
+            furigana: furigana.clone(),
+            grammar_pattern: target.pattern_name.clone().unwrap_or_default(),
+            show: row.get(4)?,
+            episode: row.get(5)?,
+            episode_name: row.get(6)?,
+            time_start: row.get(2)?,
+            time_end: row.get(3)?,
+        })
+    })?;
+
+    let mut cards = Vec::new();
+    for card in rows {
+        let mut card = card?;
+        let word_spans = word_spans.get(&card.transcript_id);
+        if let Some(&(start, end)) = word_spans.and_then(|spans| spans.first()) {
+            card.target = card
+                .sentence
+                .chars()
+                .skip(start as usize)
+                .take((end - start) as usize)
+                .collect();
+        }
+
+        let mut spans: Vec<(u32, u32)> = word_spans.cloned().unwrap_or_default();
+        spans.extend(pattern_spans.get(&card.transcript_id).into_iter().flatten());
+        card.highlighted = highlight_html(&card.sentence, &spans);
+        cards.push(card);
+    }
+    Ok(cards)
+}
+
+/// Escapes `text` as HTML and wraps the given char spans in `<b>`; overlapping spans merge
+fn highlight_html(text: &str, spans: &[(u32, u32)]) -> String {
+    let mut spans: Vec<(u32, u32)> = spans.iter().copied().filter(|(s, e)| e > s).collect();
+    spans.sort_unstable();
+    let mut merged: Vec<(u32, u32)> = Vec::new();
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let mut html = String::new();
+    let mut spans = merged.into_iter().peekable();
+    for (i, c) in text.chars().enumerate() {
+        let i = i as u32;
+        if spans.peek().is_some_and(|&(start, _)| start == i) {
+            html.push_str("<b>");
+        }
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            _ => html.push(c),
+        }
+        if spans.peek().is_some_and(|&(_, end)| end == i + 1) {
+            html.push_str("</b>");
+            spans.next();
+        }
+    }
+    html
+}
+
+/// Puts the reading over the kanji part of a word in Anki's `{{furigana:}}` syntax, keeping
+/// kana the word and reading share outside the brackets (食べる, たべる → 食[た]べる)
+fn furigana(word: &str, reading: &str) -> String {
+    if reading.is_empty() || !word.chars().any(is_kanji) {
+        return word.to_string();
+    }
+
+    let word_chars: Vec<char> = word.chars().collect();
+    let reading_chars: Vec<char> = reading.chars().collect();
+    let same = |a: char, b: char| to_hiragana(&a.to_string()) == to_hiragana(&b.to_string());
+
+    let mut prefix = 0;
+    while prefix < word_chars.len().min(reading_chars.len())
+        && !is_kanji(word_chars[prefix])
+        && same(word_chars[prefix], reading_chars[prefix])
+    {
+        prefix += 1;
+    }
+    let mut suffix = 0;
+    while suffix < (word_chars.len() - prefix).min(reading_chars.len() - prefix)
+        && !is_kanji(word_chars[word_chars.len() - 1 - suffix])
+        && same(
+            word_chars[word_chars.len() - 1 - suffix],
+            reading_chars[reading_chars.len() - 1 - suffix],
+        )
+    {
+        suffix += 1;
+    }
+
+    let collect = |chars: &[char]| chars.iter().collect::<String>();
+    let mut result = collect(&word_chars[..prefix]);
+    if prefix > 0 {
+        // Anki reads the bracket as covering everything back to the previous space
+        result.push(' ');
+    }
+    result.push_str(&format!(
+        "{}[{}]{}",
+        collect(&word_chars[prefix..word_chars.len() - suffix]),
+        collect(&reading_chars[prefix..reading_chars.len() - suffix]),
+        collect(&word_chars[word_chars.len() - suffix..])
+    ));
+    result
+}
+
+fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '々')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::grammar_pattern::{get_or_create_pattern_id, GrammarPatternOccurrence};
+    use crate::db::word::WordOccurrence;
+    use crate::test_utils::{create_test_hierarchy, create_test_transcript};
+
+    #[test]
+    fn test_furigana() {
+        assert_eq!(furigana("食べる", "たべる"), "食[た]べる");
+        assert_eq!(furigana("お茶", "おちゃ"), "お 茶[ちゃ]");
+        assert_eq!(furigana("日本", "にほん"), "日本[にほん]");
+        assert_eq!(furigana("する", "する"), "する");
+        assert_eq!(furigana("食べる", ""), "食べる");
+    }
+
+    #[test]
+    fn test_highlight_html() {
+        assert_eq!(
+            highlight_html("a<b>全部食べてしまった", &[(6, 8), (7, 12), (1, 1)]),
+            "a&lt;b&gt;全部<b>食べてしまっ</b>た"
+        );
+    }
+
+    #[test]
+    fn test_get_cards() {
+        let (_file, handler) = crate::test_utils::create_test_db();
+        let conn = &handler.conn;
+        let (_show, episode, _) = create_test_hierarchy(&handler);
+        let transcript =
+            create_test_transcript(&handler, &episode, 2, 6000, 9000, "全部食べてしまった");
+        let transcript_id = transcript.id.unwrap();
+
+        let mut word = Word::new(
+            "食べる".to_string(),
+            Some("タベル".to_string()),
+            "[]".to_string(),
+        );
+        word.insert(conn).unwrap();
+        WordOccurrence::new(word.id.unwrap(), transcript_id, vec![(2, 4)])
+            .insert(conn)
+            .unwrap();
+        let pattern_id = get_or_create_pattern_id(conn, "te_shimau", "n4").unwrap();
+        GrammarPatternOccurrence::bulk_insert_optimized(
+            &[GrammarPatternOccurrence::new(
+                pattern_id,
+                transcript_id as i64,
+                10.0,
+                3,
+                8,
+            )],
+            conn,
+        )
+        .unwrap();
+
+        let target = CardTarget {
+            word_id: word.id,
+            pattern_name: Some("te_shimau".to_string()),
+        };
+        let cards = get_cards(conn, &[transcript_id], &target).unwrap();
+        assert_eq!(cards.len(), 1);
+        let card = &cards[0];
+        assert_eq!(card.target, "食べ");
+        assert_eq!(card.highlighted, "全部<b>食べてしまっ</b>た");
+        assert_eq!(card.reading, "たべる");
+        assert_eq!(card.furigana, "食[た]べる");
+        assert_eq!(card.grammar_pattern, "te_shimau");
+        assert_eq!((card.show.as_str(), card.episode), ("Test Show", Some(1)));
+        assert_eq!((card.time_start, card.time_end), (6000, 9000));
+    }
+}
@@ -0,0 +1,299 @@
+//! Cut lists for cutting the clips around selected lines out of the source videos.
+//!
+//! Each show has a video path pattern (see `video_path`) that turns an episode into its video
+//! file. CSV rows can be fed straight to `ffmpeg -ss <start> -to <end> -i <source>`; EDL is
+//! CMX 3600 for editors.
+
+use super::{csv_field, format_time};
+use crate::db::clip::{compute_clips, ClipOptions};
+use crate::error::Error;
+use regex::Regex;
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+const DEFAULT_FRAME_RATE: u32 = 24;
+
+static PLACEHOLDER_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn get_placeholder_regex() -> &'static Regex {
+    PLACEHOLDER_REGEX.get_or_init(|| {
+        Regex::new(r"\{(show|name|episode|season)(?::0(\d+))?\}").expect("Invalid regex")
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CutListFormat {
+    #[default]
+    Csv,
+    Edl,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CutListOptions {
+    pub format: CutListFormat,
+    pub clips: ClipOptions,
+    /// Frames per second for EDL timecodes
+    pub frame_rate: u32,
+}
+
+impl Default for CutListOptions {
+    fn default() -> Self {
+        Self {
+            format: CutListFormat::default(),
+            clips: ClipOptions::default(),
+            frame_rate: DEFAULT_FRAME_RATE,
+        }
+    }
+}
+
+/// One clip to cut from a video file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cut {
+    pub source: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    /// The clip's lines, joined with spaces
+    pub text: String,
+}
+
+struct EpisodeLines {
+    source: String,
+    lines: Vec<(i32, i64, i64)>,
+    texts: HashMap<i32, String>,
+}
+
+/// Computes the clips covering the given lines, in show/episode/time order. Every show involved
+/// needs a video path pattern.
+pub fn get_cuts(
+    conn: &Connection,
+    transcript_ids: &[i32],
+    options: &ClipOptions,
+) -> Result<Vec<Cut>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT e.id, s.name, s.video_path_pattern, e.name, e.episode_number, e.season,
+                t.id, t.time_start, t.time_end, t.text
+         FROM transcripts t
+         JOIN episodes e ON e.id = t.episode_id
+         JOIN shows s ON s.id = e.show_id
+         WHERE t.id IN (SELECT value FROM json_each(?1))
+         ORDER BY s.name, e.season, e.episode_number, e.id, t.line_id",
+    )?;
+    let mut rows = stmt.query(params![serde_json::to_string(transcript_ids)?])?;
+
+    let mut episodes: Vec<EpisodeLines> = Vec::new();
+    let mut last_episode_id = None;
+    while let Some(row) = rows.next()? {
+        let episode_id: i32 = row.get(0)?;
+        if last_episode_id != Some(episode_id) {
+            let show: String = row.get(1)?;
+            let pattern: Option<String> = row.get(2)?;
+            let pattern = pattern.ok_or_else(|| {
+                Error::Other(format!("No video path pattern is set for '{}'", show))
+            })?;
+            episodes.push(EpisodeLines {
+                source: video_path(
+                    &pattern,
+                    &show,
+                    &row.get::<_, String>(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ),
+                lines: Vec::new(),
+                texts: HashMap::new(),
+            });
+            last_episode_id = Some(episode_id);
+        }
+
+        let episode = episodes.last_mut().unwrap();
+        let id: i32 = row.get(6)?;
+        episode.lines.push((id, row.get(7)?, row.get(8)?));
+        episode.texts.insert(id, row.get(9)?);
+    }
+
+    let mut cuts = Vec::new();
+    for episode in episodes {
+        for clip in compute_clips(&episode.lines, options) {
+            let text = clip
+                .transcript_ids
+                .iter()
+                .map(|id| episode.texts[id].as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            cuts.push(Cut {
+                source: episode.source.clone(),
+                start_ms: clip.start_ms,
+                end_ms: clip.end_ms,
+                text,
+            });
+        }
+    }
+    Ok(cuts)
+}
+
+/// Fills in a show's video path pattern for one episode. Placeholders are `{show}`, `{name}`
+/// (the episode name, i.e. the subtitle file name), `{episode}` and `{season}`; numbers can be
+/// zero-padded, e.g. `/anime/{show}/S{season:02}E{episode:02}.mkv`.
+pub fn video_path(
+    pattern: &str,
+    show: &str,
+    episode_name: &str,
+    episode_number: Option<i32>,
+    season: Option<i32>,
+) -> String {
+    get_placeholder_regex()
+        .replace_all(pattern, |caps: &regex::Captures| {
+            let number = match &caps[1] {
+                "show" => return show.to_string(),
+                "name" => return episode_name.to_string(),
+                "episode" => episode_number,
+                _ => season,
+            };
+            let width = caps
+                .get(2)
+                .and_then(|width| width.as_str().parse().ok())
+                .unwrap_or(0);
+            number
+                .map(|number| format!("{:0width$}", number, width = width))
+                .unwrap_or_default()
+        })
+        .into_owned()
+}
+
+/// Writes the cuts in the requested format
+pub fn format_cut_list(cuts: &[Cut], options: &CutListOptions) -> String {
+    match options.format {
+        CutListFormat::Csv => format_csv(cuts),
+        CutListFormat::Edl => format_edl(cuts, options.frame_rate.max(1)),
+    }
+}
+
+fn format_csv(cuts: &[Cut]) -> String {
+    let mut csv = String::from("source,start,end,text\n");
+    for cut in cuts {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{}",
+            csv_field(&cut.source),
+            format_time(cut.start_ms),
+            format_time(cut.end_ms),
+            csv_field(&cut.text)
+        );
+    }
+    csv
+}
+
+fn format_edl(cuts: &[Cut], frame_rate: u32) -> String {
+    let mut edl = String::from("TITLE: Subtitle search clips\nFCM: NON-DROP FRAME\n\n");
+    let mut record_ms = 0;
+    for (i, cut) in cuts.iter().enumerate() {
+        let duration = cut.end_ms - cut.start_ms;
+        let _ = writeln!(
+            edl,
+            "{:03}  AX       AA/V  C        {} {} {} {}",
+            i + 1,
+            timecode(cut.start_ms, frame_rate),
+            timecode(cut.end_ms, frame_rate),
+            timecode(record_ms, frame_rate),
+            timecode(record_ms + duration, frame_rate)
+        );
+        let _ = writeln!(edl, "* FROM CLIP NAME: {}", cut.source);
+        let _ = writeln!(edl, "* COMMENT: {}\n", cut.text.replace('\n', " "));
+        record_ms += duration;
+    }
+    edl
+}
+
+/// `HH:MM:SS:FF` at the given frame rate
+fn timecode(ms: i64, frame_rate: u32) -> String {
+    let frame_rate = frame_rate as i64;
+    format!(
+        "{:02}:{:02}:{:02}:{:02}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000 * frame_rate / 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::show::Show;
+    use crate::test_utils::{
+        create_test_db, create_test_episode, create_test_show, create_test_transcript,
+    };
+
+    #[test]
+    fn test_video_path() {
+        let pattern = "/anime/{show}/S{season:02}E{episode:03} {name}.mkv";
+        assert_eq!(
+            video_path(pattern, "Show", "Pilot", Some(7), Some(1)),
+            "/anime/Show/S01E007 Pilot.mkv"
+        );
+        assert_eq!(
+            video_path("{show}/{episode}.mkv", "Movie", "Movie", None, None),
+            "Movie/.mkv"
+        );
+    }
+
+    #[test]
+    fn test_cut_list_formats() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let episode = create_test_episode(&handler, &show, "Episode 1", Some(1));
+        let mut ids = Vec::new();
+        for (line_id, start, end, text) in [
+            (1, 61_000, 62_000, "はい、そうです"),
+            (2, 62_100, 63_000, "本当?"),
+            (3, 90_000, 91_500, "じゃあね"),
+        ] {
+            let transcript = create_test_transcript(&handler, &episode, line_id, start, end, text);
+            ids.push(transcript.id.unwrap());
+        }
+        let clip_options = ClipOptions {
+            padding_ms: 250,
+            merge_gap_ms: 0,
+        };
+
+        // Shows need a video path first
+        assert!(get_cuts(conn, &ids, &clip_options).is_err());
+        Show::set_video_path_pattern(conn, show.id.unwrap(), Some("/videos/{name}.mkv")).unwrap();
+
+        let cuts = get_cuts(conn, &ids, &clip_options).unwrap();
+        assert_eq!(cuts.len(), 2);
+        assert_eq!(
+            cuts[0],
+            Cut {
+                source: "/videos/Episode 1.mkv".to_string(),
+                start_ms: 60_750,
+                end_ms: 63_250,
+                text: "はい、そうです 本当?".to_string(),
+            }
+        );
+
+        let csv = format_cut_list(&cuts, &CutListOptions::default());
+        assert_eq!(
+            csv.lines().nth(1),
+            Some("/videos/Episode 1.mkv,00:01:00.750,00:01:03.250,はい、そうです 本当?")
+        );
+
+        let edl = format_cut_list(
+            &cuts,
+            &CutListOptions {
+                format: CutListFormat::Edl,
+                frame_rate: 25,
+                ..CutListOptions::default()
+            },
+        );
+        assert!(edl.contains(
+            "001  AX       AA/V  C        00:01:00:18 00:01:03:06 00:00:00:00 00:00:02:12"
+        ));
+        assert!(edl.contains("* FROM CLIP NAME: /videos/Episode 1.mkv"));
+    }
+}
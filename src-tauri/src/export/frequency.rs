@@ -0,0 +1,320 @@
+//! Corpus frequency lists built from the imported subtitles, as CSV or as a Yomitan frequency
+//! dictionary so the ranks show up in the popup dictionary.
+
+use super::{csv_field, zip_error};
+use crate::db::word::TOKENS_SQL;
+use crate::error::Error;
+use grammar_lib::to_hiragana;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write as _;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Entries per `term_meta_bank_*.json`, as in Yomitan's own dictionaries
+const YOMITAN_BANK_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrequencyListFormat {
+    #[default]
+    Csv,
+    /// A zip with `index.json` and `term_meta_bank_*.json`, importable in Yomitan
+    Yomitan,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FrequencyListOptions {
+    /// Only count these shows; `None` for all
+    pub shows: Option<Vec<i32>>,
+    /// Only count shows of this type, e.g. "Anime"
+    pub show_type: Option<String>,
+    pub limit: Option<usize>,
+    pub format: FrequencyListFormat,
+    /// Dictionary title shown in Yomitan
+    pub title: String,
+}
+
+impl Default for FrequencyListOptions {
+    fn default() -> Self {
+        Self {
+            shows: None,
+            show_type: None,
+            limit: None,
+            format: FrequencyListFormat::default(),
+            title: "Subtitle Frequency".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FrequencyEntry {
+    /// 1 for the most frequent word
+    pub rank: usize,
+    pub word_id: i32,
+    pub lemma: String,
+    /// Reading in hiragana
+    pub reading: Option<String>,
+    /// Part of speech, e.g. 名詞-一般
+    pub pos: String,
+    /// Tokens across the selected shows
+    pub count: i64,
+    /// Episodes the word occurs in
+    pub episode_count: i64,
+    pub shows: Vec<String>,
+}
+
+/// Counts every word in the selected shows, most frequent first
+pub fn get_frequency_list(
+    conn: &Connection,
+    options: &FrequencyListOptions,
+) -> Result<Vec<FrequencyEntry>, Error> {
+    let mut filters = vec!["1".to_string()];
+    let mut params = Vec::new();
+    if let Some(shows) = &options.shows {
+        filters.push("s.id IN (SELECT value FROM json_each(?))".to_string());
+        params.push(Value::Text(serde_json::to_string(shows)?));
+    }
+    if let Some(show_type) = &options.show_type {
+        filters.push("s.show_type = ?".to_string());
+        params.push(Value::Text(show_type.clone()));
+    }
+    // -1 is no limit to SQLite
+    params.push(Value::Integer(
+        options.limit.map_or(-1, |limit| limit as i64),
+    ));
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT w.id, w.word, w.reading, w.pos, SUM({}) AS count,
+                COUNT(DISTINCT e.id) AS episode_count, json_group_array(DISTINCT s.name)
+         FROM word_occurrences wo
+         JOIN words w ON w.id = wo.word_id
+         JOIN transcripts t ON t.id = wo.transcript_id
+         JOIN episodes e ON e.id = t.episode_id
+         JOIN shows s ON s.id = e.show_id
+         WHERE {}
+         GROUP BY w.id
+         ORDER BY count DESC, episode_count DESC, w.id
+         LIMIT ?",
+        TOKENS_SQL,
+        filters.join(" AND ")
+    ))?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        Ok((
+            row.get::<_, i32>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, i64>(4)?,
+            row.get::<_, i64>(5)?,
+            row.get::<_, String>(6)?,
+        ))
+    })?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let (word_id, lemma, reading, pos, count, episode_count, shows) = row?;
+        let mut shows: Vec<String> = serde_json::from_str(&shows)?;
+        shows.sort();
+        entries.push(FrequencyEntry {
+            rank: entries.len() + 1,
+            word_id,
+            lemma,
+            reading: reading.as_deref().map(to_hiragana),
+            pos: format_pos(&pos),
+            count,
+            episode_count,
+            shows,
+        });
+    }
+    Ok(entries)
+}
+
+/// Writes the list to `path` in the format from `options`
+pub fn write_frequency_list(
+    entries: &[FrequencyEntry],
+    options: &FrequencyListOptions,
+    path: &Path,
+) -> Result<(), Error> {
+    match options.format {
+        FrequencyListFormat::Csv => std::fs::write(path, format_csv(entries))?,
+        FrequencyListFormat::Yomitan => write_yomitan(entries, &options.title, path)?,
+    }
+    Ok(())
+}
+
+fn format_csv(entries: &[FrequencyEntry]) -> String {
+    let mut csv = String::from("rank,lemma,reading,pos,count,episode_count,shows\n");
+    for entry in entries {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{}",
+            entry.rank,
+            csv_field(&entry.lemma),
+            csv_field(entry.reading.as_deref().unwrap_or_default()),
+            csv_field(&entry.pos),
+            entry.count,
+            entry.episode_count,
+            csv_field(&entry.shows.join("; "))
+        );
+    }
+    csv
+}
+
+fn write_yomitan(entries: &[FrequencyEntry], title: &str, path: &Path) -> Result<(), Error> {
+    let revision = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let index = json!({
+        "title": title,
+        "revision": format!("subtitles-{}", revision),
+        "format": 3,
+        "sequenced": false,
+        "frequencyMode": "rank-based",
+        "description": "Word frequency ranks from imported subtitles"
+    });
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("index.json", file_options)
+        .map_err(zip_error)?;
+    zip.write_all(index.to_string().as_bytes())?;
+
+    for (i, bank) in entries.chunks(YOMITAN_BANK_SIZE).enumerate() {
+        let terms: Vec<_> = bank.iter().map(yomitan_term).collect();
+        zip.start_file(format!("term_meta_bank_{}.json", i + 1), file_options)
+            .map_err(zip_error)?;
+        zip.write_all(serde_json::to_string(&terms)?.as_bytes())?;
+    }
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+/// `[term, "freq", data]`; the reading tells apart homographs like 方 (ほう/かた)
+fn yomitan_term(entry: &FrequencyEntry) -> serde_json::Value {
+    let frequency = json!({
+        "value": entry.rank,
+        "displayValue": format!("{} ({})", entry.rank, entry.count)
+    });
+    match &entry.reading {
+        Some(reading) if *reading != entry.lemma => json!([
+            entry.lemma,
+            "freq",
+            { "reading": reading, "frequency": frequency }
+        ]),
+        _ => json!([entry.lemma, "freq", frequency]),
+    }
+}
+
+/// `["名詞","一般","*"]` → 名詞-一般
+fn format_pos(pos: &str) -> String {
+    let parts: Vec<String> = serde_json::from_str(pos).unwrap_or_default();
+    parts
+        .iter()
+        .filter(|part| !part.is_empty() && part.as_str() != "*")
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::word::{Word, WordOccurrence};
+    use crate::test_utils::{
+        create_test_db, create_test_episode, create_test_show, create_test_transcript,
+    };
+    use std::io::Read;
+
+    #[test]
+    fn test_frequency_list() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let anime = create_test_show(&handler, "Anime Show", "Anime");
+        let drama = create_test_show(&handler, "Drama Show", "Drama");
+        let anime_episode = create_test_episode(&handler, &anime, "Episode 1", Some(1));
+        let drama_episode = create_test_episode(&handler, &drama, "Episode 1", Some(1));
+
+        let mut eat = Word::new(
+            "食べる".to_string(),
+            Some("タベル".to_string()),
+            r#"["動詞","自立","*"]"#.to_string(),
+        );
+        eat.insert(conn).unwrap();
+        let mut yes = Word::new("はい".to_string(), None, r#"["感動詞"]"#.to_string());
+        yes.insert(conn).unwrap();
+
+        // 食べる three times in anime and once in drama; はい twice in drama
+        for (episode, line_id, occurrences) in [
+            (&anime_episode, 1, vec![(eat.id, vec![(0, 2), (3, 5)])]),
+            (&anime_episode, 2, vec![(eat.id, vec![(0, 2)])]),
+            (
+                &drama_episode,
+                1,
+                vec![(eat.id, vec![(0, 2)]), (yes.id, vec![(3, 5), (6, 8)])],
+            ),
+        ] {
+            let transcript = create_test_transcript(&handler, episode, line_id, 0, 1000, "");
+            for (word_id, spans) in occurrences {
+                WordOccurrence::new(word_id.unwrap(), transcript.id.unwrap(), spans)
+                    .insert(conn)
+                    .unwrap();
+            }
+        }
+
+        let entries = get_frequency_list(conn, &FrequencyListOptions::default()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            format_csv(&entries),
+            "rank,lemma,reading,pos,count,episode_count,shows\n\
+             1,食べる,たべる,動詞-自立,4,2,Anime Show; Drama Show\n\
+             2,はい,,感動詞,2,1,Drama Show\n"
+        );
+
+        let options = FrequencyListOptions {
+            show_type: Some("Drama".to_string()),
+            ..FrequencyListOptions::default()
+        };
+        let entries = get_frequency_list(conn, &options).unwrap();
+        let ranked: Vec<(&str, i64)> = entries
+            .iter()
+            .map(|entry| (entry.lemma.as_str(), entry.count))
+            .collect();
+        assert_eq!(ranked, vec![("はい", 2), ("食べる", 1)]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("frequency.zip");
+        let options = FrequencyListOptions {
+            format: FrequencyListFormat::Yomitan,
+            ..options
+        };
+        write_frequency_list(&entries, &options, &path).unwrap();
+        let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut bank = String::new();
+        archive
+            .by_name("term_meta_bank_1.json")
+            .unwrap()
+            .read_to_string(&mut bank)
+            .unwrap();
+        let bank: serde_json::Value = serde_json::from_str(&bank).unwrap();
+        assert_eq!(
+            bank,
+            json!([
+                ["はい", "freq", {"value": 1, "displayValue": "1 (2)"}],
+                ["食べる", "freq", {
+                    "reading": "たべる",
+                    "frequency": {"value": 2, "displayValue": "2 (1)"}
+                }]
+            ])
+        );
+        assert!(archive.by_name("index.json").is_ok());
+    }
+}
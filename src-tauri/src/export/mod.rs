@@ -0,0 +1,30 @@
+pub mod anki;
+pub mod cards;
+pub mod cut_list;
+pub mod frequency;
+pub mod study_list;
+
+use crate::error::Error;
+
+/// `HH:MM:SS.mmm`, as accepted by ffmpeg's `-ss` and `-to`
+fn format_time(ms: i64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn zip_error(err: zip::result::ZipError) -> Error {
+    Error::Other(format!("Failed to write package: {}", err))
+}
@@ -0,0 +1,258 @@
+//! Pre-study vocabulary lists: the words worth learning before watching an episode.
+//!
+//! Words are ranked TF-IDF style, by how often they occur in the episode times how few
+//! episodes of the whole library use them, so words that are both central to the episode and
+//! unlikely to be picked up elsewhere come first.
+
+use super::csv_field;
+use crate::db::user_word::{is_known_sql, USER_WORD_JOIN};
+use crate::db::word::{EXCLUDED_POS_SQL, TOKENS_SQL};
+use crate::error::Error;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+const DEFAULT_LIMIT: usize = 30;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StudyListFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StudyListOptions {
+    pub format: StudyListFormat,
+    pub limit: usize,
+    /// Leave out words marked known or ignored
+    pub exclude_known: bool,
+    /// Leave out words easier than this JLPT level (1-5, e.g. 4 drops N5 words). Words without
+    /// a level are kept.
+    pub min_jlpt_level: Option<u8>,
+}
+
+impl Default for StudyListOptions {
+    fn default() -> Self {
+        Self {
+            format: StudyListFormat::default(),
+            limit: DEFAULT_LIMIT,
+            exclude_known: false,
+            min_jlpt_level: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StudyWord {
+    pub word_id: i32,
+    pub word: String,
+    pub reading: Option<String>,
+    /// JLPT level (1-5), if the word has one
+    pub jlpt_level: Option<i32>,
+    /// Tokens of the word in the episode, like coverage counts them
+    pub count: i64,
+    /// Episodes of the library the word occurs in
+    pub episode_count: i64,
+    pub score: f64,
+    /// The first line of the episode using the word
+    pub example: String,
+    pub example_transcript_id: i32,
+}
+
+/// Ranks an episode's words for studying before watching it, best first
+pub fn get_study_list(
+    conn: &Connection,
+    episode_id: i32,
+    options: &StudyListOptions,
+) -> Result<Vec<StudyWord>, Error> {
+    if let Some(level) = options.min_jlpt_level {
+        if !(1..=5).contains(&level) {
+            return Err(Error::Other("Invalid JLPT level. Use 1-5.".to_string()));
+        }
+    }
+    let episode_total: i64 = conn.query_row(
+        "SELECT COUNT(DISTINCT t.episode_id)
+         FROM word_occurrences wo
+         JOIN transcripts t ON t.id = wo.transcript_id",
+        [],
+        |row| row.get(0),
+    )?;
+
+    let mut filters = vec![format!("NOT {}", EXCLUDED_POS_SQL)];
+    if options.exclude_known {
+        filters.push(format!("NOT {}", is_known_sql(false)));
+    }
+    if options.min_jlpt_level.is_some() {
+        filters.push("(jl.level IS NULL OR jl.level <= ?2)".to_string());
+    }
+
+    // SQLite takes bare columns (t.id, t.text) from the row holding MIN(t.line_id), i.e. the
+    // word's first line in the episode
+    let mut stmt = conn.prepare(&format!(
+        "SELECT w.id, w.word, w.reading, jl.level, SUM({}), MIN(t.line_id), t.id, t.text,
+                (SELECT COUNT(DISTINCT dt.episode_id)
+                 FROM word_occurrences dwo
+                 JOIN transcripts dt ON dt.id = dwo.transcript_id
+                 WHERE dwo.word_id = w.id)
+         FROM transcripts t
+         JOIN word_occurrences wo ON wo.transcript_id = t.id
+         JOIN words w ON w.id = wo.word_id
+         LEFT JOIN jlpt_levels jl ON jl.word = w.word
+         {}
+         WHERE t.episode_id = ?1 AND {}
+         GROUP BY w.id",
+        TOKENS_SQL,
+        USER_WORD_JOIN,
+        filters.join(" AND ")
+    ))?;
+    let map_row = |row: &rusqlite::Row| {
+        let count: i64 = row.get(4)?;
+        let episode_count: i64 = row.get(8)?;
+        Ok(StudyWord {
+            word_id: row.get(0)?,
+            word: row.get(1)?,
+            reading: row.get(2)?,
+            jlpt_level: row.get(3)?,
+            count,
+            episode_count,
+            score: count as f64 * (1.0 + episode_total as f64 / episode_count as f64).ln(),
+            example: row.get(7)?,
+            example_transcript_id: row.get(6)?,
+        })
+    };
+    let rows = match options.min_jlpt_level {
+        Some(level) => stmt.query_map(params![episode_id, level], map_row)?,
+        None => stmt.query_map(params![episode_id], map_row)?,
+    };
+    let mut words = rows.collect::<Result<Vec<_>, _>>()?;
+
+    words.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.count.cmp(&a.count))
+            .then(a.word_id.cmp(&b.word_id))
+    });
+    words.truncate(options.limit);
+    Ok(words)
+}
+
+/// Writes the list as JSON or CSV
+pub fn format_study_list(words: &[StudyWord], format: StudyListFormat) -> Result<String, Error> {
+    match format {
+        StudyListFormat::Json => Ok(serde_json::to_string_pretty(words)?),
+        StudyListFormat::Csv => {
+            let mut csv = String::from("word,reading,jlpt_level,count,episode_count,example\n");
+            for word in words {
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{}",
+                    csv_field(&word.word),
+                    csv_field(word.reading.as_deref().unwrap_or_default()),
+                    word.jlpt_level
+                        .map(|level| format!("N{}", level))
+                        .unwrap_or_default(),
+                    word.count,
+                    word.episode_count,
+                    csv_field(&word.example)
+                );
+            }
+            Ok(csv)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::user_word::{set_word_status, WordStatus};
+    use crate::db::word::{Word, WordOccurrence};
+    use crate::test_utils::{
+        create_test_db, create_test_episode, create_test_show, create_test_transcript,
+    };
+
+    #[test]
+    fn test_study_list_ranking_and_filters() {
+        let (_file, handler) = create_test_db();
+        let conn = &handler.conn;
+        let show = create_test_show(&handler, "Test Show", "Anime");
+        let episode = create_test_episode(&handler, &show, "Episode 1", Some(1));
+        let other = create_test_episode(&handler, &show, "Episode 2", Some(2));
+
+        let mut ids = Vec::new();
+        for (word, pos) in [
+            ("する", r#"["動詞","自立"]"#),
+            ("魔法", r#"["名詞","一般"]"#),
+            ("田中", r#"["名詞","固有名詞"]"#),
+            ("剣", r#"["名詞","一般"]"#),
+        ] {
+            let mut word = Word::new(word.to_string(), None, pos.to_string());
+            word.insert(conn).unwrap();
+            ids.push(word.id.unwrap());
+        }
+        conn.execute(
+            "INSERT INTO jlpt_levels (word, level) VALUES ('する', 5)",
+            [],
+        )
+        .unwrap();
+
+        // する and 魔法 twice each in episode 1, する also in episode 2; 田中 once and 剣 once
+        // in a line that says it twice
+        for (episode, line_id, text, words) in [
+            (
+                &episode,
+                1,
+                "魔法をする",
+                vec![(ids[0], vec![]), (ids[1], vec![])],
+            ),
+            (
+                &episode,
+                2,
+                "田中、魔法だ",
+                vec![(ids[1], vec![]), (ids[2], vec![])],
+            ),
+            (
+                &episode,
+                3,
+                "剣を剣でする",
+                vec![(ids[0], vec![(5, 7)]), (ids[3], vec![(0, 1), (2, 3)])],
+            ),
+            (&other, 1, "する", vec![(ids[0], vec![])]),
+        ] {
+            let transcript = create_test_transcript(&handler, episode, line_id, 0, 1000, text);
+            for (word_id, spans) in words {
+                WordOccurrence::new(word_id, transcript.id.unwrap(), spans)
+                    .insert(conn)
+                    .unwrap();
+            }
+        }
+
+        let episode_id = episode.id.unwrap();
+        let words = get_study_list(conn, episode_id, &StudyListOptions::default()).unwrap();
+        let ranked: Vec<&str> = words.iter().map(|word| word.word.as_str()).collect();
+        // Proper nouns are left out; する is as frequent as 魔法 and 剣 but common to both
+        // episodes
+        assert_eq!(ranked, vec!["魔法", "剣", "する"]);
+        assert_eq!(words[0].example, "魔法をする");
+        assert_eq!((words[1].count, words[1].episode_count), (2, 1));
+        assert_eq!((words[2].count, words[2].episode_count), (2, 2));
+
+        set_word_status(conn, "魔法", None, Some(WordStatus::Known)).unwrap();
+        let options = StudyListOptions {
+            exclude_known: true,
+            min_jlpt_level: Some(4),
+            ..StudyListOptions::default()
+        };
+        let words = get_study_list(conn, episode_id, &options).unwrap();
+        let ranked: Vec<&str> = words.iter().map(|word| word.word.as_str()).collect();
+        assert_eq!(ranked, vec!["剣"]);
+
+        let csv = format_study_list(&words, StudyListFormat::Csv).unwrap();
+        assert_eq!(
+            csv,
+            "word,reading,jlpt_level,count,episode_count,example\n剣,,,2,1,剣を剣でする\n"
+        );
+    }
+}
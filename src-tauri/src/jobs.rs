@@ -0,0 +1,423 @@
+//! Long-running work (subtitle import, analysis) on a worker thread with its own database
+//! connection, so searches keep working from the app's connection in the meantime.
+//!
+//! Jobs report through `JobEvent`s and are cancelled cooperatively: they call
+//! `JobContext::check_cancelled` between batches and stop with `Error::Cancelled`, before
+//! committing anything, so a cancelled job leaves the database as it was.
+
+use crate::error::Error;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+pub type JobId = u64;
+
+/// Receives every event of a job, e.g. to forward it to the UI
+pub type EmitFn = Arc<dyn Fn(JobEvent) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Import,
+    Analysis,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobUpdate {
+    /// `current` of `total` steps of a phase are done, e.g. batch 3 of 40
+    Progress {
+        phase: String,
+        current: usize,
+        total: usize,
+        /// Estimated seconds left in the phase, once a step is done
+        eta_secs: Option<u64>,
+    },
+    /// A file that couldn't be read; the job goes on without it
+    FileError {
+        path: String,
+        error: String,
+    },
+    Finished {
+        status: JobStatus,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobEvent {
+    pub job_id: JobId,
+    pub kind: JobKind,
+    #[serde(flatten)]
+    pub update: JobUpdate,
+}
+
+/// What a running job reports through and checks for cancellation
+pub struct JobContext {
+    id: JobId,
+    kind: JobKind,
+    cancelled: Arc<AtomicBool>,
+    emit: EmitFn,
+    /// Current phase and when it started, for the ETA
+    phase: RefCell<Option<(String, Instant)>>,
+}
+
+impl JobContext {
+    pub fn new(id: JobId, kind: JobKind, cancelled: Arc<AtomicBool>, emit: EmitFn) -> Self {
+        JobContext {
+            id,
+            kind,
+            cancelled,
+            emit,
+            phase: RefCell::new(None),
+        }
+    }
+
+    /// Reports `current` of `total` steps of `phase` done
+    pub fn progress(&self, phase: &str, current: usize, total: usize) {
+        let mut state = self.phase.borrow_mut();
+        if state.as_ref().is_none_or(|(name, _)| name != phase) {
+            *state = Some((phase.to_string(), Instant::now()));
+        }
+        let started = state.as_ref().map(|(_, started)| *started).unwrap();
+        let eta_secs = (current > 0 && current <= total).then(|| {
+            let elapsed = started.elapsed().as_secs_f64();
+            (elapsed / current as f64 * (total - current) as f64).round() as u64
+        });
+        self.send(JobUpdate::Progress {
+            phase: phase.to_string(),
+            current,
+            total,
+            eta_secs,
+        });
+    }
+
+    pub fn file_error(&self, path: &str, error: &str) {
+        self.send(JobUpdate::FileError {
+            path: path.to_string(),
+            error: error.to_string(),
+        });
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// `Err(Error::Cancelled)` once the job was cancelled; call between batches
+    pub fn check_cancelled(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn send(&self, update: JobUpdate) {
+        (self.emit)(JobEvent {
+            job_id: self.id,
+            kind: self.kind,
+            update,
+        });
+    }
+}
+
+/// Running jobs. One runs at a time, as jobs write to the database in long transactions.
+#[derive(Default)]
+pub struct Jobs {
+    next_id: AtomicU64,
+    running: Arc<Mutex<HashMap<JobId, Arc<AtomicBool>>>>,
+}
+
+impl Jobs {
+    /// Runs `work` on a worker thread. Its result becomes the `Finished` event's message.
+    pub fn spawn<F>(&self, kind: JobKind, emit: EmitFn, work: F) -> Result<JobId, Error>
+    where
+        F: FnOnce(&JobContext) -> Result<String, Error> + Send + 'static,
+    {
+        let mut running = self.running.lock().unwrap();
+        if !running.is_empty() {
+            return Err(Error::Other(
+                "Another job is running. Wait for it to finish or cancel it.".to_string(),
+            ));
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        running.insert(id, cancelled.clone());
+
+        let running = self.running.clone();
+        std::thread::spawn(move || {
+            let job = JobContext::new(id, kind, cancelled, emit);
+            // A panicking job still finishes, or it would block every later one
+            let result = panic::catch_unwind(AssertUnwindSafe(|| work(&job)))
+                .unwrap_or_else(|payload| Err(Error::Other(panic_message(payload.as_ref()))));
+            let (status, message) = match result {
+                Ok(message) => (JobStatus::Completed, message),
+                Err(Error::Cancelled) => (JobStatus::Cancelled, "Cancelled".to_string()),
+                Err(err) => (JobStatus::Failed, err.to_string()),
+            };
+            running.lock().unwrap().remove(&id);
+            job.send(JobUpdate::Finished { status, message });
+        });
+        Ok(id)
+    }
+
+    /// Asks a job to stop at its next batch boundary. False if it isn't running.
+    pub fn cancel(&self, id: JobId) -> bool {
+        match self.running.lock().unwrap().get(&id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn running(&self) -> Vec<JobId> {
+        self.running.lock().unwrap().keys().copied().collect()
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error");
+    format!("Job failed unexpectedly: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DbHandler;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn channel_emit() -> (EmitFn, mpsc::Receiver<JobEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        (
+            Arc::new(move |event| sender.lock().unwrap().send(event).unwrap()),
+            receiver,
+        )
+    }
+
+    fn finished(receiver: &mpsc::Receiver<JobEvent>) -> (Vec<JobUpdate>, JobUpdate) {
+        let mut updates = Vec::new();
+        loop {
+            let event = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+            if let JobUpdate::Finished { .. } = event.update {
+                return (updates, event.update);
+            }
+            updates.push(event.update);
+        }
+    }
+
+    #[test]
+    fn test_job_reports_progress_and_finishes() {
+        let jobs = Jobs::default();
+        let (emit, receiver) = channel_emit();
+        let id = jobs
+            .spawn(JobKind::Import, emit, |job| {
+                job.progress("Reading files", 0, 2);
+                job.file_error("a.srt", "Malformed subtitle");
+                job.progress("Reading files", 2, 2);
+                Ok("Imported 1 file".to_string())
+            })
+            .unwrap();
+        assert_eq!(id, 1);
+
+        let (updates, end) = finished(&receiver);
+        assert_eq!(updates.len(), 3);
+        assert!(matches!(
+            &updates[2],
+            JobUpdate::Progress {
+                current: 2,
+                total: 2,
+                eta_secs: Some(0),
+                ..
+            }
+        ));
+        assert_eq!(
+            end,
+            JobUpdate::Finished {
+                status: JobStatus::Completed,
+                message: "Imported 1 file".to_string()
+            }
+        );
+        assert!(jobs.running().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_stops_job_between_batches() {
+        let jobs = Jobs::default();
+        let (emit, receiver) = channel_emit();
+        let (started, wait_started) = mpsc::channel();
+        let id = jobs
+            .spawn(JobKind::Analysis, emit.clone(), move |job| {
+                started.send(()).unwrap();
+                for batch in 1.. {
+                    job.check_cancelled()?;
+                    job.progress("Analyzing lines", batch, 1000);
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                unreachable!()
+            })
+            .unwrap();
+        wait_started.recv().unwrap();
+
+        // One job at a time
+        assert!(jobs
+            .spawn(JobKind::Import, emit, |_| Ok(String::new()))
+            .is_err());
+
+        assert!(jobs.cancel(id));
+        let (_, end) = finished(&receiver);
+        assert!(matches!(
+            end,
+            JobUpdate::Finished {
+                status: JobStatus::Cancelled,
+                ..
+            }
+        ));
+        assert!(!jobs.cancel(id));
+    }
+
+    #[test]
+    fn test_panicking_job_fails_and_frees_its_slot() {
+        let jobs = Jobs::default();
+        let (emit, receiver) = channel_emit();
+        jobs.spawn(JobKind::Analysis, emit.clone(), |_| {
+            panic!("index out of bounds")
+        })
+        .unwrap();
+        let (_, end) = finished(&receiver);
+        assert_eq!(
+            end,
+            JobUpdate::Finished {
+                status: JobStatus::Failed,
+                message: "Error: Job failed unexpectedly: index out of bounds".to_string()
+            }
+        );
+        assert!(jobs.running().is_empty());
+
+        jobs.spawn(JobKind::Import, emit, |_| Ok("Done".to_string()))
+            .unwrap();
+        let (_, end) = finished(&receiver);
+        assert!(matches!(
+            end,
+            JobUpdate::Finished {
+                status: JobStatus::Completed,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_cancelled_import_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let show_dir = dir.path().join("Test Show");
+        std::fs::create_dir(&show_dir).unwrap();
+        std::fs::write(
+            show_dir.join("Test Show 01.srt"),
+            "1\n00:00:01,000 --> 00:00:02,000\n食べる\n\n2\n00:00:03,000 --> 00:00:04,000\n見る\n",
+        )
+        .unwrap();
+        std::fs::write(show_dir.join("Test Show 02.srt"), "not a subtitle").unwrap();
+
+        let (_file, mut handler) = crate::test_utils::create_test_db();
+        let (emit, receiver) = channel_emit();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let job = JobContext::new(1, JobKind::Import, cancelled.clone(), emit);
+
+        let shows = crate::subtitle_importer::process_srt_directory(dir.path(), &job).unwrap();
+        assert_eq!(shows.len(), 1);
+        assert_eq!(shows[0].episodes.len(), 1);
+        let file_errors: Vec<String> = receiver
+            .try_iter()
+            .filter_map(|event| match event.update {
+                JobUpdate::FileError { path, .. } => Some(path),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(file_errors.len(), 1);
+        assert!(file_errors[0].ends_with("Test Show 02.srt"));
+
+        // Cancelled after the shows and episodes went in, before any lines
+        cancelled.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            handler.import_show_entries(&shows, &job),
+            Err(Error::Cancelled)
+        ));
+        let count = |handler: &DbHandler, table: &str| -> i64 {
+            handler
+                .conn
+                .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        };
+        assert_eq!(count(&handler, "shows"), 0);
+        assert_eq!(count(&handler, "episodes"), 0);
+
+        // Importing twice reuses the show and episode and inserts no lines
+        let job = JobContext::new(2, JobKind::Import, Arc::default(), Arc::new(|_| {}));
+        assert_eq!(handler.import_show_entries(&shows, &job).unwrap(), 2);
+        assert_eq!(handler.import_show_entries(&shows, &job).unwrap(), 0);
+        assert_eq!(count(&handler, "shows"), 1);
+        assert_eq!(count(&handler, "episodes"), 1);
+        assert_eq!(count(&handler, "transcripts"), 2);
+    }
+
+    #[test]
+    fn test_import_keeps_seasons_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let show_dir = dir.path().join("Test Show");
+        std::fs::create_dir(&show_dir).unwrap();
+        for (file, text) in [
+            ("Test Show.S01E05.srt", "食べる"),
+            ("Test Show.S02E05.srt", "見る"),
+        ] {
+            std::fs::write(
+                show_dir.join(file),
+                format!("1\n00:00:01,000 --> 00:00:02,000\n{}\n", text),
+            )
+            .unwrap();
+        }
+
+        let (_file, mut handler) = crate::test_utils::create_test_db();
+        let job = JobContext::new(1, JobKind::Import, Arc::default(), Arc::new(|_| {}));
+        let shows = crate::subtitle_importer::process_srt_directory(dir.path(), &job).unwrap();
+        assert_eq!(handler.import_show_entries(&shows, &job).unwrap(), 2);
+        assert_eq!(handler.import_show_entries(&shows, &job).unwrap(), 0);
+
+        let mut stmt = handler
+            .conn
+            .prepare(
+                "SELECT e.season, e.episode_number, t.text FROM episodes e
+                 JOIN transcripts t ON t.episode_id = e.id ORDER BY e.season",
+            )
+            .unwrap();
+        let episodes: Vec<(i32, i32, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            episodes,
+            vec![(1, 5, "食べる".to_string()), (2, 5, "見る".to_string())]
+        );
+    }
+}
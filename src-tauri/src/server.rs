@@ -0,0 +1,389 @@
+//! Local HTTP/JSON API over a corpus, for tools outside the app such as browser extensions and
+//! media player plugins. Bound to 127.0.0.1 only.
+//!
+//! Each operation is `POST /<command>` with the arguments of the app command of the same name
+//! as a JSON object (snake_case, optional ones may be left out), answered with the same result
+//! type as JSON. `GET` works for operations whose arguments are all optional. Errors are
+//! `{"error": "..."}` with status 400 for bad requests, 403 for requests not addressed to
+//! 127.0.0.1 or localhost or from an origin that isn't allowed, 404 for unknown operations, 405
+//! for methods other than GET and POST, and 500 otherwise.
+//!
+//! Browsers send an `Origin` with their requests; only the allowed origins, e.g. a browser
+//! extension's `chrome-extension://<id>`, get through and may read the responses, so websites
+//! the user visits can't query the corpus. Clients outside a browser send no `Origin`. The
+//! `Host` check keeps out pages that rebind their own domain to 127.0.0.1.
+
+use crate::analysis::query_tokenizer::QueryTokenizer;
+use crate::db::coverage::CoverageOptions;
+use crate::db::grammar_pattern::PatternQuery;
+use crate::db::search::SearchOptions;
+use crate::db::DbHandler;
+use crate::error::Error;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Port used when none is given
+pub const DEFAULT_PORT: u16 = 6080;
+
+/// Port of the server's own kagome, apart from the app's so both can run at once
+const TOKENIZER_PORT: u16 = 6064;
+
+/// Requests handled at once, each worker with its own connection
+const WORKERS: usize = 4;
+
+/// Larger request bodies are refused
+const MAX_BODY_BYTES: u64 = 1 << 20;
+
+/// Sent along with `Access-Control-Allow-Origin` to allowed origins, and all their CORS
+/// preflights get
+const CORS_HEADERS: &[(&str, &str)] = &[
+    ("Access-Control-Allow-Methods", "GET, POST, OPTIONS"),
+    ("Access-Control-Allow-Headers", "Content-Type"),
+    ("Vary", "Origin"),
+];
+
+#[derive(Debug, Deserialize)]
+struct WordSearchRequest {
+    word: String,
+    word_id: Option<i32>,
+    /// All shows if left out
+    enabled_show_ids: Option<Vec<i32>>,
+    options: Option<SearchOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PatternSearchRequest {
+    pattern_name: Option<String>,
+    jlpt_level: Option<String>,
+    enabled_show_ids: Option<Vec<i32>>,
+    min_confidence: Option<f64>,
+    options: Option<SearchOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PatternStatsRequest {
+    pattern_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpisodeCoverageRequest {
+    episode_id: i32,
+    options: Option<CoverageOptions>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RankEpisodesRequest {
+    shows: Option<Vec<i32>>,
+    options: Option<CoverageOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnalyzeRequest {
+    text: String,
+}
+
+/// A failed request, answered with `status` and the message as `{"error": ...}`
+#[derive(Debug)]
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        ApiError {
+            status: 400,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        ApiError {
+            status: 500,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// A running server. Requests are handled on worker threads until `stop`.
+pub struct ApiServer {
+    server: Arc<Server>,
+    stopped: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ApiServer {
+    /// Serves the database at `db_path` on `127.0.0.1:port`; port 0 picks a free port. Browser
+    /// requests are only answered for `allowed_origins`. The database must already be migrated,
+    /// e.g. by `DbHandler::create_tables`.
+    pub fn start(db_path: &str, port: u16, allowed_origins: Vec<String>) -> Result<Self, Error> {
+        let server = Server::http(("127.0.0.1", port))
+            .map_err(|err| Error::Other(format!("Could not listen on port {}: {}", port, err)))?;
+        let server = Arc::new(server);
+        let port = server
+            .server_addr()
+            .to_ip()
+            .map_or(port, |addr| addr.port());
+        let stopped = Arc::new(AtomicBool::new(false));
+        let tokenizer = Arc::new(QueryTokenizer::with_port(TOKENIZER_PORT));
+        let allowed_origins = Arc::new(allowed_origins);
+
+        let mut workers = Vec::with_capacity(WORKERS);
+        for _ in 0..WORKERS {
+            // Opened here so a bad path fails `start` rather than every request
+            let mut db = DbHandler::new(db_path)?;
+            let server = server.clone();
+            let stopped = stopped.clone();
+            let tokenizer = tokenizer.clone();
+            let allowed_origins = allowed_origins.clone();
+            workers.push(std::thread::spawn(move || loop {
+                match server.recv() {
+                    Ok(request) => respond(request, port, &allowed_origins, &mut db, &tokenizer),
+                    Err(_) if stopped.load(Ordering::Relaxed) => break,
+                    Err(err) => eprintln!("Failed to receive request: {}", err),
+                }
+            }));
+        }
+
+        Ok(ApiServer {
+            server,
+            stopped,
+            workers,
+        })
+    }
+
+    /// Port the server listens on
+    pub fn port(&self) -> u16 {
+        self.server
+            .server_addr()
+            .to_ip()
+            .map_or(0, |addr| addr.port())
+    }
+
+    /// Blocks until the server stops, i.e. for as long as the process runs
+    pub fn wait(self) {
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+    }
+
+    /// Stops accepting requests and waits for the ones being handled
+    pub fn stop(self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        for _ in &self.workers {
+            self.server.unblock();
+        }
+        self.wait();
+    }
+}
+
+fn respond(
+    mut request: Request,
+    port: u16,
+    allowed_origins: &[String],
+    db: &mut DbHandler,
+    tokenizer: &QueryTokenizer,
+) {
+    let checked = check_host(&request, port).and_then(|()| check_origin(&request, allowed_origins));
+    let (origin, result) = match checked {
+        Err(err) => (None, Err(err)),
+        // CORS preflight; the headers below are the answer
+        Ok(origin) if *request.method() == Method::Options => (origin, Ok(None)),
+        Ok(origin) => {
+            let result = read_body(&mut request).and_then(|body| {
+                let operation = request.url().split('?').next().unwrap_or("");
+                let operation = operation.trim_start_matches('/').to_string();
+                handle(request.method(), &operation, &body, db, tokenizer).map(Some)
+            });
+            (origin, result)
+        }
+    };
+
+    let response = match result {
+        Ok(Some(value)) => json_response(200, &value),
+        Ok(None) => Response::from_string("").with_status_code(204),
+        Err(err) => json_response(err.status, &json!({ "error": err.message })),
+    };
+    let response = match origin {
+        Some(origin) => CORS_HEADERS.iter().fold(
+            response.with_header(header("Access-Control-Allow-Origin", &origin)),
+            |response, (field, value)| response.with_header(header(field, value)),
+        ),
+        None => response,
+    };
+    if let Err(err) = request.respond(response) {
+        eprintln!("Failed to send response: {}", err);
+    }
+}
+
+fn json_response(status: u16, body: &Value) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).unwrap()
+}
+
+/// Refuses requests whose `Host` isn't this server, e.g. from a page whose domain was rebound
+/// to 127.0.0.1 after it loaded
+fn check_host(request: &Request, port: u16) -> Result<(), ApiError> {
+    let host = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Host"))
+        .map(|header| header.value.as_str());
+    let allowed = [format!("127.0.0.1:{}", port), format!("localhost:{}", port)];
+    match host {
+        Some(host) if allowed.iter().any(|name| name.eq_ignore_ascii_case(host)) => Ok(()),
+        _ => Err(ApiError {
+            status: 403,
+            message: format!("Requests must be addressed to 127.0.0.1:{}", port),
+        }),
+    }
+}
+
+/// The request's `Origin` if it's allowed, `None` for requests from outside a browser
+fn check_origin(request: &Request, allowed_origins: &[String]) -> Result<Option<String>, ApiError> {
+    let origin = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Origin"))
+        .map(|header| header.value.to_string());
+    match origin {
+        None => Ok(None),
+        Some(origin) if allowed_origins.contains(&origin) => Ok(Some(origin)),
+        Some(origin) => Err(ApiError {
+            status: 403,
+            message: format!(
+                "Origin {} is not allowed; start the server with --allow-origin {}",
+                origin, origin
+            ),
+        }),
+    }
+}
+
+fn read_body(request: &mut Request) -> Result<String, ApiError> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_BYTES + 1)
+        .read_to_string(&mut body)
+        .map_err(|err| ApiError::bad_request(format!("Could not read request body: {}", err)))?;
+    if body.len() as u64 > MAX_BODY_BYTES {
+        return Err(ApiError::bad_request("Request body too large"));
+    }
+    Ok(body)
+}
+
+fn handle(
+    method: &Method,
+    operation: &str,
+    body: &str,
+    db: &mut DbHandler,
+    tokenizer: &QueryTokenizer,
+) -> Result<Value, ApiError> {
+    if !matches!(method, Method::Get | Method::Post) {
+        return Err(ApiError {
+            status: 405,
+            message: format!("Method {} not allowed", method),
+        });
+    }
+
+    match operation {
+        "get_all_shows" => to_json(db.get_show_id_name_pairs()?),
+        "search_word_with_context" => {
+            let request: WordSearchRequest = parse(body)?;
+            let shows = shows_or_all(db, request.enabled_show_ids)?;
+            let options = request.options.unwrap_or_default();
+            let result = match request.word_id {
+                Some(word_id) => {
+                    db.search_word_id_with_context(&request.word, word_id, &shows, &options)?
+                }
+                None => db.search_word_with_context(&request.word, &shows, &options, |text| {
+                    // Without a tokenizer, exact and kana matches still work
+                    tokenizer.lemmatize(text).or_else(|err| {
+                        eprintln!("Could not lemmatize search input: {}", err);
+                        Ok(Vec::new())
+                    })
+                })?,
+            };
+            to_json(result)
+        }
+        "search_grammar_pattern_with_context" => {
+            let request: PatternSearchRequest = parse(body)?;
+            let query = match (&request.pattern_name, &request.jlpt_level) {
+                (Some(name), _) => PatternQuery::Name(name),
+                (None, Some(level)) => PatternQuery::Level(level),
+                (None, None) => {
+                    return Err(ApiError::bad_request(
+                        "A pattern name or JLPT level is required",
+                    ))
+                }
+            };
+            let shows = shows_or_all(db, request.enabled_show_ids)?;
+            to_json(db.search_grammar_pattern_with_context(
+                query,
+                &shows,
+                request.min_confidence.unwrap_or(0.0),
+                &request.options.unwrap_or_default(),
+            )?)
+        }
+        "get_grammar_pattern_stats" => {
+            let request: PatternStatsRequest = parse(body)?;
+            to_json(db.get_pattern_stats(&request.pattern_name)?)
+        }
+        "get_episode_coverage" => {
+            let request: EpisodeCoverageRequest = parse(body)?;
+            to_json(
+                db.get_episode_coverage(request.episode_id, &request.options.unwrap_or_default())?,
+            )
+        }
+        "rank_episodes_by_coverage" => {
+            let request: RankEpisodesRequest = parse(body)?;
+            let shows = shows_or_all(db, request.shows)?;
+            to_json(db.rank_episodes_by_coverage(&shows, &request.options.unwrap_or_default())?)
+        }
+        "analyze_sentence" => {
+            let request: AnalyzeRequest = parse(body)?;
+            to_json(tokenizer.analyze(&request.text)?)
+        }
+        _ => Err(ApiError {
+            status: 404,
+            message: format!("Unknown operation '{}'", operation),
+        }),
+    }
+}
+
+/// The request body as `T`; an empty body is an empty object
+fn parse<T: DeserializeOwned>(body: &str) -> Result<T, ApiError> {
+    let body = if body.trim().is_empty() { "{}" } else { body };
+    serde_json::from_str(body)
+        .map_err(|err| ApiError::bad_request(format!("Invalid request: {}", err)))
+}
+
+fn to_json(value: impl serde::Serialize) -> Result<Value, ApiError> {
+    Ok(serde_json::to_value(value).map_err(Error::from)?)
+}
+
+/// The given show ids, or every show when left out
+fn shows_or_all(db: &mut DbHandler, shows: Option<Vec<i32>>) -> Result<Vec<i32>, Error> {
+    match shows {
+        Some(shows) => Ok(shows),
+        None => Ok(db
+            .get_show_id_name_pairs()?
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()),
+    }
+}
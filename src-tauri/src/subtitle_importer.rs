@@ -4,4 +4,4 @@ mod parsing;
 mod show_configs;
 mod types;
 
-pub use parsing::process_srt_directory;
+pub use parsing::{process_srt_directory, ShowEntry};
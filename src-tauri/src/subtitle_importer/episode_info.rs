@@ -15,6 +15,7 @@ pub struct ShowConfig {
 // Static regex patterns compiled once
 static PARENTHESES_REGEX: OnceLock<Regex> = OnceLock::new();
 static EPISODE_EXX_REGEX: OnceLock<Regex> = OnceLock::new();
+static SEASON_REGEX: OnceLock<Regex> = OnceLock::new();
 
 fn get_parentheses_regex() -> &'static Regex {
     PARENTHESES_REGEX.get_or_init(|| Regex::new(r"\((\d+)\)").expect("Invalid regex"))
@@ -24,6 +25,10 @@ fn get_episode_exx_regex() -> &'static Regex {
     EPISODE_EXX_REGEX.get_or_init(|| Regex::new(r"E(\d+)").expect("Invalid regex"))
 }
 
+fn get_season_regex() -> &'static Regex {
+    SEASON_REGEX.get_or_init(|| Regex::new(r"(?i)S(\d+)E\d+").expect("Invalid regex"))
+}
+
 // Specific extractors
 pub mod extractors {
     use super::*;
@@ -88,3 +93,12 @@ pub fn get_episode_number(
     // Try to parse the extracted number, return None if parsing fails
     number_str.parse().ok()
 }
+
+// Function to extract the season number from names like "Show S02E05"
+pub fn get_season_number(file_path: &Path) -> Option<i32> {
+    let file_name = file_path.file_stem().and_then(|s| s.to_str())?;
+    get_season_regex()
+        .captures(file_name)
+        .and_then(|cap| cap.get(1))
+        .and_then(|m| m.as_str().parse().ok())
+}
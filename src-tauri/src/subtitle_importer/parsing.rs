@@ -1,10 +1,14 @@
-use super::episode_info::{create_show_configs, get_episode_number, get_show_name, ShowConfig};
+use super::episode_info::{
+    create_show_configs, get_episode_number, get_season_number, get_show_name, ShowConfig,
+};
 use super::errors::ParsingError;
 use super::types::{Subtitle, Subtitles, Timestamp};
+use crate::error::Error;
+use crate::jobs::JobContext;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use walkdir::WalkDir;
 
@@ -12,6 +16,7 @@ pub struct SrtEntry {
     pub show_name: String,
     pub episode_name: String,
     pub episode_number: Option<i32>,
+    pub season: Option<i32>,
     pub content: Subtitles,
 }
 
@@ -20,38 +25,43 @@ pub struct ShowEntry {
     pub episodes: Vec<SrtEntry>,
 }
 
-pub fn process_srt_directory(root_dir: &Path) -> Vec<ShowEntry> {
+/// Parses the `.srt` files in `root_dir` and its subdirectories, grouped by show. Files that
+/// can't be parsed are reported to `job` and skipped.
+pub fn process_srt_directory(root_dir: &Path, job: &JobContext) -> Result<Vec<ShowEntry>, Error> {
     let mut show_entries: Vec<ShowEntry> = Vec::new();
     let mut show_name_to_index: HashMap<String, usize> = HashMap::new();
     let configs = create_show_configs();
 
-    let walker = WalkDir::new(root_dir)
+    let paths: Vec<PathBuf> = WalkDir::new(root_dir)
         .sort_by(|a, b| a.file_name().cmp(b.file_name()))
         .min_depth(1)
         .max_depth(2)
-        .into_iter();
-
-    for entry in walker.filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.extension().is_some_and(|ext| ext == "srt") {
-            println!("Processing {:?}...", path.file_name().unwrap());
-            match process_srt_file(path, &configs) {
-                Ok(srt_entry) => {
-                    if let Some(&show_index) = show_name_to_index.get(&srt_entry.show_name) {
-                        show_entries[show_index].episodes.push(srt_entry);
-                    } else {
-                        let show_index = show_entries.len();
-                        show_name_to_index.insert(srt_entry.show_name.clone(), show_index);
-                        show_entries.push(ShowEntry {
-                            name: srt_entry.show_name.clone(),
-                            episodes: vec![srt_entry],
-                        });
-                    }
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "srt"))
+        .collect();
+
+    for (index, path) in paths.iter().enumerate() {
+        job.check_cancelled()?;
+        job.progress("Reading subtitle files", index, paths.len());
+        match process_srt_file(path, &configs) {
+            Ok(srt_entry) => {
+                if let Some(&show_index) = show_name_to_index.get(&srt_entry.show_name) {
+                    show_entries[show_index].episodes.push(srt_entry);
+                } else {
+                    let show_index = show_entries.len();
+                    show_name_to_index.insert(srt_entry.show_name.clone(), show_index);
+                    show_entries.push(ShowEntry {
+                        name: srt_entry.show_name.clone(),
+                        episodes: vec![srt_entry],
+                    });
                 }
-                Err(e) => eprintln!("Error processing file {:?}: {}", path, e),
             }
+            Err(e) => job.file_error(&path.to_string_lossy(), &e.to_string()),
         }
     }
+    job.progress("Reading subtitle files", paths.len(), paths.len());
 
     show_entries.sort_by(|a, b| a.name.cmp(&b.name));
 
@@ -74,7 +84,7 @@ pub fn process_srt_directory(root_dir: &Path) -> Vec<ShowEntry> {
             .sort_by_key(|entry| entry.episode_number.unwrap_or(i32::MAX));
     }
 
-    show_entries
+    Ok(show_entries)
 }
 
 pub fn process_srt_file(
@@ -83,6 +93,7 @@ pub fn process_srt_file(
 ) -> Result<SrtEntry, ParsingError> {
     let show_name = get_show_name(file_path);
     let episode_number = get_episode_number(&show_name, file_path, configs);
+    let season = get_season_number(file_path);
     let episode_name = file_path
         .file_stem()
         .and_then(|name| name.to_str())
@@ -98,6 +109,7 @@ pub fn process_srt_file(
         show_name,
         episode_name,
         episode_number,
+        season,
         content,
     })
 }
@@ -38,15 +38,15 @@ pub fn create_test_transcript(
     handler: &DbHandler,
     episode: &Episode,
     line_id: i32,
-    time_start: &str,
-    time_end: &str,
+    time_start: i64,
+    time_end: i64,
     text: &str,
 ) -> Transcript {
     let mut transcript = Transcript::new(
         episode.id.unwrap(),
         line_id,
-        time_start.to_string(),
-        time_end.to_string(),
+        time_start,
+        time_end,
         text.to_string(),
     );
     transcript.insert(&handler.conn).unwrap();
@@ -57,13 +57,6 @@ pub fn create_test_transcript(
 pub fn create_test_hierarchy(handler: &DbHandler) -> (Show, Episode, Transcript) {
     let show = create_test_show(handler, "Test Show", "Anime");
     let episode = create_test_episode(handler, &show, "Test Episode", Some(1));
-    let transcript = create_test_transcript(
-        handler,
-        &episode,
-        1,
-        "00:00:01,000",
-        "00:00:05,000",
-        "Hello, world!",
-    );
+    let transcript = create_test_transcript(handler, &episode, 1, 1000, 5000, "Hello, world!");
     (show, episode, transcript)
 }
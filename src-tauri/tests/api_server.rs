@@ -0,0 +1,114 @@
+use japanese_subtitle_parser_lib::cli;
+use japanese_subtitle_parser_lib::server::ApiServer;
+use serde_json::{json, Value};
+
+const EXTENSION_ORIGIN: &str = "chrome-extension://abcdefghijklmnop";
+
+#[test]
+fn test_api_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let show_dir = dir.path().join("subtitles").join("Test Show");
+    std::fs::create_dir_all(&show_dir).unwrap();
+    std::fs::write(
+        show_dir.join("Test Show 01.srt"),
+        "1\n00:00:01,000 --> 00:00:02,000\n食べる\n\n2\n00:00:03,000 --> 00:00:04,000\n見る\n",
+    )
+    .unwrap();
+    let db_path = dir.path().join("test.db");
+    let db = db_path.to_str().unwrap().to_string();
+    let subtitles = dir.path().join("subtitles").to_str().unwrap().to_string();
+    cli::run(vec!["--db".into(), db.clone(), "import".into(), subtitles]).unwrap();
+
+    let server = ApiServer::start(&db, 0, vec![EXTENSION_ORIGIN.to_string()]).unwrap();
+    let url = format!("http://127.0.0.1:{}", server.port());
+    let client = reqwest::blocking::Client::new();
+    let post = |operation: &str, body: Value| {
+        let response = client
+            .post(format!("{}/{}", url, operation))
+            .json(&body)
+            .send()
+            .unwrap();
+        (
+            response.status().as_u16(),
+            response.json::<Value>().unwrap(),
+        )
+    };
+
+    let shows = client
+        .get(format!("{}/get_all_shows", url))
+        .send()
+        .unwrap()
+        .json::<Value>()
+        .unwrap();
+    assert_eq!(shows[0][1], "Test Show");
+
+    let (status, result) = post(
+        "search_grammar_pattern_with_context",
+        json!({ "jlpt_level": "n5" }),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(result["results"], json!([]));
+
+    // Nothing is analyzed yet
+    let (status, stats) = post(
+        "get_grammar_pattern_stats",
+        json!({ "pattern_name": "te_form" }),
+    );
+    assert_eq!(status, 200);
+    assert!(stats.is_null());
+
+    // Bad requests are answered with an error instead of failing the server
+    let (status, error) = post("search_grammar_pattern_with_context", json!({}));
+    assert_eq!(status, 400);
+    assert!(error["error"].is_string());
+    let (status, _) = post("get_episode_coverage", json!({ "episode_id": "one" }));
+    assert_eq!(status, 400);
+    let (status, _) = post("delete_everything", json!({}));
+    assert_eq!(status, 404);
+
+    // Only the allowed origin gets through CORS; rebound domains don't get through at all
+    let preflight = |origin: &str| {
+        client
+            .request(
+                reqwest::Method::OPTIONS,
+                format!("{}/search_word_with_context", url),
+            )
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", "POST")
+            .send()
+            .unwrap()
+    };
+    let allowed = preflight(EXTENSION_ORIGIN);
+    assert_eq!(allowed.status().as_u16(), 204);
+    assert_eq!(
+        allowed.headers()["access-control-allow-origin"],
+        EXTENSION_ORIGIN
+    );
+    let foreign = preflight("https://example.com");
+    assert_eq!(foreign.status().as_u16(), 403);
+    assert!(!foreign
+        .headers()
+        .contains_key("access-control-allow-origin"));
+    let foreign = client
+        .post(format!("{}/analyze_sentence", url))
+        .header("Origin", "https://example.com")
+        .json(&json!({ "text": "食べる" }))
+        .send()
+        .unwrap();
+    assert_eq!(foreign.status().as_u16(), 403);
+    let response = client
+        .post(format!("{}/get_all_shows", url))
+        .send()
+        .unwrap();
+    assert!(!response
+        .headers()
+        .contains_key("access-control-allow-origin"));
+    let rebound = client
+        .post(format!("{}/get_all_shows", url))
+        .header("Host", format!("attacker.example:{}", server.port()))
+        .send()
+        .unwrap();
+    assert_eq!(rebound.status().as_u16(), 403);
+
+    server.stop();
+}
@@ -7,12 +7,101 @@ import {
 } from "@/components/ui/accordion";
 import { Button } from "./components/ui/button";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { open } from "@tauri-apps/plugin-dialog";
 
+type JobEvent = {
+  job_id: number;
+  kind: "import" | "analysis";
+} & (
+  | {
+      type: "progress";
+      phase: string;
+      current: number;
+      total: number;
+      eta_secs: number | null;
+    }
+  | { type: "file_error"; path: string; error: string }
+  | {
+      type: "finished";
+      status: "completed" | "cancelled" | "failed";
+      message: string;
+    }
+);
+
+function formatProgress(event: JobEvent & { type: "progress" }) {
+  const eta =
+    event.eta_secs === null
+      ? ""
+      : `, about ${Math.ceil(event.eta_secs / 60)} min left`;
+  return `${event.phase}: ${event.current}/${event.total}${eta}`;
+}
+
 export default function Install() {
   const [expandedItem, setExpandedItem] = createSignal(["item-0"]);
   const [selectedDirectory, setSelectedDirectory] = createSignal<string>("");
   const [isProcessing, setIsProcessing] = createSignal(false);
+  const [jobId, setJobId] = createSignal<number | null>(null);
+  const [progress, setProgress] = createSignal("");
+
+  // Runs a background job command and resolves with its final event. Only one job runs at a
+  // time, so events are matched to it by id once the command returns it.
+  const runJob = async (command: string, args?: Record<string, unknown>) => {
+    const events: JobEvent[] = [];
+    let finish: (event: JobEvent) => void = () => {};
+    const finished = new Promise<JobEvent>((resolve) => (finish = resolve));
+    const handle = (event: JobEvent) => {
+      if (event.type === "progress") {
+        setProgress(formatProgress(event));
+      } else if (event.type === "file_error") {
+        console.warn(`Skipped ${event.path}: ${event.error}`);
+      } else {
+        finish(event);
+      }
+    };
+    const unlisten = await listen<JobEvent>("job", ({ payload }) => {
+      if (jobId() === null) {
+        events.push(payload);
+      } else if (payload.job_id === jobId()) {
+        handle(payload);
+      }
+    });
+    try {
+      setJobId(await invoke<number>(command, args));
+      events.filter((event) => event.job_id === jobId()).forEach(handle);
+      return await finished;
+    } finally {
+      unlisten();
+      setJobId(null);
+      setProgress("");
+    }
+  };
+
+  const cancelJob = async () => {
+    const id = jobId();
+    if (id !== null) {
+      await invoke("cancel_job", { jobId: id });
+    }
+  };
+
+  const runAndReport = async (
+    command: string,
+    args?: Record<string, unknown>,
+  ) => {
+    setIsProcessing(true);
+    try {
+      const result = await runJob(command, args);
+      if (result.type === "finished") {
+        console.log(`${command} ${result.status}:`, result.message);
+        alert(result.message);
+      }
+    } catch (error) {
+      console.error(`Error running ${command}:`, error);
+      alert(`Error: ${error}`);
+    } finally {
+      setIsProcessing(false);
+    }
+  };
 
   const selectDirectory = async () => {
     try {
@@ -36,19 +125,9 @@ export default function Install() {
       return;
     }
 
-    setIsProcessing(true);
-    try {
-      const result = await invoke("import_subtitles_from_directory", {
-        rootDir: selectedDirectory(),
-      });
-      console.log("Process result:", result);
-      alert("Processing completed successfully!");
-    } catch (error) {
-      console.error("Error processing SRT files:", error);
-      alert(`Error: ${error}`);
-    } finally {
-      setIsProcessing(false);
-    }
+    await runAndReport("import_subtitles_from_directory", {
+      rootDir: selectedDirectory(),
+    });
   };
 
   return (
@@ -249,7 +328,13 @@ export default function Install() {
                 >
                   {isProcessing() ? "Processing..." : "Parse Subtitles"}
                 </Button>
+                {isProcessing() && (
+                  <Button variant="outline" onClick={cancelJob}>
+                    Cancel
+                  </Button>
+                )}
               </div>
+              {progress() && <p class="text-center">{progress()}</p>}
               <p>
                 This process may take a while depending on the number of
                 subtitle files.
@@ -265,21 +350,21 @@ export default function Install() {
                 Click the button to generate reverse indexes. This may take a
                 while.
               </p>
-              <Button
-                variant="outline"
-                onClick={async () => {
-                  try {
-                    const result = await invoke("analyze_japanese_transcripts");
-                    console.log("Index result:", result);
-                    alert("Reverse index created successfully!");
-                  } catch (error) {
-                    console.error("Error creating reverse index:", error);
-                    alert(`Error: ${error}`);
-                  }
-                }}
-              >
-                Create Reverse Index
-              </Button>
+              <div class="flex justify-center gap-4 py-4">
+                <Button
+                  variant="outline"
+                  onClick={() => runAndReport("analyze_japanese_transcripts")}
+                  disabled={isProcessing()}
+                >
+                  {isProcessing() ? "Processing..." : "Create Reverse Index"}
+                </Button>
+                {isProcessing() && (
+                  <Button variant="outline" onClick={cancelJob}>
+                    Cancel
+                  </Button>
+                )}
+              </div>
+              {progress() && <p class="text-center">{progress()}</p>}
             </div>
           )}
         </div>
@@ -9,6 +9,50 @@ import {
 } from "./components/ui/checkbox";
 import { Copy } from "lucide-solid";
 
+type Highlight = {
+  start: number;
+  end: number;
+  pattern?: string;
+  confidence?: number;
+};
+
+type ContextLine = {
+  id: number;
+  line_id: number;
+  time_start: number;
+  time_end: number;
+  text: string;
+  highlights: Highlight[];
+};
+
+type EpisodeHits = {
+  episode_id: number;
+  episode: number | null;
+  episode_name: string;
+  season: number | null;
+  lines: ContextLine[];
+};
+
+type ShowHits = {
+  show_id: number;
+  show: string;
+  instances: EpisodeHits[];
+};
+
+type SearchResult = {
+  keyword: string;
+  results: ShowHits[];
+  total: number;
+  next_cursor: string | null;
+  candidates?: Array<{
+    id: number;
+    word: string;
+    reading: string | null;
+    pos: string;
+    occurrences: number;
+  }>;
+};
+
 export default function Search() {
   const [value, setValue] = createSignal("");
   const [shows, setShows] = createSignal<
@@ -30,11 +74,11 @@ export default function Search() {
       .filter((show) => show.checked)
       .map((show) => show.id);
     try {
-      const results = await invoke<string>("search_word_with_context", {
+      const results = await invoke<SearchResult>("search_word_with_context", {
         word: value(),
         enabledShowIds: enabledShows,
       });
-      setSearchResults(results);
+      setSearchResults(JSON.stringify(results, null, 2));
     } catch (error) {
       console.error("Error during search:", error);
     }